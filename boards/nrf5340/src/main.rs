@@ -0,0 +1,189 @@
+#![no_std]
+#![no_main]
+
+use assign_resources::assign_resources;
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_futures::select::{select, Either};
+use embassy_knx::application_layer::{self, ApplicationLayer, ApplicationServiceInd};
+use embassy_knx::group_address_table::GroupAddressTable;
+use embassy_knx::group_object_association_table::GroupObjectAssociationTable;
+use embassy_knx::group_object_table::GroupObjectTable;
+use embassy_knx::ncn51_driver::NCN51Driver;
+use embassy_knx::phy::{PhyChannels, PhyRunner};
+use embassy_knx::settings::ProgrammingMode;
+use embassy_knx::{data_link_layer, frame, network_layer, transport_layer};
+use embassy_nrf::buffered_uarte::{self, BufferedUarte};
+use embassy_nrf::gpio::{Input, Level, Output, OutputDrive, Pull};
+use embassy_nrf::peripherals::{self, SERIAL0, TIMER0};
+use embassy_nrf::{bind_interrupts, uarte};
+use embassy_sync::channel::{Channel, Receiver};
+use embassy_time::Timer;
+use static_cell::StaticCell;
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+
+use {defmt_rtt as _, panic_probe as _};
+
+assign_resources! {
+    uart: UartResources {
+        serial: SERIAL0,
+        timer: TIMER0,
+        ppi_ch1: PPI_CH0,
+        ppi_ch2: PPI_CH1,
+        ppi_group: PPI_GROUP0,
+        rx: P1_10,
+        tx: P1_11,
+    }
+}
+
+bind_interrupts!(struct Irqs {
+    SERIAL0 => buffered_uarte::InterruptHandler<SERIAL0>;
+});
+
+type Serial = BufferedUarte<'static, SERIAL0, TIMER0>;
+
+fn ncn51_uart(resources: UartResources) -> Serial {
+    static RX_BUFFER: StaticCell<[u8; 64]> = StaticCell::new();
+    static TX_BUFFER: StaticCell<[u8; 64]> = StaticCell::new();
+    let mut config = uarte::Config::default();
+    config.parity = uarte::Parity::INCLUDED;
+    config.baudrate = uarte::Baudrate::BAUD38400;
+    BufferedUarte::new(
+        resources.serial,
+        resources.timer,
+        resources.ppi_ch1,
+        resources.ppi_ch2,
+        resources.ppi_group,
+        Irqs,
+        resources.rx,
+        resources.tx,
+        config,
+        RX_BUFFER.init([0; 64]),
+        TX_BUFFER.init([0; 64]),
+    )
+}
+
+#[embassy_executor::task]
+async fn uart_task(runner: PhyRunner<NCN51Driver<Serial>>) -> ! {
+    runner.run().await;
+}
+
+#[embassy_executor::task]
+async fn application_task(application: ApplicationLayer) -> ! {
+    application.run().await;
+}
+
+#[embassy_executor::task]
+async fn indication_task(
+    indications: Receiver<'static, CriticalSectionRawMutex, ApplicationServiceInd, 4>,
+) -> ! {
+    loop {
+        match indications.receive().await {
+            ApplicationServiceInd::GroupValueRead(asap) => {
+                info!("Answered read request on ASAP: {}", asap);
+            }
+            ApplicationServiceInd::GroupValueWriteCon(asap, con) => {
+                info!("Write on ASAP {} confirmed: {}", asap, con);
+            }
+            ApplicationServiceInd::GroupValueWrite(asap, value) => {
+                info!("ASAP {} written with {}", asap, value);
+            }
+            ApplicationServiceInd::GroupValueResponse(asap, value) => {
+                info!("ASAP {} responded with {}", asap, value);
+            }
+            ApplicationServiceInd::IndividualAddressWrite(address) => {
+                info!("Programmed to address {}", address);
+                PROGRAMMING_MODE.set(false);
+            }
+            other => {
+                info!("Indication: {}", other);
+            }
+        }
+    }
+}
+
+/// Programming button toggles programming mode, the programming LED shows it
+#[embassy_executor::task]
+async fn programming_task(mut button: Input<'static>, mut led: Output<'static>) -> ! {
+    loop {
+        match select(button.wait_for_falling_edge(), PROGRAMMING_MODE.wait()).await {
+            Either::First(()) => {
+                PROGRAMMING_MODE.toggle();
+                // Debounce the button
+                Timer::after_millis(50).await;
+            }
+            Either::Second(enabled) => {
+                info!("Programming mode: {}", enabled);
+                led.set_level(if enabled { Level::High } else { Level::Low });
+            }
+        }
+    }
+}
+
+static PROGRAMMING_MODE: ProgrammingMode = ProgrammingMode::new();
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_nrf::init(Default::default());
+    let t = embassy_nrf::pac::DCNF.cpuid().read().cpuid();
+    let mut led = Output::new(p.P1_05, Level::Low, OutputDrive::Standard);
+    let programming_button = Input::new(p.P1_08, Pull::Up);
+    let programming_led = Output::new(p.P1_07, Level::Low, OutputDrive::Standard);
+    let r = split_resources!(p);
+    static SERVICE_CHANNEL_RX: Channel<CriticalSectionRawMutex, ApplicationServiceInd, 4> =
+        Channel::new();
+    static SERVICE_CHANNEL_TX: Channel<
+        CriticalSectionRawMutex,
+        application_layer::ApplicationServiceRes,
+        4,
+    > = Channel::new();
+
+    info!("Hello from rust! We're on core: {}", t);
+    info!("My address: {}", embassy_knx::settings::ADDRESS);
+
+    static PHY_CHANNELS: PhyChannels = PhyChannels::new();
+    static GROUP_ADDRESSES: GroupAddressTable = GroupAddressTable::new();
+    static ASSOCIATIONS: GroupObjectAssociationTable = GroupObjectAssociationTable::new();
+    static GROUP_OBJECTS: GroupObjectTable = GroupObjectTable::new();
+
+    frame::init_frame_pool();
+    // 1/1/98 as the only group address, on group object 0: a switch that can be read,
+    // written and transmits
+    unwrap!(GROUP_ADDRESSES.load(&[0x02, 0x11, 0x78, 0x09, 0x62]));
+    unwrap!(ASSOCIATIONS.load(&[0x01, 0x01, 0x00]));
+    unwrap!(GROUP_OBJECTS.load(&[0x01, 0x50, 0x51, 0x5F, 0x00]));
+    let driver = NCN51Driver::new(ncn51_uart(r.uart));
+    let runner = PhyRunner::new(driver, &PHY_CHANNELS);
+    let data_link = data_link_layer::DataLinkLayer::new(&PHY_CHANNELS);
+    let network = network_layer::NetworkLayer::new(data_link);
+    let transport = transport_layer::TransportLayer::new(network, &GROUP_ADDRESSES);
+    let application = application_layer::ApplicationLayer::with_programming_mode(
+        transport,
+        &ASSOCIATIONS,
+        &GROUP_OBJECTS,
+        SERVICE_CHANNEL_TX.receiver(),
+        SERVICE_CHANNEL_RX.sender(),
+        &PROGRAMMING_MODE,
+    );
+
+    spawner.spawn(uart_task(runner)).unwrap();
+    spawner.spawn(application_task(application)).unwrap();
+    spawner
+        .spawn(programming_task(programming_button, programming_led))
+        .unwrap();
+
+    spawner
+        .spawn(indication_task(SERVICE_CHANNEL_RX.receiver()))
+        .unwrap();
+
+    // The LED shows the switch, reads from the bus are answered with its state
+    const SWITCH: u8 = 0;
+    let switch = GROUP_OBJECTS.object(SWITCH);
+    loop {
+        switch.wait_for_update().await;
+        let on = switch.get().is_some_and(|object| object.value()[0] != 0);
+        info!("Switch set to {}", on);
+        led.set_level(if on { Level::High } else { Level::Low });
+    }
+}
@@ -0,0 +1,172 @@
+//! Runs the stack on a simulated line on the host and opens a KNXnet/IP tunnel to it.
+//!
+//! `cargo run --example knx_ip_tunnel [ip]` listens on port 3671 of `ip` (default 127.0.0.1),
+//! point the scripts in `knxtest/` at that address to talk to the stack.
+
+use async_io::Async;
+use embassy_futures::join::{join, join4};
+use embassy_futures::select::{select, Either};
+use embassy_knx::application_layer::*;
+use embassy_knx::data_link_layer::DataLinkLayer;
+use embassy_knx::data_point::*;
+use embassy_knx::frame::*;
+use embassy_knx::group_address_table::GroupAddressTable;
+use embassy_knx::group_object_association_table::GroupObjectAssociationTable;
+use embassy_knx::group_object_table::GroupObjectTable;
+use embassy_knx::knx_ip::tunnelling::{TunnellingConfig, TunnellingServer};
+use embassy_knx::knx_ip::{DeviceInfo, KNX_IP_MULTICAST_ADDR, KNX_IP_PORT};
+use embassy_knx::network_layer::NetworkLayer;
+use embassy_knx::phy::{PhyChannels, PhyRunner};
+use embassy_knx::settings;
+use embassy_knx::sim_bus::SimBus;
+use embassy_knx::transport_layer::TransportLayer;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embedded_io_async::ErrorKind;
+use embedded_nal_async::UnconnectedUdp;
+use heapless::pool::boxed::BoxBlock;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+
+struct HostUdp {
+    socket: Async<UdpSocket>,
+    local: SocketAddr,
+}
+
+impl UnconnectedUdp for HostUdp {
+    type Error = ErrorKind;
+
+    async fn send(
+        &mut self,
+        _local: SocketAddr,
+        remote: SocketAddr,
+        data: &[u8],
+    ) -> Result<(), Self::Error> {
+        self.socket
+            .send_to(data, remote)
+            .await
+            .map(|_| ())
+            .map_err(|_| ErrorKind::Other)
+    }
+
+    async fn receive_into(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<(usize, SocketAddr, SocketAddr), Self::Error> {
+        let (len, remote) = self
+            .socket
+            .recv_from(buffer)
+            .await
+            .map_err(|_| ErrorKind::Other)?;
+        Ok((len, self.local, remote))
+    }
+}
+
+fn main() {
+    static BUS: SimBus<2> = SimBus::new();
+    static DEVICE_CHANNELS: PhyChannels = PhyChannels::new();
+    static TUNNEL_CHANNELS: PhyChannels = PhyChannels::new();
+    static IND: Channel<CriticalSectionRawMutex, ApplicationServiceInd, 4> = Channel::new();
+    static RES: Channel<CriticalSectionRawMutex, ApplicationServiceRes, 4> = Channel::new();
+    static GROUP_ADDRESSES: GroupAddressTable = GroupAddressTable::new();
+    static ASSOCIATIONS: GroupObjectAssociationTable = GroupObjectAssociationTable::new();
+    static GROUP_OBJECTS: GroupObjectTable = GroupObjectTable::new();
+
+    let ip: Ipv4Addr = match std::env::args().nth(1) {
+        Some(ip) => ip.parse().expect("invalid IPv4 address"),
+        None => Ipv4Addr::LOCALHOST,
+    };
+    let endpoint = SocketAddrV4::new(ip, KNX_IP_PORT);
+    let socket = UdpSocket::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, KNX_IP_PORT))
+        .expect("port 3671 in use");
+    if let Err(e) = socket.join_multicast_v4(&KNX_IP_MULTICAST_ADDR, &ip) {
+        println!("Search requests will not be answered: {}", e);
+    }
+    let socket = HostUdp {
+        socket: Async::new(socket).unwrap(),
+        local: endpoint.into(),
+    };
+
+    init_frame_pool();
+    // 1/1/98 as the only group address, on group object 0: a switch that can be read,
+    // written and transmits
+    GROUP_ADDRESSES
+        .load(&[0x02, 0x11, 0x78, 0x09, 0x62])
+        .unwrap();
+    ASSOCIATIONS.load(&[0x01, 0x01, 0x00]).unwrap();
+    GROUP_OBJECTS.load(&[0x01, 0x50, 0x51, 0x5F, 0x00]).unwrap();
+    extend_frame_pool(Box::leak(Box::new([const { BoxBlock::new() }; 32])));
+
+    let device_runner = PhyRunner::new(BUS.port(0), &DEVICE_CHANNELS);
+    let application = ApplicationLayer::new(
+        TransportLayer::new(
+            NetworkLayer::new(DataLinkLayer::new(&DEVICE_CHANNELS)),
+            &GROUP_ADDRESSES,
+        ),
+        &ASSOCIATIONS,
+        &GROUP_OBJECTS,
+        RES.receiver(),
+        IND.sender(),
+    );
+    let tunnel_runner = PhyRunner::new(BUS.port(1), &TUNNEL_CHANNELS);
+    let server = TunnellingServer::new(
+        socket,
+        DataLinkLayer::new(&TUNNEL_CHANNELS),
+        TunnellingConfig {
+            endpoint,
+            tunnel_address: IndividualAddress::from_parts(1, 1, 100),
+            device: DeviceInfo {
+                individual_address: IndividualAddress::from_parts(1, 1, 0),
+                serial_number: [0x00, 0x00, 0x00, 0x00, 0x00, 0x01],
+                mac_address: [0x02, 0x00, 0x00, 0x00, 0x00, 0x01],
+                name: "embassy-knx",
+            },
+        },
+    );
+    const SWITCH: u8 = 0;
+    let switch = GROUP_OBJECTS.object(SWITCH);
+    // Reads of 1/1/98 are answered with this until it is written
+    switch.set(&DataPoint::B1(B1::new(true))).unwrap();
+    let indications = async {
+        loop {
+            match IND.receive().await {
+                ApplicationServiceInd::GroupValueRead(asap) => {
+                    println!("Answered read request on ASAP: {}", asap);
+                }
+                ApplicationServiceInd::GroupValueWriteCon(asap, con) => {
+                    println!("Write on ASAP {} confirmed: {:?}", asap, con);
+                }
+                ApplicationServiceInd::GroupValueWrite(asap, value) => {
+                    println!("ASAP {} written with {:?}", asap, value);
+                }
+                ApplicationServiceInd::GroupValueResponse(asap, value) => {
+                    println!("ASAP {} responded with {:?}", asap, value);
+                }
+                other => {
+                    println!("Indication: {:?}", other);
+                }
+            }
+        }
+    };
+    let user = async {
+        loop {
+            switch.wait_for_update().await;
+            println!("Switch set to {:?}", switch.value());
+        }
+    };
+
+    println!(
+        "Device {:?} listening for tunnelling connections on {}",
+        settings::ADDRESS,
+        endpoint
+    );
+    let stack = join4(
+        device_runner.run(),
+        application.run(),
+        tunnel_runner.run(),
+        server.run(),
+    );
+    match async_io::block_on(select(stack, join(indications, user))) {
+        Either::First(_) => unreachable!(),
+        Either::Second(_) => {}
+    }
+}
@@ -25,7 +25,7 @@ const testStatus = new DataPoints.Switch(
 
 testStatus.bind(knxClient);
 
-const ip = "10.0.20.185"
+const ip = process.env.KNX_IP || "10.0.20.185"
 const port = 3671
 console.log("Connecting to ", ip, port);
 knxClient.connectAsync(ip, port).then(() => console.log("Connected through channel id ", knxClient.channelID)).then(() => console.log("Reading test status"))
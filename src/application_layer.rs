@@ -1,104 +1,1336 @@
+use crate::data_link_layer::ConStatus;
 use crate::data_point::*;
-use crate::transport_layer::{TransportLayer, TransportServiceInd};
+use crate::group_object::{GroupObject, GroupObjectState, MAX_VALUE_SIZE};
+use crate::group_object_association_table::GroupObjectAssociationTable;
+use crate::group_object_table::{GroupObjectReq, GroupObjectTable};
+use crate::phy::TransferError;
+use crate::settings::ProgrammingMode;
+use crate::transport_layer::{TransportLayer, TransportServiceInd, TransportServiceReq};
 use crate::{frame::*, transport_layer};
-use defmt::*;
-use embassy_futures::select::{select, Either};
-use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
+use embassy_futures::select::{select3, Either3};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::channel::{Receiver, Sender};
+use heapless::Vec;
 
+/// Value carried by an A_GroupValue_Write or A_GroupValue_Response.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GroupValue {
+    /// Up to 6 bits, sent in the APCI byte.
+    Short(u8),
+    /// The bytes following the APCI.
+    Long(Vec<u8, MAX_VALUE_SIZE>),
+}
+
+impl GroupValue {
+    /// Takes the value from the APDU data of a frame, starting at the APCI byte.
+    pub fn from_apdu(apdu: &[u8]) -> Result<Self, FrameError> {
+        match apdu {
+            [] => Err(FrameError::InvalidLength),
+            [short] => Ok(Self::Short(short & 0x3F)),
+            [_, data @ ..] => Vec::from_slice(data)
+                .map(Self::Long)
+                .map_err(|_| FrameError::TooLong(data.len())),
+        }
+    }
+
+    /// Decodes the value as `dpt`, which has to match its size and form.
+    pub fn read(&self, dpt: DataPointType) -> Result<DataPoint, FrameError> {
+        match self {
+            Self::Short(value) if dpt.is_short() => DataPoint::from_value(dpt, &[*value]),
+            Self::Long(value) if !dpt.is_short() => DataPoint::from_value(dpt, value),
+            _ => Err(FrameError::InvalidLength),
+        }
+    }
+}
+
+/// Longest APDU an extended frame carries, from the TPCI byte on.
+const MAX_APDU_LENGTH: usize = 255;
+
+/// Application layer service of a frame with its parameters, as numbered by the APCI.
+///
+/// Variable length data is borrowed from the frame the APDU was read from.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Apdu<'a> {
+    GroupValueRead,
+    GroupValueResponse(GroupValue),
+    GroupValueWrite(GroupValue),
+    IndividualAddressWrite(IndividualAddress),
+    IndividualAddressRead,
+    IndividualAddressResponse,
+    AdcRead {
+        channel: u8,
+        count: u8,
+    },
+    AdcResponse {
+        channel: u8,
+        count: u8,
+        sum: u16,
+    },
+    SystemNetworkParameterRead {
+        object_type: u16,
+        /// 12 bits.
+        pid: u16,
+        operand: &'a [u8],
+    },
+    SystemNetworkParameterResponse {
+        object_type: u16,
+        pid: u16,
+        /// The operand of the read followed by the result.
+        data: &'a [u8],
+    },
+    SystemNetworkParameterWrite {
+        object_type: u16,
+        pid: u16,
+        value: &'a [u8],
+    },
+    MemoryRead {
+        /// Up to 63 bytes.
+        number: u8,
+        address: u16,
+    },
+    /// No data if the memory couldn't be read.
+    MemoryResponse {
+        address: u16,
+        data: &'a [u8],
+    },
+    MemoryWrite {
+        address: u16,
+        data: &'a [u8],
+    },
+    UserMemoryRead {
+        /// Up to 15 bytes.
+        number: u8,
+        /// 20 bits.
+        address: u32,
+    },
+    UserMemoryResponse {
+        address: u32,
+        data: &'a [u8],
+    },
+    UserMemoryWrite {
+        address: u32,
+        data: &'a [u8],
+    },
+    UserManufacturerInfoRead,
+    UserManufacturerInfoResponse {
+        manufacturer_id: u8,
+        data: u16,
+    },
+    FunctionPropertyCommand {
+        object_index: u8,
+        pid: u8,
+        data: &'a [u8],
+    },
+    FunctionPropertyStateRead {
+        object_index: u8,
+        pid: u8,
+        data: &'a [u8],
+    },
+    /// The return code followed by the data, nothing if the property doesn't exist.
+    FunctionPropertyStateResponse {
+        object_index: u8,
+        pid: u8,
+        data: &'a [u8],
+    },
+    DeviceDescriptorRead {
+        descriptor_type: u8,
+    },
+    /// No descriptor if the type isn't supported.
+    DeviceDescriptorResponse {
+        descriptor_type: u8,
+        descriptor: &'a [u8],
+    },
+    Restart,
+    MasterReset {
+        erase_code: u8,
+        channel: u8,
+    },
+    /// Answers [`Apdu::MasterReset`].
+    RestartResponse {
+        error_code: u8,
+        process_time: u16,
+    },
+    AuthorizeRequest {
+        key: u32,
+    },
+    AuthorizeResponse {
+        level: u8,
+    },
+    KeyWrite {
+        level: u8,
+        key: u32,
+    },
+    KeyResponse {
+        level: u8,
+    },
+    PropertyValueRead {
+        object_index: u8,
+        pid: u8,
+        /// 4 bits, 0 in a response if the property couldn't be read.
+        count: u8,
+        /// 12 bits.
+        start_index: u16,
+    },
+    PropertyValueResponse {
+        object_index: u8,
+        pid: u8,
+        count: u8,
+        start_index: u16,
+        data: &'a [u8],
+    },
+    PropertyValueWrite {
+        object_index: u8,
+        pid: u8,
+        count: u8,
+        start_index: u16,
+        data: &'a [u8],
+    },
+    /// The property is looked up by `property_index` if `pid` is 0.
+    PropertyDescriptionRead {
+        object_index: u8,
+        pid: u8,
+        property_index: u8,
+    },
+    PropertyDescriptionResponse {
+        object_index: u8,
+        pid: u8,
+        property_index: u8,
+        write_enable: bool,
+        /// 6 bits.
+        property_type: u8,
+        /// 12 bits.
+        max_elements: u16,
+        /// Read level in the high, write level in the low nibble.
+        access: u8,
+    },
+    NetworkParameterRead {
+        object_type: u16,
+        pid: u8,
+        test_info: &'a [u8],
+    },
+    NetworkParameterResponse {
+        object_type: u16,
+        pid: u8,
+        /// The test info of the read followed by the result.
+        data: &'a [u8],
+    },
+    NetworkParameterWrite {
+        object_type: u16,
+        pid: u8,
+        value: &'a [u8],
+    },
+    IndividualAddressSerialNumberRead {
+        serial_number: [u8; 6],
+    },
+    IndividualAddressSerialNumberResponse {
+        serial_number: [u8; 6],
+        domain_address: u16,
+    },
+    IndividualAddressSerialNumberWrite {
+        serial_number: [u8; 6],
+        address: IndividualAddress,
+    },
+    LinkRead {
+        group_object: u8,
+        /// 4 bits.
+        start_index: u8,
+    },
+    LinkResponse {
+        group_object: u8,
+        /// 4 bits, index of the sending address in `addresses`, 0 if there is none.
+        sending_address: u8,
+        /// 4 bits.
+        start_index: u8,
+        /// Two bytes per group address.
+        addresses: &'a [u8],
+    },
+    LinkWrite {
+        group_object: u8,
+        flags: u8,
+        address: GroupAddress,
+    },
+}
+
+/// The parameters of a service that only has fixed size ones.
+fn fixed<const N: usize>(data: &[u8]) -> Result<[u8; N], FrameError> {
+    data.try_into().map_err(|_| FrameError::LengthMismatch {
+        expected: N,
+        actual: data.len(),
+    })
+}
+
+/// The fixed size parameters of a service and the data following them.
+fn split<const N: usize>(data: &[u8]) -> Result<([u8; N], &[u8]), FrameError> {
+    if data.len() < N {
+        return Err(FrameError::TooShort(data.len()));
+    }
+    let (head, data) = data.split_at(N);
+    Ok((fixed(head)?, data))
+}
+
+/// Data whose length is given by the number in the APCI.
+fn counted(number: u8, data: &[u8]) -> Result<&[u8], FrameError> {
+    if data.len() == number as usize {
+        Ok(data)
+    } else {
+        Err(FrameError::LengthMismatch {
+            expected: number as usize,
+            actual: data.len(),
+        })
+    }
+}
+
+impl<'a> Apdu<'a> {
+    /// Decodes the APDU of `frame`. Fails with [`FrameError::InvalidApci`] for unknown
+    /// services and with a length error if the parameters don't fit the service.
+    pub fn from_frame(frame: &'a Frame) -> Result<Self, FrameError> {
+        let apdu = frame.apdu_data();
+        let Some((first, data)) = apdu.split_first() else {
+            return Err(FrameError::InvalidLength);
+        };
+        let apci = frame.apci(ApciBits::Ten);
+        // The parameter some 4 bit services carry in the APCI
+        let low = first & 0x3F;
+        let apdu = match apci {
+            0x000..=0x03F => Self::GroupValueRead,
+            0x040..=0x07F => Self::GroupValueResponse(GroupValue::from_apdu(apdu)?),
+            0x080..=0x0BF => Self::GroupValueWrite(GroupValue::from_apdu(apdu)?),
+            0x0C0..=0x0FF => {
+                Self::IndividualAddressWrite(IndividualAddress::from(&fixed::<2>(data)?[..]))
+            }
+            0x100..=0x13F => {
+                fixed::<0>(data)?;
+                Self::IndividualAddressRead
+            }
+            0x140..=0x17F => {
+                fixed::<0>(data)?;
+                Self::IndividualAddressResponse
+            }
+            0x180..=0x1BF => {
+                let [count] = fixed(data)?;
+                Self::AdcRead {
+                    channel: low,
+                    count,
+                }
+            }
+            0x1C8..=0x1CA => {
+                let ([t0, t1, p0, p1], data) = split(data)?;
+                let object_type = u16::from_be_bytes([t0, t1]);
+                let pid = u16::from_be_bytes([p0, p1]) >> 4;
+                match apci {
+                    0x1C8 => Self::SystemNetworkParameterRead {
+                        object_type,
+                        pid,
+                        operand: data,
+                    },
+                    0x1C9 => Self::SystemNetworkParameterResponse {
+                        object_type,
+                        pid,
+                        data,
+                    },
+                    _ => Self::SystemNetworkParameterWrite {
+                        object_type,
+                        pid,
+                        value: data,
+                    },
+                }
+            }
+            0x1C0..=0x1FF => {
+                let [count, s0, s1] = fixed(data)?;
+                Self::AdcResponse {
+                    channel: low,
+                    count,
+                    sum: u16::from_be_bytes([s0, s1]),
+                }
+            }
+            0x200..=0x23F => {
+                let [a0, a1] = fixed(data)?;
+                Self::MemoryRead {
+                    number: low,
+                    address: u16::from_be_bytes([a0, a1]),
+                }
+            }
+            0x240..=0x2BF => {
+                let ([a0, a1], data) = split(data)?;
+                let address = u16::from_be_bytes([a0, a1]);
+                let data = counted(low, data)?;
+                if apci < 0x280 {
+                    Self::MemoryResponse { address, data }
+                } else {
+                    Self::MemoryWrite { address, data }
+                }
+            }
+            0x2C0..=0x2C2 => {
+                let ([extension, a0, a1], data) = split(data)?;
+                let number = extension & 0xF;
+                let address = (extension as u32 >> 4) << 16 | u16::from_be_bytes([a0, a1]) as u32;
+                match apci {
+                    0x2C0 => {
+                        fixed::<0>(data)?;
+                        Self::UserMemoryRead { number, address }
+                    }
+                    0x2C1 => Self::UserMemoryResponse {
+                        address,
+                        data: counted(number, data)?,
+                    },
+                    _ => Self::UserMemoryWrite {
+                        address,
+                        data: counted(number, data)?,
+                    },
+                }
+            }
+            0x2C5 => {
+                fixed::<0>(data)?;
+                Self::UserManufacturerInfoRead
+            }
+            0x2C6 => {
+                let [manufacturer_id, d0, d1] = fixed(data)?;
+                Self::UserManufacturerInfoResponse {
+                    manufacturer_id,
+                    data: u16::from_be_bytes([d0, d1]),
+                }
+            }
+            0x2C7..=0x2C9 => {
+                let ([object_index, pid], data) = split(data)?;
+                match apci {
+                    0x2C7 => Self::FunctionPropertyCommand {
+                        object_index,
+                        pid,
+                        data,
+                    },
+                    0x2C8 => Self::FunctionPropertyStateRead {
+                        object_index,
+                        pid,
+                        data,
+                    },
+                    _ => Self::FunctionPropertyStateResponse {
+                        object_index,
+                        pid,
+                        data,
+                    },
+                }
+            }
+            0x300..=0x33F => {
+                fixed::<0>(data)?;
+                Self::DeviceDescriptorRead {
+                    descriptor_type: low,
+                }
+            }
+            0x340..=0x37F => Self::DeviceDescriptorResponse {
+                descriptor_type: low,
+                descriptor: data,
+            },
+            0x380 => {
+                fixed::<0>(data)?;
+                Self::Restart
+            }
+            0x381 => {
+                let [erase_code, channel] = fixed(data)?;
+                Self::MasterReset {
+                    erase_code,
+                    channel,
+                }
+            }
+            0x3A1 => {
+                let [error_code, t0, t1] = fixed(data)?;
+                Self::RestartResponse {
+                    error_code,
+                    process_time: u16::from_be_bytes([t0, t1]),
+                }
+            }
+            0x3D1 => {
+                let [_, k0, k1, k2, k3] = fixed(data)?;
+                Self::AuthorizeRequest {
+                    key: u32::from_be_bytes([k0, k1, k2, k3]),
+                }
+            }
+            0x3D2 => {
+                let [level] = fixed(data)?;
+                Self::AuthorizeResponse { level }
+            }
+            0x3D3 => {
+                let [level, k0, k1, k2, k3] = fixed(data)?;
+                Self::KeyWrite {
+                    level,
+                    key: u32::from_be_bytes([k0, k1, k2, k3]),
+                }
+            }
+            0x3D4 => {
+                let [level] = fixed(data)?;
+                Self::KeyResponse { level }
+            }
+            0x3D5..=0x3D7 => {
+                let ([object_index, pid, c, s], data) = split(data)?;
+                let count = c >> 4;
+                let start_index = u16::from_be_bytes([c & 0xF, s]);
+                match apci {
+                    0x3D5 => {
+                        fixed::<0>(data)?;
+                        Self::PropertyValueRead {
+                            object_index,
+                            pid,
+                            count,
+                            start_index,
+                        }
+                    }
+                    0x3D6 => Self::PropertyValueResponse {
+                        object_index,
+                        pid,
+                        count,
+                        start_index,
+                        data,
+                    },
+                    _ => Self::PropertyValueWrite {
+                        object_index,
+                        pid,
+                        count,
+                        start_index,
+                        data,
+                    },
+                }
+            }
+            0x3D8 => {
+                let [object_index, pid, property_index] = fixed(data)?;
+                Self::PropertyDescriptionRead {
+                    object_index,
+                    pid,
+                    property_index,
+                }
+            }
+            0x3D9 => {
+                let [object_index, pid, property_index, t, m0, m1, access] = fixed(data)?;
+                Self::PropertyDescriptionResponse {
+                    object_index,
+                    pid,
+                    property_index,
+                    write_enable: t & 0x80 != 0,
+                    property_type: t & 0x3F,
+                    max_elements: u16::from_be_bytes([m0 & 0xF, m1]),
+                    access,
+                }
+            }
+            0x3DA | 0x3DB | 0x3E4 => {
+                let ([t0, t1, pid], data) = split(data)?;
+                let object_type = u16::from_be_bytes([t0, t1]);
+                match apci {
+                    0x3DA => Self::NetworkParameterRead {
+                        object_type,
+                        pid,
+                        test_info: data,
+                    },
+                    0x3DB => Self::NetworkParameterResponse {
+                        object_type,
+                        pid,
+                        data,
+                    },
+                    _ => Self::NetworkParameterWrite {
+                        object_type,
+                        pid,
+                        value: data,
+                    },
+                }
+            }
+            0x3DC => Self::IndividualAddressSerialNumberRead {
+                serial_number: fixed(data)?,
+            },
+            0x3DD => {
+                let (serial_number, data) = split(data)?;
+                let [d0, d1, _, _] = fixed(data)?;
+                Self::IndividualAddressSerialNumberResponse {
+                    serial_number,
+                    domain_address: u16::from_be_bytes([d0, d1]),
+                }
+            }
+            0x3DE => {
+                let (serial_number, data) = split(data)?;
+                let [a0, a1, _, _, _, _] = fixed(data)?;
+                Self::IndividualAddressSerialNumberWrite {
+                    serial_number,
+                    address: IndividualAddress::from(&[a0, a1][..]),
+                }
+            }
+            0x3E5 => {
+                let [group_object, start_index] = fixed(data)?;
+                Self::LinkRead {
+                    group_object,
+                    start_index: start_index & 0xF,
+                }
+            }
+            0x3E6 => {
+                let ([group_object, indices], addresses) = split(data)?;
+                if addresses.len() % 2 != 0 {
+                    return Err(FrameError::InvalidLength);
+                }
+                Self::LinkResponse {
+                    group_object,
+                    sending_address: indices >> 4,
+                    start_index: indices & 0xF,
+                    addresses,
+                }
+            }
+            0x3E7 => {
+                let [group_object, flags, g0, g1] = fixed(data)?;
+                Self::LinkWrite {
+                    group_object,
+                    flags,
+                    address: GroupAddress::from(&[g0, g1][..]),
+                }
+            }
+            _ => return Err(FrameError::InvalidApci(apci)),
+        };
+        Ok(apdu)
+    }
+
+    /// Encodes the service into a new frame, the addresses and the TPCI are left to the
+    /// lower layers.
+    pub fn to_frame(&self) -> Result<Frame, FrameError> {
+        match self {
+            Self::GroupValueRead => encode(0x000, &[]),
+            Self::GroupValueResponse(value) => encode_group_value(0x040, value),
+            Self::GroupValueWrite(value) => encode_group_value(0x080, value),
+            Self::IndividualAddressWrite(address) => {
+                let mut buf = [0; 2];
+                address.write(&mut buf);
+                encode(0x0C0, &[&buf])
+            }
+            Self::IndividualAddressRead => encode(0x100, &[]),
+            Self::IndividualAddressResponse => encode(0x140, &[]),
+            Self::AdcRead { channel, count } => encode(0x180 | short(*channel), &[&[*count]]),
+            Self::AdcResponse {
+                channel,
+                count,
+                sum,
+            } => encode(0x1C0 | short(*channel), &[&[*count], &sum.to_be_bytes()]),
+            Self::SystemNetworkParameterRead {
+                object_type,
+                pid,
+                operand: data,
+            } => encode_system_parameter(0x1C8, *object_type, *pid, data),
+            Self::SystemNetworkParameterResponse {
+                object_type,
+                pid,
+                data,
+            } => encode_system_parameter(0x1C9, *object_type, *pid, data),
+            Self::SystemNetworkParameterWrite {
+                object_type,
+                pid,
+                value: data,
+            } => encode_system_parameter(0x1CA, *object_type, *pid, data),
+            Self::MemoryRead { number, address } => {
+                encode(0x200 | short(*number), &[&address.to_be_bytes()])
+            }
+            Self::MemoryResponse { address, data } => {
+                encode(0x240 | count(data, 0x3F)?, &[&address.to_be_bytes(), data])
+            }
+            Self::MemoryWrite { address, data } => {
+                encode(0x280 | count(data, 0x3F)?, &[&address.to_be_bytes(), data])
+            }
+            Self::UserMemoryRead { number, address } => {
+                encode_user_memory(0x2C0, *number & 0xF, *address, &[])
+            }
+            Self::UserMemoryResponse { address, data } => {
+                encode_user_memory(0x2C1, count(data, 0xF)? as u8, *address, data)
+            }
+            Self::UserMemoryWrite { address, data } => {
+                encode_user_memory(0x2C2, count(data, 0xF)? as u8, *address, data)
+            }
+            Self::UserManufacturerInfoRead => encode(0x2C5, &[]),
+            Self::UserManufacturerInfoResponse {
+                manufacturer_id,
+                data,
+            } => encode(0x2C6, &[&[*manufacturer_id], &data.to_be_bytes()]),
+            Self::FunctionPropertyCommand {
+                object_index,
+                pid,
+                data,
+            } => encode(0x2C7, &[&[*object_index, *pid], data]),
+            Self::FunctionPropertyStateRead {
+                object_index,
+                pid,
+                data,
+            } => encode(0x2C8, &[&[*object_index, *pid], data]),
+            Self::FunctionPropertyStateResponse {
+                object_index,
+                pid,
+                data,
+            } => encode(0x2C9, &[&[*object_index, *pid], data]),
+            Self::DeviceDescriptorRead { descriptor_type } => {
+                encode(0x300 | short(*descriptor_type), &[])
+            }
+            Self::DeviceDescriptorResponse {
+                descriptor_type,
+                descriptor,
+            } => encode(0x340 | short(*descriptor_type), &[descriptor]),
+            Self::Restart => encode(0x380, &[]),
+            Self::MasterReset {
+                erase_code,
+                channel,
+            } => encode(0x381, &[&[*erase_code, *channel]]),
+            Self::RestartResponse {
+                error_code,
+                process_time,
+            } => encode(0x3A1, &[&[*error_code], &process_time.to_be_bytes()]),
+            Self::AuthorizeRequest { key } => encode(0x3D1, &[&[0], &key.to_be_bytes()]),
+            Self::AuthorizeResponse { level } => encode(0x3D2, &[&[*level]]),
+            Self::KeyWrite { level, key } => encode(0x3D3, &[&[*level], &key.to_be_bytes()]),
+            Self::KeyResponse { level } => encode(0x3D4, &[&[*level]]),
+            Self::PropertyValueRead {
+                object_index,
+                pid,
+                count,
+                start_index,
+            } => encode_property(0x3D5, *object_index, *pid, *count, *start_index, &[]),
+            Self::PropertyValueResponse {
+                object_index,
+                pid,
+                count,
+                start_index,
+                data,
+            } => encode_property(0x3D6, *object_index, *pid, *count, *start_index, data),
+            Self::PropertyValueWrite {
+                object_index,
+                pid,
+                count,
+                start_index,
+                data,
+            } => encode_property(0x3D7, *object_index, *pid, *count, *start_index, data),
+            Self::PropertyDescriptionRead {
+                object_index,
+                pid,
+                property_index,
+            } => encode(0x3D8, &[&[*object_index, *pid, *property_index]]),
+            Self::PropertyDescriptionResponse {
+                object_index,
+                pid,
+                property_index,
+                write_enable,
+                property_type,
+                max_elements,
+                access,
+            } => {
+                let t = (*write_enable as u8) << 7 | property_type & 0x3F;
+                let max_elements = (max_elements & 0xFFF).to_be_bytes();
+                encode(
+                    0x3D9,
+                    &[
+                        &[*object_index, *pid, *property_index, t],
+                        &max_elements,
+                        &[*access],
+                    ],
+                )
+            }
+            Self::NetworkParameterRead {
+                object_type,
+                pid,
+                test_info: data,
+            } => encode(0x3DA, &[&object_type.to_be_bytes(), &[*pid], data]),
+            Self::NetworkParameterResponse {
+                object_type,
+                pid,
+                data,
+            } => encode(0x3DB, &[&object_type.to_be_bytes(), &[*pid], data]),
+            Self::NetworkParameterWrite {
+                object_type,
+                pid,
+                value: data,
+            } => encode(0x3E4, &[&object_type.to_be_bytes(), &[*pid], data]),
+            Self::IndividualAddressSerialNumberRead { serial_number } => {
+                encode(0x3DC, &[serial_number])
+            }
+            Self::IndividualAddressSerialNumberResponse {
+                serial_number,
+                domain_address,
+            } => encode(
+                0x3DD,
+                &[serial_number, &domain_address.to_be_bytes(), &[0; 2]],
+            ),
+            Self::IndividualAddressSerialNumberWrite {
+                serial_number,
+                address,
+            } => {
+                let mut buf = [0; 2];
+                address.write(&mut buf);
+                encode(0x3DE, &[serial_number, &buf, &[0; 4]])
+            }
+            Self::LinkRead {
+                group_object,
+                start_index,
+            } => encode(0x3E5, &[&[*group_object, start_index & 0xF]]),
+            Self::LinkResponse {
+                group_object,
+                sending_address,
+                start_index,
+                addresses,
+            } => {
+                let indices = (sending_address & 0xF) << 4 | start_index & 0xF;
+                encode(0x3E6, &[&[*group_object, indices], addresses])
+            }
+            Self::LinkWrite {
+                group_object,
+                flags,
+                address,
+            } => {
+                let mut buf = [0; 2];
+                address.write(&mut buf);
+                encode(0x3E7, &[&[*group_object, *flags], &buf])
+            }
+        }
+    }
+}
+
+/// A parameter carried in the low 6 bits of the APCI.
+fn short(value: u8) -> u16 {
+    (value & 0x3F) as u16
+}
+
+/// The number of bytes in `data` for the APCI or the extension byte, which can't be more
+/// than `max`.
+fn count(data: &[u8], max: u16) -> Result<u16, FrameError> {
+    match u16::try_from(data.len()) {
+        Ok(n) if n <= max => Ok(n),
+        _ => Err(FrameError::TooLong(data.len())),
+    }
+}
+
+/// Frame with the APCI followed by the parameters.
+fn encode(apci: u16, parameters: &[&[u8]]) -> Result<Frame, FrameError> {
+    let mut apdu: Vec<u8, MAX_APDU_LENGTH> = Vec::new();
+    // The two high bits of the APCI share the byte with the TPCI
+    unwrap!(apdu.extend_from_slice(&apci.to_be_bytes()));
+    for parameter in parameters {
+        apdu.extend_from_slice(parameter)
+            .map_err(|_| FrameError::TooLong(apdu.len() + parameter.len()))?;
+    }
+    Frame::from_apdu(&apdu)
+}
+
+fn encode_group_value(apci: u16, value: &GroupValue) -> Result<Frame, FrameError> {
+    match value {
+        GroupValue::Short(value) => encode(apci | short(*value), &[]),
+        GroupValue::Long(data) => encode(apci, &[data]),
+    }
+}
+
+fn encode_system_parameter(
+    apci: u16,
+    object_type: u16,
+    pid: u16,
+    data: &[u8],
+) -> Result<Frame, FrameError> {
+    let pid = ((pid & 0xFFF) << 4).to_be_bytes();
+    encode(apci, &[&object_type.to_be_bytes(), &pid, data])
+}
+
+fn encode_user_memory(
+    apci: u16,
+    number: u8,
+    address: u32,
+    data: &[u8],
+) -> Result<Frame, FrameError> {
+    let extension = ((address >> 16) as u8 & 0xF) << 4 | number;
+    encode(apci, &[&[extension], &(address as u16).to_be_bytes(), data])
+}
+
+fn encode_property(
+    apci: u16,
+    object_index: u8,
+    pid: u8,
+    count: u8,
+    start_index: u16,
+    data: &[u8],
+) -> Result<Frame, FrameError> {
+    let [s0, s1] = (start_index & 0xFFF).to_be_bytes();
+    encode(
+        apci,
+        &[&[object_index, pid, (count & 0xF) << 4 | s0, s1], data],
+    )
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ApplicationServiceInd {
+    /// A_GroupValue_Read.ind, already answered with the value of the group object.
     GroupValueRead(u8 /*ASAP */),
+    /// A_GroupValue_Write.ind, the group object already has the new value.
+    GroupValueWrite(u8 /*ASAP */, GroupValue),
+    /// A_GroupValue_Response.ind, the group object already has the new value.
+    GroupValueResponse(u8 /*ASAP */, GroupValue),
+    /// A_GroupValue_Write.con, whether the write was acknowledged on the bus.
+    GroupValueWriteCon(u8 /*ASAP */, Result<ConStatus, TransferError>),
+    /// A_IndividualAddress_Write.ind, the device already uses the new address. It has to be
+    /// stored to survive a restart.
+    IndividualAddressWrite(IndividualAddress),
+    /// A device opened a connection to us.
+    Connect(IndividualAddress),
+    /// The connection to the device is closed, by either side or by a timeout.
+    Disconnect(IndividualAddress),
+    /// APDU received on the open connection, already acknowledged.
+    DataConnected(Frame),
+    /// Answers [`ApplicationServiceRes::Connect`], whether the connection is open.
+    ConnectCon(IndividualAddress, Result<ConStatus, TransferError>),
+    /// Answers [`ApplicationServiceRes::Disconnect`].
+    DisconnectCon(Result<ConStatus, TransferError>),
+    /// Answers [`ApplicationServiceRes::DataConnected`], whether the peer acknowledged the APDU.
+    DataConnectedCon(Result<ConStatus, TransferError>),
+    /// APDU sent to us without a connection, the frame's source address is the sender.
+    DataIndividual(Frame),
+    /// APDU broadcast to all devices, other than the ones programming the individual address.
+    DataBroadcast(Frame),
+    /// APDU broadcast to all devices of the system.
+    DataSystemBroadcast(Frame),
+    /// Answers [`ApplicationServiceRes::DataIndividual`], whether the device acknowledged it.
+    DataIndividualCon(IndividualAddress, Result<ConStatus, TransferError>),
+    /// Answers [`ApplicationServiceRes::DataBroadcast`], whether the broadcast was sent.
+    DataBroadcastCon(Result<ConStatus, TransferError>),
 }
 
-pub struct GroupReadResponse {
+fn group_value_frame(
+    data: &impl DataPointAccess,
+    apci: u16,
+    priority: Priority,
+    hop_count: u8,
+) -> Result<Frame, FrameError> {
+    let mut frame = Frame::from_datapoint(data)?;
+    frame.set_apci(ApciBits::Four, apci);
+    frame.set_priority(priority);
+    frame.set_hop_count(hop_count);
+    Ok(frame)
+}
+
+pub struct GroupWriteRequest {
     data: DataPoint,
     asap: u8,
     hop_count: u8,
     priority: Priority,
 }
 
-impl GroupReadResponse {
+impl GroupWriteRequest {
     pub fn new(asap: u8, data: DataPoint, priority: Priority) -> Self {
         Self {
-            asap: asap,
-            data: data,
+            asap,
+            data,
             hop_count: 7,
-            priority: priority,
+            priority,
         }
     }
 
-    fn to_transport(self, tsap: u8) -> Result<transport_layer::DataGroupReq, FrameError> {
-        let mut frame = Frame::from_datapoint(&self.data)?;
-        frame.set_apci(ApciBits::Four, 0x1);
-        frame.set_priority(self.priority);
-        frame.set_hop_count(self.hop_count);
-        Ok(transport_layer::DataGroupReq::new(tsap, frame))
+    fn frame(&self) -> Result<Frame, FrameError> {
+        group_value_frame(&self.data, 0x2, self.priority, self.hop_count)
     }
 }
 
 pub enum ApplicationServiceRes {
-    GroupValueRead(GroupReadResponse),
+    /// A_GroupValue_Write.req, answered with [`ApplicationServiceInd::GroupValueWriteCon`].
+    GroupValueWrite(GroupWriteRequest),
+    /// Opens a connection to the device, answered with [`ApplicationServiceInd::ConnectCon`].
+    /// Only one connection can be open at a time.
+    Connect(IndividualAddress),
+    /// Closes the connection, answered with [`ApplicationServiceInd::DisconnectCon`].
+    Disconnect,
+    /// Sends the APDU of the frame on the open connection, answered with
+    /// [`ApplicationServiceInd::DataConnectedCon`]. The transport layer takes care of the
+    /// addresses, the sequence number and the repetitions, if the peer never acknowledges it
+    /// the connection ends with [`ApplicationServiceInd::Disconnect`].
+    DataConnected(Frame),
+    /// Sends the APDU of the frame to the device without a connection, e.g. to answer an
+    /// [`ApplicationServiceInd::DataIndividual`]. Answered with
+    /// [`ApplicationServiceInd::DataIndividualCon`].
+    DataIndividual(IndividualAddress, Frame),
+    /// Broadcasts the APDU of the frame to all devices, answered with
+    /// [`ApplicationServiceInd::DataBroadcastCon`].
+    DataBroadcast(Frame),
 }
 
 pub struct ApplicationLayer {
     transport: TransportLayer,
-    rx: Receiver<'static, ThreadModeRawMutex, ApplicationServiceRes, 4>,
-    tx: Sender<'static, ThreadModeRawMutex, ApplicationServiceInd, 4>,
+    associations: &'static GroupObjectAssociationTable,
+    objects: &'static GroupObjectTable,
+    rx: Receiver<'static, CriticalSectionRawMutex, ApplicationServiceRes, 4>,
+    tx: Sender<'static, CriticalSectionRawMutex, ApplicationServiceInd, 4>,
+    programming_mode: Option<&'static ProgrammingMode>,
 }
 
 impl ApplicationLayer {
     pub fn new(
         transport: TransportLayer,
-        rx: Receiver<'static, ThreadModeRawMutex, ApplicationServiceRes, 4>,
-        tx: Sender<'static, ThreadModeRawMutex, ApplicationServiceInd, 4>,
+        associations: &'static GroupObjectAssociationTable,
+        objects: &'static GroupObjectTable,
+        rx: Receiver<'static, CriticalSectionRawMutex, ApplicationServiceRes, 4>,
+        tx: Sender<'static, CriticalSectionRawMutex, ApplicationServiceInd, 4>,
+    ) -> Self {
+        Self {
+            transport,
+            associations,
+            objects,
+            rx,
+            tx,
+            programming_mode: None,
+        }
+    }
+
+    /// Like [`Self::new`], also taking part in programming the individual address while
+    /// `programming_mode` is on.
+    pub fn with_programming_mode(
+        transport: TransportLayer,
+        associations: &'static GroupObjectAssociationTable,
+        objects: &'static GroupObjectTable,
+        rx: Receiver<'static, CriticalSectionRawMutex, ApplicationServiceRes, 4>,
+        tx: Sender<'static, CriticalSectionRawMutex, ApplicationServiceInd, 4>,
+        programming_mode: &'static ProgrammingMode,
     ) -> Self {
         Self {
-            transport: transport,
-            rx: rx,
-            tx: tx,
+            programming_mode: Some(programming_mode),
+            ..Self::new(transport, associations, objects, rx, tx)
         }
     }
 
+    const HOP_COUNT: u8 = 7;
+
     pub async fn receive(&self, frame: Result<TransportServiceInd, FrameError>) {
         match frame {
-            Ok(TransportServiceInd::DataGroup(frame)) => {
-                let apci = frame.apci(ApciBits::Four);
-                match apci {
-                    0 => {
+            Ok(TransportServiceInd::DataGroup(tsap, frame)) => {
+                match Apdu::from_frame(&frame) {
+                    Ok(Apdu::GroupValueRead) => {
                         info!("A_GroupValue_Read");
-                        self.tx.send(ApplicationServiceInd::GroupValueRead(0)).await;
+                        for asap in self.associations.asaps(tsap) {
+                            if self.allows(asap, |object| object.config().read()) {
+                                self.respond(asap).await;
+                                self.tx
+                                    .send(ApplicationServiceInd::GroupValueRead(asap))
+                                    .await;
+                            }
+                        }
                     }
-                    1 => {
+                    Ok(Apdu::GroupValueResponse(value)) => {
                         info!("A_GroupValue_Response");
+                        // Responses to our own reads always update
+                        self.receive_value(
+                            tsap,
+                            &frame,
+                            value,
+                            |object| {
+                                object.config().update()
+                                    || object.state() == GroupObjectState::ReadRequest
+                            },
+                            true,
+                            ApplicationServiceInd::GroupValueResponse,
+                        )
+                        .await;
                     }
-                    2 => {
+                    Ok(Apdu::GroupValueWrite(value)) => {
                         info!("A_GroupValue_Write");
+                        self.receive_value(
+                            tsap,
+                            &frame,
+                            value,
+                            |object| object.config().write(),
+                            false,
+                            ApplicationServiceInd::GroupValueWrite,
+                        )
+                        .await;
                     }
-                    _ => {
-                        error!("Invalid APCI: {:x}", apci);
-                    }
+                    Ok(apdu) => warn!("Not a group service: {}", apdu),
+                    Err(e) => error!("Invalid APDU: {}", e),
                 }
             }
-            Ok(_) => {}
+            Ok(TransportServiceInd::Connect(address)) => {
+                self.tx.send(ApplicationServiceInd::Connect(address)).await;
+            }
+            Ok(TransportServiceInd::Disconnect(address)) => {
+                self.tx
+                    .send(ApplicationServiceInd::Disconnect(address))
+                    .await;
+            }
+            Ok(TransportServiceInd::DataConnected(frame)) => {
+                self.tx
+                    .send(ApplicationServiceInd::DataConnected(frame))
+                    .await;
+            }
+            Ok(TransportServiceInd::DataConnectedCon(status)) => {
+                self.tx
+                    .send(ApplicationServiceInd::DataConnectedCon(Ok(status)))
+                    .await;
+            }
+            Ok(TransportServiceInd::DataIndividual(frame)) => {
+                self.tx
+                    .send(ApplicationServiceInd::DataIndividual(frame))
+                    .await;
+            }
+            Ok(TransportServiceInd::DataBroadcast(frame)) => self.receive_broadcast(frame).await,
+            Ok(TransportServiceInd::DataSystemBroadcast(frame)) => {
+                self.tx
+                    .send(ApplicationServiceInd::DataSystemBroadcast(frame))
+                    .await;
+            }
+            Ok(TransportServiceInd::DataTagGroup(_)) => {}
             Err(e) => error!("Frame reception error: {}", e),
         }
     }
 
+    /// Answers the broadcasts programming the individual address, only in programming mode.
+    /// All other broadcasts are indicated to the application.
+    async fn receive_broadcast(&self, frame: Frame) {
+        let programming = self.programming_mode.is_some_and(|mode| mode.is_enabled());
+        match Apdu::from_frame(&frame) {
+            Ok(Apdu::IndividualAddressRead | Apdu::IndividualAddressWrite(_)) if !programming => {}
+            Ok(Apdu::IndividualAddressRead) => {
+                info!("A_IndividualAddress_Read");
+                // The response carries the address as its source
+                match self.broadcast(&Apdu::IndividualAddressResponse).await {
+                    Ok(ConStatus::Ok) => {}
+                    con => warn!("A_IndividualAddress_Response not confirmed: {}", con),
+                }
+            }
+            Ok(Apdu::IndividualAddressWrite(address)) => {
+                info!("A_IndividualAddress_Write: {}", address);
+                self.transport.set_address(address.clone());
+                self.tx
+                    .send(ApplicationServiceInd::IndividualAddressWrite(address))
+                    .await;
+            }
+            Ok(_) => {
+                self.tx
+                    .send(ApplicationServiceInd::DataBroadcast(frame))
+                    .await;
+            }
+            Err(e) => warn!("Invalid APDU: {}", e),
+        }
+    }
+
+    async fn broadcast(&self, apdu: &Apdu<'_>) -> Result<ConStatus, TransferError> {
+        let mut frame = apdu.to_frame()?;
+        frame.set_priority(Priority::System);
+        frame.set_hop_count(Self::HOP_COUNT);
+        self.transport
+            .send(TransportServiceReq::DataBroadcast(frame))
+            .await
+    }
+
+    /// Whether group object `asap` communicates and passes `check`.
+    fn allows(&self, asap: u8, check: fn(&GroupObject) -> bool) -> bool {
+        self.objects
+            .get(asap)
+            .is_some_and(|object| object.config().communication() && check(&object))
+    }
+
+    /// Sets the value of the group objects on `tsap` that pass `check` and indicates it
+    /// with `ind` for each of them. Only a `response` ends the wait of an object for one.
+    async fn receive_value(
+        &self,
+        tsap: u8,
+        frame: &Frame,
+        value: GroupValue,
+        check: fn(&GroupObject) -> bool,
+        response: bool,
+        ind: fn(u8, GroupValue) -> ApplicationServiceInd,
+    ) {
+        for asap in self.associations.asaps(tsap) {
+            if !self.allows(asap, check) {
+                continue;
+            }
+            let res = self.objects.update(asap, |object| {
+                object.set_apdu(frame.apdu_data())?;
+                if response || object.state() != GroupObjectState::ReadRequest {
+                    object.set_state(GroupObjectState::Update);
+                }
+                Ok::<_, FrameError>(())
+            });
+            match res {
+                Some(Ok(())) => self.tx.send(ind(asap, value.clone())).await,
+                Some(Err(e)) => warn!("Invalid value for ASAP {}: {}", asap, e),
+                None => {}
+            }
+        }
+    }
+
+    /// Answers an A_GroupValue_Read with the value of group object `asap`.
+    async fn respond(&self, asap: u8) {
+        let Some(object) = self.objects.get(asap) else {
+            return;
+        };
+        let frame = group_value_frame(&object, 0x1, object.config().priority(), Self::HOP_COUNT);
+        match self.send_group(asap, frame).await {
+            Ok(ConStatus::Ok) => {}
+            con => warn!("A_GroupValue_Response not confirmed: {}", con),
+        }
+    }
+
+    /// Stores `data` in group object `asap` and writes it to the bus, if the object
+    /// communicates and has the transmit flag set.
+    async fn write(
+        &self,
+        asap: u8,
+        data: &DataPoint,
+        frame: Result<Frame, FrameError>,
+    ) -> Result<ConStatus, TransferError> {
+        if !self.allows(asap, |object| object.config().transmit()) {
+            warn!("ASAP {} may not transmit", asap);
+            return Ok(ConStatus::NotOk);
+        }
+        let stored = self.objects.update(asap, |object| {
+            object.set_data(data)?;
+            object.set_state(GroupObjectState::Transmitting);
+            Ok::<_, FrameError>(())
+        });
+        if let Some(Err(e)) = stored {
+            warn!("Invalid value for ASAP {}: {}", asap, e);
+            return Err(TransferError::FrameError(e));
+        }
+        let con = self.send_group(asap, frame).await;
+        let state = match con {
+            Ok(ConStatus::Ok) => GroupObjectState::Ok,
+            _ => GroupObjectState::Error,
+        };
+        self.objects.update(asap, |object| object.set_state(state));
+        con
+    }
+
+    /// Sends an A_GroupValue_Read for group object `asap`, if it communicates and has the
+    /// transmit flag set. The object waits for the response in [`GroupObjectState::ReadRequest`].
+    async fn read(&self, asap: u8) -> Result<ConStatus, TransferError> {
+        if !self.allows(asap, |object| object.config().transmit()) {
+            warn!("ASAP {} may not transmit", asap);
+            return Ok(ConStatus::NotOk);
+        }
+        let Some(priority) = self.objects.update(asap, |object| {
+            object.set_state(GroupObjectState::ReadRequest);
+            object.config().priority()
+        }) else {
+            return Ok(ConStatus::NotOk);
+        };
+        let frame = Apdu::GroupValueRead.to_frame().map(|mut frame| {
+            frame.set_priority(priority);
+            frame.set_hop_count(Self::HOP_COUNT);
+            frame
+        });
+        let con = self.send_group(asap, frame).await;
+        if !matches!(con, Ok(ConStatus::Ok)) {
+            self.objects
+                .update(asap, |object| object.set_state(GroupObjectState::Error));
+        }
+        con
+    }
+
+    /// Sends an A_GroupValue_Read for every group object with the read on init flag set.
+    async fn read_on_init(&self) {
+        for asap in self
+            .objects
+            .filter(|config| config.communication() && config.read_on_init())
+        {
+            match self.read(asap).await {
+                Ok(ConStatus::Ok) => {}
+                con => warn!("A_GroupValue_Read for ASAP {} not confirmed: {}", asap, con),
+            }
+        }
+    }
+
+    /// Sends `frame` on the TSAP group object `asap` is associated with.
+    async fn send_group(
+        &self,
+        asap: u8,
+        frame: Result<Frame, FrameError>,
+    ) -> Result<ConStatus, TransferError> {
+        let frame = frame?;
+        let Some(tsap) = self.associations.tsap(asap) else {
+            warn!("No association for ASAP {}", asap);
+            return Ok(ConStatus::NotOk);
+        };
+        self.transport
+            .send(transport_layer::TransportServiceReq::DataGroupReq(
+                transport_layer::DataGroupReq::new(tsap, frame),
+            ))
+            .await
+    }
+
     pub async fn run(self) -> ! {
+        self.read_on_init().await;
         loop {
-            match select(self.transport.receive(), self.rx.ready_to_receive()).await {
-                Either::First(frame) => {
-                    self.receive(frame).await;
-                }
-                Either::Second(_) => match self.rx.receive().await {
-                    ApplicationServiceRes::GroupValueRead(resp) => {
-                        self.transport
-                            .send(transport_layer::TransportServiceReq::DataGroupReq(unwrap!(
-                                resp.to_transport(0),
-                            )))
+            match select3(
+                self.transport.next_event(),
+                self.rx.ready_to_receive(),
+                self.objects.requests.receive(),
+            )
+            .await
+            {
+                // Handled outside the select, a request arriving meanwhile can't cut it off
+                Either3::First(event) => match self.transport.handle(event).await {
+                    Ok(inds) => {
+                        for ind in inds {
+                            self.receive(Ok(ind)).await;
+                        }
+                    }
+                    Err(e) => self.receive(Err(e)).await,
+                },
+                Either3::Second(_) => match self.rx.receive().await {
+                    ApplicationServiceRes::GroupValueWrite(req) => {
+                        let asap = req.asap;
+                        let con = self.write(asap, &req.data, req.frame()).await;
+                        self.tx
+                            .send(ApplicationServiceInd::GroupValueWriteCon(asap, con))
+                            .await;
+                    }
+                    ApplicationServiceRes::Connect(address) => {
+                        let con = self
+                            .transport
+                            .send(TransportServiceReq::Connect(address.clone()))
+                            .await;
+                        self.tx
+                            .send(ApplicationServiceInd::ConnectCon(address, con))
+                            .await;
+                    }
+                    ApplicationServiceRes::Disconnect => {
+                        let con = self.transport.send(TransportServiceReq::Disconnect).await;
+                        self.tx
+                            .send(ApplicationServiceInd::DisconnectCon(con))
+                            .await;
+                    }
+                    ApplicationServiceRes::DataConnected(frame) => {
+                        let con = self
+                            .transport
+                            .send(TransportServiceReq::DataConnected(frame))
+                            .await;
+                        // Once sent, the confirmation waits for the peer's acknowledgement
+                        if !matches!(con, Ok(ConStatus::Ok)) {
+                            self.tx
+                                .send(ApplicationServiceInd::DataConnectedCon(con))
+                                .await;
+                        }
+                    }
+                    ApplicationServiceRes::DataIndividual(address, frame) => {
+                        let con = self
+                            .transport
+                            .send(TransportServiceReq::DataIndividual(address.clone(), frame))
+                            .await;
+                        self.tx
+                            .send(ApplicationServiceInd::DataIndividualCon(address, con))
+                            .await;
+                    }
+                    ApplicationServiceRes::DataBroadcast(frame) => {
+                        let con = self
+                            .transport
+                            .send(TransportServiceReq::DataBroadcast(frame))
+                            .await;
+                        self.tx
+                            .send(ApplicationServiceInd::DataBroadcastCon(con))
                             .await;
                     }
                 },
+                Either3::Third((id, req)) => {
+                    let con = match req {
+                        GroupObjectReq::Write(asap, data) => {
+                            let priority = self
+                                .objects
+                                .get(asap)
+                                .map_or(Priority::Low, |object| object.config().priority());
+                            let frame = group_value_frame(&data, 0x2, priority, Self::HOP_COUNT);
+                            self.write(asap, &data, frame).await
+                        }
+                        GroupObjectReq::Read(asap) => self.read(asap).await,
+                    };
+                    self.objects.requests.confirm(id, con);
+                }
             }
         }
     }
@@ -0,0 +1,281 @@
+//! Common External Message Interface (cEMI) encoding of L_Data frames, as used by
+//! KNXnet/IP and KNX USB.
+
+use crate::data_link_layer::ConStatus;
+use crate::frame::*;
+use heapless::Vec;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+const MAX_ADDITIONAL_INFO_LENGTH: usize = 32;
+// Control field 1, control field 2, source, destination and length
+const LDATA_HEADER_LENGTH: usize = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum MessageCode {
+    LDataReq = 0x11,
+    LDataCon = 0x2E,
+    LDataInd = 0x29,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CemiError {
+    TooShort(usize),
+    BufferTooSmall(usize),
+    UnknownMessageCode(u8),
+    InvalidAdditionalInfo,
+    LengthMismatch { expected: usize, actual: usize },
+    FrameError(FrameError),
+}
+
+impl From<FrameError> for CemiError {
+    fn from(value: FrameError) -> Self {
+        Self::FrameError(value)
+    }
+}
+
+/// Additional information type IDs.
+pub mod additional_info_type {
+    pub const PL_MEDIUM_INFO: u8 = 0x01;
+    pub const RF_MEDIUM_INFO: u8 = 0x02;
+    pub const BUSMONITOR_STATUS_INFO: u8 = 0x03;
+    pub const TIMESTAMP_RELATIVE: u8 = 0x04;
+    pub const TIME_DELAY_UNTIL_SENDING: u8 = 0x05;
+    pub const EXTENDED_RELATIVE_TIMESTAMP: u8 = 0x06;
+    pub const BIBAT_INFO: u8 = 0x07;
+    pub const RF_MULTI_INFO: u8 = 0x08;
+    pub const PREAMBLE_AND_POSTAMBLE: u8 = 0x09;
+    pub const RF_FAST_ACK_INFO: u8 = 0x0A;
+    pub const MANUFACTURER_SPECIFIC: u8 = 0xFE;
+}
+
+/// The additional information block, a list of type/length/value entries.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AdditionalInfo(Vec<u8, MAX_ADDITIONAL_INFO_LENGTH>);
+
+impl AdditionalInfo {
+    pub const fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    fn from_slice(buf: &[u8]) -> Result<Self, CemiError> {
+        let mut rest = buf;
+        while !rest.is_empty() {
+            let len = *rest.get(1).ok_or(CemiError::InvalidAdditionalInfo)? as usize;
+            rest = rest
+                .get(2 + len..)
+                .ok_or(CemiError::InvalidAdditionalInfo)?;
+        }
+        Vec::from_slice(buf)
+            .map(Self)
+            .map_err(|_| CemiError::InvalidAdditionalInfo)
+    }
+
+    /// Appends an entry, fails if the block is full.
+    pub fn push(&mut self, info_type: u8, data: &[u8]) -> Result<(), CemiError> {
+        if data.len() > u8::MAX as usize
+            || self.0.len() + 2 + data.len() > MAX_ADDITIONAL_INFO_LENGTH
+        {
+            return Err(CemiError::InvalidAdditionalInfo);
+        }
+        // Capacity is checked above
+        let _ = self.0.extend_from_slice(&[info_type, data.len() as u8]);
+        let _ = self.0.extend_from_slice(data);
+        Ok(())
+    }
+
+    /// Returns the first entry of the given type.
+    pub fn get(&self, info_type: u8) -> Option<&[u8]> {
+        self.iter()
+            .find(|(t, _)| *t == info_type)
+            .map(|(_, data)| data)
+    }
+
+    /// Iterates over all entries as `(type, data)`.
+    pub fn iter(&self) -> impl Iterator<Item = (u8, &[u8])> {
+        let mut rest = self.0.as_slice();
+        core::iter::from_fn(move || {
+            let (header, tail) = rest.split_at_checked(2)?;
+            let (data, tail) = tail.split_at(header[1] as usize);
+            rest = tail;
+            Some((header[0], data))
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// An L_Data.req/.con/.ind cEMI message wrapping a TP1 frame.
+///
+/// The control fields map onto the TP1 frame header, only the acknowledge request and the
+/// confirm flag have no place in the frame and are kept separately. System broadcasts are not
+/// supported on TP1 and decoded as normal broadcasts.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CemiMessage {
+    code: MessageCode,
+    additional_info: AdditionalInfo,
+    ack_request: bool,
+    con_status: ConStatus,
+    frame: Frame,
+}
+
+impl CemiMessage {
+    const CTRL1_FRAME_TYPE: u8 = 0x80;
+    const CTRL1_BROADCAST: u8 = 0x10;
+    const CTRL1_ACK_REQUEST: u8 = 0x02;
+    const CTRL1_CONFIRM_ERROR: u8 = 0x01;
+    const TP1_CTRL_MASK: u8 = 0xFC;
+    const STANDARD_MAX_LENGTH: u8 = 0x0F;
+
+    pub fn new(code: MessageCode, frame: Frame) -> Self {
+        Self {
+            code,
+            additional_info: AdditionalInfo::new(),
+            ack_request: code == MessageCode::LDataReq,
+            con_status: ConStatus::Ok,
+            frame,
+        }
+    }
+
+    pub fn code(&self) -> MessageCode {
+        self.code
+    }
+    pub fn frame(&self) -> &Frame {
+        &self.frame
+    }
+    pub fn into_frame(self) -> Frame {
+        self.frame
+    }
+    pub fn additional_info(&self) -> &AdditionalInfo {
+        &self.additional_info
+    }
+    pub fn additional_info_mut(&mut self) -> &mut AdditionalInfo {
+        &mut self.additional_info
+    }
+    pub fn ack_request(&self) -> bool {
+        self.ack_request
+    }
+    pub fn set_ack_request(&mut self, ack_request: bool) {
+        self.ack_request = ack_request;
+    }
+    /// Only meaningful for L_Data.con.
+    pub fn con_status(&self) -> &ConStatus {
+        &self.con_status
+    }
+    pub fn set_con_status(&mut self, con_status: ConStatus) {
+        self.con_status = con_status;
+    }
+
+    /// Decodes a cEMI L_Data message, the frame is allocated from the frame pool.
+    pub fn parse(buf: &[u8]) -> Result<Self, CemiError> {
+        if buf.len() < 2 {
+            return Err(CemiError::TooShort(buf.len()));
+        }
+        let code =
+            MessageCode::try_from(buf[0]).map_err(|e| CemiError::UnknownMessageCode(e.number))?;
+        let info_end = 2 + buf[1] as usize;
+        // At least one byte of TPDU has to follow the header
+        if buf.len() < info_end + LDATA_HEADER_LENGTH + 1 {
+            return Err(CemiError::TooShort(buf.len()));
+        }
+        let additional_info = AdditionalInfo::from_slice(&buf[2..info_end])?;
+        let ldata = &buf[info_end..];
+        let ctrl1 = ldata[0];
+        let length = ldata[6];
+        let expected = info_end + LDATA_HEADER_LENGTH + 1 + length as usize;
+        if buf.len() != expected {
+            return Err(CemiError::LengthMismatch {
+                expected,
+                actual: buf.len(),
+            });
+        }
+        // Long frames have to go out as extended frames, whatever the control field says
+        let ctrl = (ctrl1 & Self::TP1_CTRL_MASK & !Self::CTRL1_FRAME_TYPE) | Self::CTRL1_BROADCAST;
+        let frame = if ctrl1 & Self::CTRL1_FRAME_TYPE != 0 && length <= Self::STANDARD_MAX_LENGTH {
+            Frame::Standard(Self::to_tp1(ldata, ctrl | Self::CTRL1_FRAME_TYPE)?)
+        } else {
+            Frame::Extended(Self::to_tp1(ldata, ctrl)?)
+        };
+        Ok(Self {
+            code,
+            additional_info,
+            ack_request: ctrl1 & Self::CTRL1_ACK_REQUEST != 0,
+            con_status: if ctrl1 & Self::CTRL1_CONFIRM_ERROR != 0 {
+                ConStatus::NotOk
+            } else {
+                ConStatus::Ok
+            },
+            frame,
+        })
+    }
+
+    /// Encodes the message into `buf` and returns the number of bytes written.
+    pub fn write(&self, buf: &mut [u8]) -> Result<usize, CemiError> {
+        let (ctrl2, (addresses, tpdu)) = match &self.frame {
+            Frame::Standard(f) => (
+                f.data()[StandardFrame::AT_FIELD] & !StandardFrame::LG_MASK,
+                Self::tpdu(f),
+            ),
+            Frame::Extended(f) => (f.data()[ExtendedFrame::AT_FIELD], Self::tpdu(f)),
+        };
+        let info = self.additional_info.0.as_slice();
+        let len = 2 + info.len() + LDATA_HEADER_LENGTH + tpdu.len();
+        let buf = buf.get_mut(..len).ok_or(CemiError::BufferTooSmall(len))?;
+
+        let mut ctrl1 = (self.frame.data()[0] & Self::TP1_CTRL_MASK) | Self::CTRL1_BROADCAST;
+        if self.ack_request {
+            ctrl1 |= Self::CTRL1_ACK_REQUEST;
+        }
+        if let ConStatus::NotOk = self.con_status {
+            ctrl1 |= Self::CTRL1_CONFIRM_ERROR;
+        }
+        buf[0] = self.code.into();
+        buf[1] = info.len() as u8;
+        buf[2..2 + info.len()].copy_from_slice(info);
+        let ldata = &mut buf[2 + info.len()..];
+        ldata[0] = ctrl1;
+        ldata[1] = ctrl2;
+        ldata[2..6].copy_from_slice(addresses);
+        ldata[6] = (tpdu.len() - 1) as u8;
+        ldata[LDATA_HEADER_LENGTH..].copy_from_slice(tpdu);
+        Ok(len)
+    }
+
+    /// Splits a TP1 frame into its addresses and the TPDU without the checksum.
+    fn tpdu<T: FrameReader>(frame: &T) -> (&[u8], &[u8]) {
+        let data = frame.data();
+        (
+            &data[T::SRC_ADDR_OFFSET..T::DST_ADDR_OFFSET + 2],
+            &data[T::TPCI_OFFSET..data.len() - 1],
+        )
+    }
+
+    /// Builds a TP1 frame from the L_Data part of a cEMI message.
+    fn to_tp1<T: FrameWriter>(ldata: &[u8], ctrl: u8) -> Result<T, FrameError> {
+        let tpdu = &ldata[LDATA_HEADER_LENGTH..];
+        let length = T::HEADER_LENGTH + tpdu.len();
+        if length > T::MAX_FRAME_SIZE {
+            return Err(FrameError::TooLong(length));
+        }
+        let mut frame = T::new(length)?;
+        let data = frame.mut_data();
+        data[T::CTRL] = ctrl;
+        data[T::AT_FIELD] = ldata[1];
+        data[T::SRC_ADDR_OFFSET..T::DST_ADDR_OFFSET + 2].copy_from_slice(&ldata[2..6]);
+        data[T::LG_FIELD] = (data[T::LG_FIELD] & !T::LG_MASK) | (ldata[6] & T::LG_MASK);
+        data[T::TPCI_OFFSET..length - 1].copy_from_slice(tpdu);
+        let checksum = frame.checksum();
+        frame.mut_data()[length - 1] = checksum;
+        Ok(frame)
+    }
+}
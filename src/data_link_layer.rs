@@ -1,19 +1,27 @@
 use crate::frame::*;
-use crate::ncn51_driver::{ConStatus, CON_SIGNAL};
-use defmt::*;
-use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
-use embassy_sync::channel::{Receiver, Sender};
+use crate::phy::{PhyChannels, TransferError};
+use core::cell::RefCell;
+use embassy_futures::select::{select, Either};
+use embassy_time::{Duration, WithTimeout};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConStatus {
+    Ok,
+    NotOk,
+}
 
 pub struct DataLinkLayer {
-    rx: Receiver<'static, ThreadModeRawMutex, Frame, 8>,
-    tx: Sender<'static, ThreadModeRawMutex, Frame, 8>,
+    channels: &'static PhyChannels,
+    address: RefCell<IndividualAddress>,
 }
 
-#[derive(Format)]
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum DataServiceInd {
     Data(Frame),
     SystemBroadcast(Frame),
-    Busmon(Frame),
+    Busmon(Telegram),
     ServiceInformation(Frame),
 }
 
@@ -22,24 +30,52 @@ pub enum DataServiceCon {
 }
 
 impl DataLinkLayer {
-    pub fn new(
-        rx: Receiver<'static, ThreadModeRawMutex, Frame, 8>,
-        tx: Sender<'static, ThreadModeRawMutex, Frame, 8>,
-    ) -> Self {
-        Self { rx: rx, tx: tx }
+    const CON_TIMEOUT_MS: u64 = 1000;
+
+    pub fn new(channels: &'static PhyChannels) -> Self {
+        Self {
+            channels,
+            address: RefCell::new(crate::settings::ADDRESS),
+        }
+    }
+
+    /// The individual address of the device, [`crate::settings::ADDRESS`] until it's set.
+    pub fn address(&self) -> IndividualAddress {
+        self.address.borrow().clone()
+    }
+
+    /// Changes the individual address of the device, the transceiver acknowledges frames
+    /// for the new one from now on.
+    pub fn set_address(&self, address: IndividualAddress) {
+        self.address.replace(address.clone());
+        self.channels.address.signal(address);
     }
 
-    pub async fn send(&self, frame: Frame) {
-        CON_SIGNAL.reset();
-        self.tx.send(frame).await;
-        let con = CON_SIGNAL.wait().await;
+    /// Sends a frame and returns its L_Data.con. Fails with [`TransferError::TimeoutError`]
+    /// if the transceiver doesn't confirm within a second, or with the transceiver's error.
+    pub async fn send(&self, frame: Frame) -> Result<ConStatus, TransferError> {
+        self.channels
+            .tx
+            .request(frame)
+            .with_timeout(Duration::from_millis(Self::CON_TIMEOUT_MS))
+            .await
+            .map_err(|_| TransferError::TimeoutError(0))?
     }
 
     pub async fn receive(&self) -> DataServiceInd {
-        loop {
-            let frame = self.rx.receive().await;
-            info!("{}", frame);
-            return DataServiceInd::Data(frame);
+        match select(self.channels.rx.receive(), self.channels.busmon.receive()).await {
+            Either::First(frame) => {
+                info!("{}", frame);
+                DataServiceInd::Data(frame)
+            }
+            Either::Second(telegram) => DataServiceInd::Busmon(telegram),
         }
     }
+
+    /// Switches the transceiver into or out of bus monitor mode. In bus monitor mode every
+    /// telegram on the line, acknowledgements and corrupt frames included, is indicated as
+    /// [`DataServiceInd::Busmon`] and sending fails.
+    pub fn set_busmon(&self, enabled: bool) {
+        self.channels.busmon_mode.signal(enabled);
+    }
 }
@@ -1,7 +1,9 @@
-use defmt::*;
+use crate::frame::FrameError;
 use enum_dispatch::enum_dispatch;
+use heapless::Vec;
 
-#[derive(Format)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum DataPointLength {
     Bit(usize),
     Byte(usize),
@@ -13,7 +15,7 @@ pub trait DataPointAccess {
     fn write(&self, buf: &mut [u8]);
     fn byte_length(&self) -> usize {
         match self.length() {
-            DataPointLength::Bit(n) if n < 6 => 0,
+            DataPointLength::Bit(n) if n <= 6 => 0,
             DataPointLength::Bit(_) => 1,
             DataPointLength::Byte(n) => n,
         }
@@ -21,14 +23,15 @@ pub trait DataPointAccess {
 }
 
 #[enum_dispatch(DataPointAccess)]
-#[derive(Format)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum DataPoint {
     B1,
     B2,
-    //B1U3,
-    //Char,
+    B1U3,
+    Char,
     U8,
-    /*V8,
+    V8,
     StatusMode,
     U16,
     S16,
@@ -50,16 +53,228 @@ pub enum DataPoint {
     SceneInfo,
     B32,
     UnicodeString,
-    V64,*/
+    V64,
 }
 
-#[derive(Format)]
+/// Format of a [`DataPoint`], to know how to decode received bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DataPointType {
+    B1,
+    B2,
+    B1U3,
+    Char,
+    U8,
+    V8,
+    StatusMode,
+    U16,
+    S16,
+    F16,
+    Time,
+    Date,
+    U32,
+    S32,
+    F32,
+    AccessData,
+    String,
+    SceneNumber,
+    SceneControl,
+    DateTime,
+    N8,
+    B8,
+    N2,
+    VarString,
+    SceneInfo,
+    B32,
+    UnicodeString,
+    V64,
+}
+
+impl DataPointType {
+    /// Size of values of this format, `None` for the strings of variable length.
+    pub fn length(&self) -> Option<DataPointLength> {
+        Some(match self {
+            Self::B1 => DataPointLength::Bit(1),
+            Self::B2 | Self::N2 => DataPointLength::Bit(2),
+            Self::B1U3 => DataPointLength::Bit(4),
+            Self::Char
+            | Self::U8
+            | Self::V8
+            | Self::StatusMode
+            | Self::SceneNumber
+            | Self::SceneControl
+            | Self::N8
+            | Self::B8
+            | Self::SceneInfo => DataPointLength::Byte(1),
+            Self::U16 | Self::S16 | Self::F16 => DataPointLength::Byte(2),
+            Self::Time | Self::Date => DataPointLength::Byte(3),
+            Self::U32 | Self::S32 | Self::F32 | Self::AccessData | Self::B32 => {
+                DataPointLength::Byte(4)
+            }
+            Self::DateTime | Self::V64 => DataPointLength::Byte(8),
+            Self::String => DataPointLength::Byte(14),
+            Self::VarString | Self::UnicodeString => return None,
+        })
+    }
+
+    /// Whether values of this format are sent in the APCI byte, true for up to 6 bits.
+    pub fn is_short(&self) -> bool {
+        matches!(self.length(), Some(DataPointLength::Bit(n)) if n <= 6)
+    }
+
+    /// Size of the raw value, values of up to 8 bits take a single byte.
+    fn value_length(&self) -> Option<usize> {
+        match self.length()? {
+            DataPointLength::Bit(_) => Some(1),
+            DataPointLength::Byte(n) => Some(n),
+        }
+    }
+}
+
+impl DataPoint {
+    /// Decodes the APDU data of a received frame, starting at the APCI byte which holds the
+    /// value of short formats.
+    pub fn read(dpt: DataPointType, apdu: &[u8]) -> Result<Self, FrameError> {
+        let expected = match dpt.value_length() {
+            _ if dpt.is_short() => 1,
+            Some(n) => 1 + n,
+            // At least the terminating zero
+            None => apdu.len().max(2),
+        };
+        match apdu {
+            _ if apdu.len() != expected => Err(FrameError::LengthMismatch {
+                expected,
+                actual: apdu.len(),
+            }),
+            [short] => Self::from_value(dpt, &[short & 0x3F]),
+            [_, value @ ..] => Self::from_value(dpt, value),
+            [] => Err(FrameError::InvalidLength),
+        }
+    }
+
+    /// Decodes a raw value without the APCI byte, a single byte holding the bits for formats
+    /// of up to 8 bits.
+    pub fn from_value(dpt: DataPointType, value: &[u8]) -> Result<Self, FrameError> {
+        if let Some(expected) = dpt.value_length() {
+            if value.len() != expected {
+                return Err(FrameError::LengthMismatch {
+                    expected,
+                    actual: value.len(),
+                });
+            }
+        }
+        Ok(match dpt {
+            DataPointType::B1 => Self::B1(B1::new(value[0] & 0x1 != 0)),
+            DataPointType::B2 => Self::B2(B2::new(value[0])),
+            DataPointType::B1U3 => Self::B1U3(B1U3(value[0] & 0xF)),
+            DataPointType::Char => Self::Char(Char::decode(value)),
+            DataPointType::U8 => Self::U8(U8::decode(value)),
+            DataPointType::V8 => Self::V8(V8::decode(value)),
+            DataPointType::StatusMode => Self::StatusMode(StatusMode(value[0])),
+            DataPointType::U16 => Self::U16(U16::decode(value)),
+            DataPointType::S16 => Self::S16(S16::decode(value)),
+            DataPointType::F16 => Self::F16(F16(u16::from_be_bytes([value[0], value[1]]))),
+            DataPointType::Time => Self::Time(Time::decode(value)),
+            DataPointType::Date => Self::Date(Date::decode(value)),
+            DataPointType::U32 => Self::U32(U32::decode(value)),
+            DataPointType::S32 => Self::S32(S32::decode(value)),
+            DataPointType::F32 => Self::F32(F32::decode(value)),
+            DataPointType::AccessData => Self::AccessData(AccessData::decode(value)),
+            DataPointType::String => Self::String(String::decode(value)),
+            DataPointType::SceneNumber => Self::SceneNumber(SceneNumber::new(value[0])),
+            DataPointType::SceneControl => Self::SceneControl(SceneControl(value[0] & 0xBF)),
+            DataPointType::DateTime => Self::DateTime(DateTime::decode(value)),
+            DataPointType::N8 => Self::N8(N8::decode(value)),
+            DataPointType::B8 => Self::B8(B8::decode(value)),
+            DataPointType::N2 => Self::N2(N2::new(value[0])),
+            DataPointType::VarString => Self::VarString(VarString::decode(value)?),
+            DataPointType::SceneInfo => Self::SceneInfo(SceneInfo(value[0] & 0x7F)),
+            DataPointType::B32 => Self::B32(B32::decode(value)),
+            DataPointType::UnicodeString => Self::UnicodeString(UnicodeString::decode(value)?),
+            DataPointType::V64 => Self::V64(V64::decode(value)),
+        })
+    }
+
+    /// The format of the value.
+    pub fn data_point_type(&self) -> DataPointType {
+        match self {
+            Self::B1(_) => DataPointType::B1,
+            Self::B2(_) => DataPointType::B2,
+            Self::B1U3(_) => DataPointType::B1U3,
+            Self::Char(_) => DataPointType::Char,
+            Self::U8(_) => DataPointType::U8,
+            Self::V8(_) => DataPointType::V8,
+            Self::StatusMode(_) => DataPointType::StatusMode,
+            Self::U16(_) => DataPointType::U16,
+            Self::S16(_) => DataPointType::S16,
+            Self::F16(_) => DataPointType::F16,
+            Self::Time(_) => DataPointType::Time,
+            Self::Date(_) => DataPointType::Date,
+            Self::U32(_) => DataPointType::U32,
+            Self::S32(_) => DataPointType::S32,
+            Self::F32(_) => DataPointType::F32,
+            Self::AccessData(_) => DataPointType::AccessData,
+            Self::String(_) => DataPointType::String,
+            Self::SceneNumber(_) => DataPointType::SceneNumber,
+            Self::SceneControl(_) => DataPointType::SceneControl,
+            Self::DateTime(_) => DataPointType::DateTime,
+            Self::N8(_) => DataPointType::N8,
+            Self::B8(_) => DataPointType::B8,
+            Self::N2(_) => DataPointType::N2,
+            Self::VarString(_) => DataPointType::VarString,
+            Self::SceneInfo(_) => DataPointType::SceneInfo,
+            Self::B32(_) => DataPointType::B32,
+            Self::UnicodeString(_) => DataPointType::UnicodeString,
+            Self::V64(_) => DataPointType::V64,
+        }
+    }
+}
+
+/// Formats that are a plain number, sent big endian.
+macro_rules! number {
+    ($(#[$doc:meta])* $name:ident, $type:ty) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, PartialEq)]
+        #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+        pub struct $name($type);
+
+        impl $name {
+            pub fn new(data: $type) -> Self {
+                Self(data)
+            }
+            pub fn value(&self) -> $type {
+                self.0
+            }
+            fn decode(value: &[u8]) -> Self {
+                let mut bytes = [0; core::mem::size_of::<$type>()];
+                bytes.copy_from_slice(value);
+                Self(<$type>::from_be_bytes(bytes))
+            }
+        }
+
+        impl DataPointAccess for $name {
+            fn length(&self) -> DataPointLength {
+                DataPointLength::Byte(core::mem::size_of::<$type>())
+            }
+            fn write(&self, buf: &mut [u8]) {
+                let bytes = self.0.to_be_bytes();
+                buf[..bytes.len()].copy_from_slice(&bytes);
+            }
+        }
+    };
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct B1(u8);
 
 impl B1 {
     pub fn new(data: bool) -> Self {
         Self(data as u8)
     }
+    pub fn value(&self) -> bool {
+        self.0 != 0
+    }
 }
 
 impl DataPointAccess for B1 {
@@ -71,11 +286,15 @@ impl DataPointAccess for B1 {
     }
 }
 
-#[derive(Format)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct B2(u8);
 impl B2 {
     pub fn new(data: u8) -> Self {
-        Self(data)
+        Self(data & 0x3)
+    }
+    pub fn value(&self) -> u8 {
+        self.0
     }
 }
 
@@ -87,16 +306,587 @@ impl DataPointAccess for B2 {
         buf[0] |= self.0 & 0x3;
     }
 }
-//pub struct B1U3 {}
-//pub struct Char {}
-#[derive(Format)]
-pub struct U8(u8);
-impl U8 {
+
+/// Control bit and step code, relative dimming and blinds (DPT 3).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct B1U3(u8);
+impl B1U3 {
+    /// `step` 0 stops, 1 to 7 divide the range into 2^(step-1) intervals.
+    pub fn new(increase: bool, step: u8) -> Self {
+        Self((increase as u8) << 3 | step & 0x7)
+    }
+    pub fn increase(&self) -> bool {
+        self.0 & 0x8 != 0
+    }
+    pub fn step(&self) -> u8 {
+        self.0 & 0x7
+    }
+}
+impl DataPointAccess for B1U3 {
+    fn length(&self) -> DataPointLength {
+        DataPointLength::Bit(4)
+    }
+    fn write(&self, buf: &mut [u8]) {
+        buf[0] |= self.0 & 0xF;
+    }
+}
+
+number!(
+    /// A character in ISO 8859-1 (DPT 4).
+    Char,
+    u8
+);
+number!(U8, u8);
+number!(
+    /// Signed 8 bit value (DPT 6).
+    V8,
+    i8
+);
+
+/// Status bits A to E and the active one of three modes (DPT 6.020).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct StatusMode(u8);
+impl StatusMode {
+    /// `status` has bit A as its highest of 5 bits, `mode` is 0, 1 or 2.
+    pub fn new(status: u8, mode: u8) -> Self {
+        Self((status & 0x1F) << 3 | 1 << mode.min(2))
+    }
+    pub fn status(&self) -> u8 {
+        self.0 >> 3
+    }
+    /// The active mode, `None` if the received value doesn't have exactly one.
+    pub fn mode(&self) -> Option<u8> {
+        match self.0 & 0x7 {
+            0x1 => Some(0),
+            0x2 => Some(1),
+            0x4 => Some(2),
+            _ => None,
+        }
+    }
+}
+impl DataPointAccess for StatusMode {
+    fn length(&self) -> DataPointLength {
+        DataPointLength::Byte(1)
+    }
+    fn write(&self, buf: &mut [u8]) {
+        buf[0] = self.0;
+    }
+}
+
+number!(
+    /// Unsigned 16 bit value (DPT 7).
+    U16,
+    u16
+);
+number!(
+    /// Signed 16 bit value (DPT 8).
+    S16,
+    i16
+);
+
+/// 16 bit float (DPT 9): `0.01 * mantissa * 2^exponent` with a 12 bit two's complement
+/// mantissa and a 4 bit exponent, sent as the sign, the exponent and the lower 11 bits of the
+/// mantissa.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct F16(u16);
+impl F16 {
+    /// Sent instead of a value, e.g. by a broken sensor.
+    pub const INVALID: Self = Self(0x7FFF);
+    /// Largest value, `0.01 * 2046 * 2^15`. The encoding of `2047 * 2^15` is [`F16::INVALID`].
+    pub const MAX: f32 = 670_433.28;
+    /// Smallest value, `0.01 * -2048 * 2^15`.
+    // As in the spec, the closest f32 is what gets encoded
+    #[allow(clippy::excessive_precision)]
+    pub const MIN: f32 = -671_088.64;
+
+    /// Encodes `value` with the smallest exponent its rounded mantissa fits, which gives the
+    /// best resolution. The mantissa is rounded half away from zero.
+    ///
+    /// Values beyond [`F16::MIN`] and [`F16::MAX`] are sent as those, NaN as [`F16::INVALID`].
+    pub fn new(value: f32) -> Self {
+        if value.is_nan() {
+            return Self::INVALID;
+        }
+        // In f64, so hundredths like 21.1 * 100 don't pick up f32 rounding errors
+        let hundredths = value as f64 * 100.0;
+        for exponent in 0..16 {
+            let mantissa = round(hundredths / (1 << exponent) as f64);
+            if (-2048..=2047).contains(&mantissa) && (exponent, mantissa) != (15, 2047) {
+                return Self::encode(mantissa as i16, exponent);
+            }
+        }
+        if hundredths < 0.0 {
+            Self::encode(-2048, 15)
+        } else {
+            Self::encode(2046, 15)
+        }
+    }
+
+    fn encode(mantissa: i16, exponent: u16) -> Self {
+        let sign = if mantissa < 0 { 0x8000 } else { 0 };
+        Self(sign | exponent << 11 | (mantissa as u16 & 0x7FF))
+    }
+
+    /// The value, `None` for [`F16::INVALID`].
+    pub fn value(&self) -> Option<f32> {
+        if *self == Self::INVALID {
+            return None;
+        }
+        let exponent = (self.0 >> 11) & 0xF;
+        let mut mantissa = (self.0 & 0x7FF) as i32;
+        if self.0 & 0x8000 != 0 {
+            mantissa -= 0x800;
+        }
+        Some((0.01 * (mantissa << exponent) as f64) as f32)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        *self != Self::INVALID
+    }
+}
+impl DataPointAccess for F16 {
+    fn length(&self) -> DataPointLength {
+        DataPointLength::Byte(2)
+    }
+    fn write(&self, buf: &mut [u8]) {
+        buf[..2].copy_from_slice(&self.0.to_be_bytes());
+    }
+}
+
+/// Rounds half away from zero, `f64::round` needs std. Saturates beyond the range of `i64`.
+pub(crate) fn round(value: f64) -> i64 {
+    if value < 0.0 {
+        (value - 0.5) as i64
+    } else {
+        (value + 0.5) as i64
+    }
+}
+
+/// Time of day (DPT 10).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Time {
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+}
+impl Time {
+    /// `day` is 1 for Monday to 7 for Sunday, 0 for no day.
+    pub fn new(day: u8, hour: u8, minute: u8, second: u8) -> Self {
+        Self {
+            day: day & 0x7,
+            hour: hour & 0x1F,
+            minute: minute & 0x3F,
+            second: second & 0x3F,
+        }
+    }
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+    pub fn hour(&self) -> u8 {
+        self.hour
+    }
+    pub fn minute(&self) -> u8 {
+        self.minute
+    }
+    pub fn second(&self) -> u8 {
+        self.second
+    }
+    fn decode(value: &[u8]) -> Self {
+        Self::new(value[0] >> 5, value[0], value[1], value[2])
+    }
+}
+impl DataPointAccess for Time {
+    fn length(&self) -> DataPointLength {
+        DataPointLength::Byte(3)
+    }
+    fn write(&self, buf: &mut [u8]) {
+        buf[..3].copy_from_slice(&[self.day << 5 | self.hour, self.minute, self.second]);
+    }
+}
+
+/// Date from 1990 to 2089 (DPT 11).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Date {
+    year: u16,
+    month: u8,
+    day: u8,
+}
+impl Date {
+    pub fn new(year: u16, month: u8, day: u8) -> Self {
+        Self {
+            year,
+            month: month & 0xF,
+            day: day & 0x1F,
+        }
+    }
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+    fn decode(value: &[u8]) -> Self {
+        // Only two digits are sent, 90 to 99 are in the 20th century
+        let year = (value[2] & 0x7F) as u16;
+        let century = if year >= 90 { 1900 } else { 2000 };
+        Self::new(century + year, value[1], value[0])
+    }
+}
+impl DataPointAccess for Date {
+    fn length(&self) -> DataPointLength {
+        DataPointLength::Byte(3)
+    }
+    fn write(&self, buf: &mut [u8]) {
+        buf[..3].copy_from_slice(&[self.day, self.month, (self.year % 100) as u8]);
+    }
+}
+
+number!(
+    /// Unsigned 32 bit value (DPT 12).
+    U32,
+    u32
+);
+number!(
+    /// Signed 32 bit value (DPT 13).
+    S32,
+    i32
+);
+number!(
+    /// IEEE 754 float (DPT 14).
+    F32,
+    f32
+);
+
+/// Access control data of a reader (DPT 15): a 6 digit access code, flags and the index of
+/// the reader.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AccessData {
+    code: u32,
+    flags: u8,
+    index: u8,
+}
+impl AccessData {
+    /// Reading the code failed.
+    pub const ERROR: u8 = 0x8;
+    /// Access is granted.
+    pub const PERMISSION: u8 = 0x4;
+    /// Read the digits from right to left.
+    pub const DIRECTION: u8 = 0x2;
+    /// The code is encrypted.
+    pub const ENCRYPTED: u8 = 0x1;
+
+    /// Only the lower 6 decimal digits of `code` are sent.
+    pub fn new(code: u32, flags: u8, index: u8) -> Self {
+        Self {
+            code: code % 1_000_000,
+            flags: flags & 0xF,
+            index: index & 0xF,
+        }
+    }
+    pub fn code(&self) -> u32 {
+        self.code
+    }
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+    fn decode(value: &[u8]) -> Self {
+        // The digits are BCD, the most significant first
+        let code = value[..3].iter().fold(0, |code, byte| {
+            code * 100 + (byte >> 4) as u32 * 10 + (byte & 0xF) as u32
+        });
+        Self::new(code, value[3] >> 4, value[3])
+    }
+}
+impl DataPointAccess for AccessData {
+    fn length(&self) -> DataPointLength {
+        DataPointLength::Byte(4)
+    }
+    fn write(&self, buf: &mut [u8]) {
+        let mut code = self.code;
+        for byte in buf[..3].iter_mut().rev() {
+            *byte = (((code / 10 % 10) << 4) | (code % 10)) as u8;
+            code /= 100;
+        }
+        buf[3] = self.flags << 4 | self.index;
+    }
+}
+
+/// Up to 14 characters in ISO 8859-1 (DPT 16), padded with zeros when sent.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct String(Vec<u8, 14>);
+impl String {
+    pub fn new(data: &[u8]) -> Result<Self, FrameError> {
+        Vec::from_slice(data)
+            .map(Self)
+            .map_err(|_| FrameError::TooLong(data.len()))
+    }
+    pub fn value(&self) -> &[u8] {
+        &self.0
+    }
+    fn decode(value: &[u8]) -> Self {
+        let end = value.iter().position(|&c| c == 0).unwrap_or(value.len());
+        // Can't fail, the value is 14 bytes
+        Self(Vec::from_slice(&value[..end]).unwrap_or_default())
+    }
+}
+impl DataPointAccess for String {
+    fn length(&self) -> DataPointLength {
+        DataPointLength::Byte(14)
+    }
+    fn write(&self, buf: &mut [u8]) {
+        buf[..14].fill(0);
+        buf[..self.0.len()].copy_from_slice(&self.0);
+    }
+}
+
+/// Scene number 0 to 63 (DPT 17).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SceneNumber(u8);
+impl SceneNumber {
+    pub fn new(scene: u8) -> Self {
+        Self(scene & 0x3F)
+    }
+    pub fn value(&self) -> u8 {
+        self.0
+    }
+}
+impl DataPointAccess for SceneNumber {
+    fn length(&self) -> DataPointLength {
+        DataPointLength::Byte(1)
+    }
+    fn write(&self, buf: &mut [u8]) {
+        buf[0] = self.0;
+    }
+}
+
+/// Activates or learns a scene (DPT 18).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SceneControl(u8);
+impl SceneControl {
+    pub fn new(learn: bool, scene: u8) -> Self {
+        Self((learn as u8) << 7 | scene & 0x3F)
+    }
+    pub fn learn(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+    pub fn scene(&self) -> u8 {
+        self.0 & 0x3F
+    }
+}
+impl DataPointAccess for SceneControl {
+    fn length(&self) -> DataPointLength {
+        DataPointLength::Byte(1)
+    }
+    fn write(&self, buf: &mut [u8]) {
+        buf[0] = self.0;
+    }
+}
+
+/// Date and time with its quality (DPT 19).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DateTime {
+    year: u16,
+    month: u8,
+    day: u8,
+    day_of_week: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+    flags: u16,
+}
+impl DateTime {
+    pub const FAULT: u16 = 0x8000;
+    pub const WORKING_DAY: u16 = 0x4000;
+    /// The working day flag isn't valid.
+    pub const NO_WORKING_DAY: u16 = 0x2000;
+    pub const NO_YEAR: u16 = 0x1000;
+    pub const NO_DATE: u16 = 0x0800;
+    pub const NO_DAY_OF_WEEK: u16 = 0x0400;
+    pub const NO_TIME: u16 = 0x0200;
+    pub const SUMMER_TIME: u16 = 0x0100;
+    /// The clock is synchronized to an external source.
+    pub const CLOCK_QUALITY: u16 = 0x0080;
+    /// The external source is reliable.
+    pub const SYNC_SOURCE: u16 = 0x0040;
+
+    /// `year` is 1900 to 2155, the day of week and the flags start out as 0.
+    pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Self {
+        Self {
+            year: year.clamp(1900, 2155),
+            month: month & 0xF,
+            day: day & 0x1F,
+            day_of_week: 0,
+            hour: hour & 0x1F,
+            minute: minute & 0x3F,
+            second: second & 0x3F,
+            flags: 0,
+        }
+    }
+    /// `day_of_week` is 1 for Monday to 7 for Sunday, 0 for any day.
+    pub fn with_day_of_week(self, day_of_week: u8) -> Self {
+        Self {
+            day_of_week: day_of_week & 0x7,
+            ..self
+        }
+    }
+    pub fn with_flags(self, flags: u16) -> Self {
+        Self {
+            flags: flags & 0xFFC0,
+            ..self
+        }
+    }
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+    pub fn day_of_week(&self) -> u8 {
+        self.day_of_week
+    }
+    pub fn hour(&self) -> u8 {
+        self.hour
+    }
+    pub fn minute(&self) -> u8 {
+        self.minute
+    }
+    pub fn second(&self) -> u8 {
+        self.second
+    }
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+    fn decode(value: &[u8]) -> Self {
+        Self::new(
+            1900 + value[0] as u16,
+            value[1],
+            value[2],
+            value[3],
+            value[4],
+            value[5],
+        )
+        .with_day_of_week(value[3] >> 5)
+        .with_flags(u16::from_be_bytes([value[6], value[7]]))
+    }
+}
+impl DataPointAccess for DateTime {
+    fn length(&self) -> DataPointLength {
+        DataPointLength::Byte(8)
+    }
+    fn write(&self, buf: &mut [u8]) {
+        let flags = self.flags.to_be_bytes();
+        buf[..8].copy_from_slice(&[
+            (self.year - 1900) as u8,
+            self.month,
+            self.day,
+            self.day_of_week << 5 | self.hour,
+            self.minute,
+            self.second,
+            flags[0],
+            flags[1],
+        ]);
+    }
+}
+
+number!(
+    /// 8 bit enumeration (DPT 20).
+    N8,
+    u8
+);
+number!(
+    /// 8 status bits (DPT 21).
+    B8,
+    u8
+);
+
+/// 2 bit enumeration (DPT 23).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct N2(u8);
+impl N2 {
     pub fn new(data: u8) -> Self {
-        Self(data)
+        Self(data & 0x3)
+    }
+    pub fn value(&self) -> u8 {
+        self.0
+    }
+}
+impl DataPointAccess for N2 {
+    fn length(&self) -> DataPointLength {
+        DataPointLength::Bit(2)
+    }
+    fn write(&self, buf: &mut [u8]) {
+        buf[0] |= self.0;
+    }
+}
+
+/// Characters in ISO 8859-1 terminated by a zero (DPT 24), up to 13 to fit a standard frame.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct VarString(Vec<u8, 13>);
+impl VarString {
+    pub fn new(data: &[u8]) -> Result<Self, FrameError> {
+        if data.contains(&0) {
+            return Err(FrameError::InvalidValue);
+        }
+        Vec::from_slice(data)
+            .map(Self)
+            .map_err(|_| FrameError::TooLong(data.len()))
+    }
+    pub fn value(&self) -> &[u8] {
+        &self.0
+    }
+    fn decode(value: &[u8]) -> Result<Self, FrameError> {
+        let end = value.iter().position(|&c| c == 0).unwrap_or(value.len());
+        Self::new(&value[..end])
     }
 }
-impl DataPointAccess for U8 {
+impl DataPointAccess for VarString {
+    fn length(&self) -> DataPointLength {
+        DataPointLength::Byte(self.0.len() + 1)
+    }
+    fn write(&self, buf: &mut [u8]) {
+        buf[..self.0.len()].copy_from_slice(&self.0);
+        buf[self.0.len()] = 0;
+    }
+}
+
+/// Whether a scene is active (DPT 26).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SceneInfo(u8);
+impl SceneInfo {
+    pub fn new(inactive: bool, scene: u8) -> Self {
+        Self((inactive as u8) << 6 | scene & 0x3F)
+    }
+    pub fn inactive(&self) -> bool {
+        self.0 & 0x40 != 0
+    }
+    pub fn scene(&self) -> u8 {
+        self.0 & 0x3F
+    }
+}
+impl DataPointAccess for SceneInfo {
     fn length(&self) -> DataPointLength {
         DataPointLength::Byte(1)
     }
@@ -104,4 +894,47 @@ impl DataPointAccess for U8 {
         buf[0] = self.0;
     }
 }
-//pub struct V8 {}
+
+number!(
+    /// 16 outputs with a mask bit each (DPT 27), the mask in the upper 16 bits.
+    B32,
+    u32
+);
+
+/// UTF-8 text terminated by a zero (DPT 28), up to 13 bytes to fit a standard frame.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct UnicodeString(heapless::String<13>);
+impl UnicodeString {
+    pub fn new(data: &str) -> Result<Self, FrameError> {
+        if data.contains('\0') {
+            return Err(FrameError::InvalidValue);
+        }
+        heapless::String::try_from(data)
+            .map(Self)
+            .map_err(|_| FrameError::TooLong(data.len()))
+    }
+    pub fn value(&self) -> &str {
+        &self.0
+    }
+    fn decode(value: &[u8]) -> Result<Self, FrameError> {
+        let end = value.iter().position(|&c| c == 0).unwrap_or(value.len());
+        let data = core::str::from_utf8(&value[..end]).map_err(|_| FrameError::InvalidValue)?;
+        Self::new(data)
+    }
+}
+impl DataPointAccess for UnicodeString {
+    fn length(&self) -> DataPointLength {
+        DataPointLength::Byte(self.0.len() + 1)
+    }
+    fn write(&self, buf: &mut [u8]) {
+        buf[..self.0.len()].copy_from_slice(self.0.as_bytes());
+        buf[self.0.len()] = 0;
+    }
+}
+
+number!(
+    /// Signed 64 bit value (DPT 29).
+    V64,
+    i64
+);
@@ -0,0 +1,274 @@
+//! Datapoint types by their main and sub number, with the format, unit and range behind them.
+
+use crate::data_point::*;
+use crate::frame::FrameError;
+
+/// A datapoint type, like 1.001 for a switch or 5.001 for a percentage.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Dpt {
+    main: u16,
+    sub: u16,
+}
+
+impl Dpt {
+    pub const fn new(main: u16, sub: u16) -> Self {
+        Self { main, sub }
+    }
+
+    pub fn main(&self) -> u16 {
+        self.main
+    }
+
+    pub fn sub(&self) -> u16 {
+        self.sub
+    }
+
+    /// What the registry knows about the type, `None` if it isn't in there.
+    pub fn info(&self) -> Option<&'static DptInfo> {
+        REGISTRY.iter().find(|info| info.dpt == *self)
+    }
+}
+
+/// A datapoint type in the registry.
+///
+/// Numeric types have a range, values outside of it aren't encoded. Scaled types multiply the
+/// raw values of their format by a factor, 5.001 sends 0 to 100 % as 0 to 255.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DptInfo {
+    dpt: Dpt,
+    name: &'static str,
+    format: DataPointType,
+    unit: &'static str,
+    range: Option<(f32, f32)>,
+    scaling: Option<f64>,
+}
+
+impl DptInfo {
+    const fn new(main: u16, sub: u16, name: &'static str, format: DataPointType) -> Self {
+        Self {
+            dpt: Dpt::new(main, sub),
+            name,
+            format,
+            unit: "",
+            range: None,
+            scaling: None,
+        }
+    }
+
+    const fn with_range(self, min: f32, max: f32, unit: &'static str) -> Self {
+        Self {
+            range: Some((min, max)),
+            unit,
+            ..self
+        }
+    }
+
+    /// Values are `factor` times the raw value.
+    const fn with_scaling(self, factor: f64) -> Self {
+        Self {
+            scaling: Some(factor),
+            ..self
+        }
+    }
+
+    pub fn dpt(&self) -> Dpt {
+        self.dpt
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The format values of the type are sent in.
+    pub fn format(&self) -> DataPointType {
+        self.format
+    }
+
+    /// The unit of the values, empty if there is none.
+    pub fn unit(&self) -> &'static str {
+        self.unit
+    }
+
+    /// Smallest and largest value, `None` for types that aren't numbers.
+    pub fn range(&self) -> Option<(f32, f32)> {
+        self.range
+    }
+
+    /// Encodes `value`, scaled and rounded to the format.
+    ///
+    /// Fails with [`FrameError::InvalidValue`] if `value` is out of range or the type isn't a
+    /// number.
+    pub fn encode(&self, value: f32) -> Result<DataPoint, FrameError> {
+        let (min, max) = self.range.ok_or(FrameError::InvalidValue)?;
+        if !(min..=max).contains(&value) {
+            return Err(FrameError::InvalidValue);
+        }
+        let raw = match self.scaling {
+            Some(factor) => value as f64 / factor,
+            None => value as f64,
+        };
+        from_number(self.format, raw).ok_or(FrameError::InvalidValue)
+    }
+
+    /// The value of `data`, scaled from the raw value of the format.
+    ///
+    /// Fails with [`FrameError::InvalidValue`] if `data` isn't in the type's format, the type
+    /// isn't a number or `data` holds no value, like [`F16::INVALID`].
+    pub fn decode(&self, data: &DataPoint) -> Result<f32, FrameError> {
+        self.range.ok_or(FrameError::InvalidValue)?;
+        if data.data_point_type() != self.format {
+            return Err(FrameError::InvalidValue);
+        }
+        let raw = number(data).ok_or(FrameError::InvalidValue)?;
+        Ok(match self.scaling {
+            Some(factor) => raw * factor,
+            None => raw,
+        } as f32)
+    }
+
+    /// Decodes the APDU data of a received frame, see [`DataPoint::read`].
+    pub fn read(&self, apdu: &[u8]) -> Result<f32, FrameError> {
+        self.decode(&DataPoint::read(self.format, apdu)?)
+    }
+}
+
+/// The raw value of numeric formats.
+fn number(data: &DataPoint) -> Option<f64> {
+    Some(match data {
+        DataPoint::B1(v) => v.value() as u8 as f64,
+        DataPoint::B2(v) => v.value() as f64,
+        DataPoint::U8(v) => v.value() as f64,
+        DataPoint::V8(v) => v.value() as f64,
+        DataPoint::U16(v) => v.value() as f64,
+        DataPoint::S16(v) => v.value() as f64,
+        DataPoint::F16(v) => v.value()? as f64,
+        DataPoint::U32(v) => v.value() as f64,
+        DataPoint::S32(v) => v.value() as f64,
+        DataPoint::F32(v) => v.value() as f64,
+        DataPoint::SceneNumber(v) => v.value() as f64,
+        DataPoint::N8(v) => v.value() as f64,
+        DataPoint::N2(v) => v.value() as f64,
+        DataPoint::V64(v) => v.value() as f64,
+        _ => return None,
+    })
+}
+
+/// A value of a numeric format, rounded for the integers. `value` has to be in the range of
+/// the format.
+fn from_number(format: DataPointType, value: f64) -> Option<DataPoint> {
+    let int = round(value);
+    Some(match format {
+        DataPointType::B1 => DataPoint::B1(B1::new(int != 0)),
+        DataPointType::B2 => DataPoint::B2(B2::new(int as u8)),
+        DataPointType::U8 => DataPoint::U8(U8::new(int as u8)),
+        DataPointType::V8 => DataPoint::V8(V8::new(int as i8)),
+        DataPointType::U16 => DataPoint::U16(U16::new(int as u16)),
+        DataPointType::S16 => DataPoint::S16(S16::new(int as i16)),
+        DataPointType::F16 => DataPoint::F16(F16::new(value as f32)),
+        DataPointType::U32 => DataPoint::U32(U32::new(int as u32)),
+        DataPointType::S32 => DataPoint::S32(S32::new(int as i32)),
+        DataPointType::F32 => DataPoint::F32(F32::new(value as f32)),
+        DataPointType::SceneNumber => DataPoint::SceneNumber(SceneNumber::new(int as u8)),
+        DataPointType::N8 => DataPoint::N8(N8::new(int as u8)),
+        DataPointType::N2 => DataPoint::N2(N2::new(int as u8)),
+        DataPointType::V64 => DataPoint::V64(V64::new(int)),
+        _ => return None,
+    })
+}
+
+/// The datapoint types this crate knows about.
+static REGISTRY: &[DptInfo] = &[
+    DptInfo::new(1, 1, "Switch", DataPointType::B1).with_range(0.0, 1.0, ""),
+    DptInfo::new(1, 2, "Bool", DataPointType::B1).with_range(0.0, 1.0, ""),
+    DptInfo::new(1, 3, "Enable", DataPointType::B1).with_range(0.0, 1.0, ""),
+    DptInfo::new(1, 8, "UpDown", DataPointType::B1).with_range(0.0, 1.0, ""),
+    DptInfo::new(1, 9, "OpenClose", DataPointType::B1).with_range(0.0, 1.0, ""),
+    DptInfo::new(1, 10, "Start", DataPointType::B1).with_range(0.0, 1.0, ""),
+    DptInfo::new(1, 17, "Trigger", DataPointType::B1).with_range(0.0, 1.0, ""),
+    DptInfo::new(1, 18, "Occupancy", DataPointType::B1).with_range(0.0, 1.0, ""),
+    DptInfo::new(1, 19, "WindowDoor", DataPointType::B1).with_range(0.0, 1.0, ""),
+    DptInfo::new(1, 24, "DayNight", DataPointType::B1).with_range(0.0, 1.0, ""),
+    DptInfo::new(2, 1, "SwitchControl", DataPointType::B2).with_range(0.0, 3.0, ""),
+    DptInfo::new(3, 7, "ControlDimming", DataPointType::B1U3),
+    DptInfo::new(3, 8, "ControlBlinds", DataPointType::B1U3),
+    DptInfo::new(4, 1, "CharAscii", DataPointType::Char),
+    DptInfo::new(4, 2, "Char8859_1", DataPointType::Char),
+    DptInfo::new(5, 1, "Scaling", DataPointType::U8)
+        .with_range(0.0, 100.0, "%")
+        .with_scaling(100.0 / 255.0),
+    DptInfo::new(5, 3, "Angle", DataPointType::U8)
+        .with_range(0.0, 360.0, "°")
+        .with_scaling(360.0 / 255.0),
+    DptInfo::new(5, 4, "PercentU8", DataPointType::U8).with_range(0.0, 255.0, "%"),
+    DptInfo::new(5, 10, "Counter", DataPointType::U8).with_range(0.0, 255.0, "pulses"),
+    DptInfo::new(6, 1, "PercentV8", DataPointType::V8).with_range(-128.0, 127.0, "%"),
+    DptInfo::new(6, 10, "Counter", DataPointType::V8).with_range(-128.0, 127.0, "pulses"),
+    DptInfo::new(6, 20, "StatusMode", DataPointType::StatusMode),
+    DptInfo::new(7, 1, "Counter", DataPointType::U16).with_range(0.0, 65535.0, "pulses"),
+    DptInfo::new(7, 2, "TimePeriodMsec", DataPointType::U16).with_range(0.0, 65535.0, "ms"),
+    DptInfo::new(7, 5, "TimePeriodSec", DataPointType::U16).with_range(0.0, 65535.0, "s"),
+    DptInfo::new(7, 7, "TimePeriodHrs", DataPointType::U16).with_range(0.0, 65535.0, "h"),
+    DptInfo::new(7, 13, "Brightness", DataPointType::U16).with_range(0.0, 65535.0, "lx"),
+    DptInfo::new(7, 600, "ColourTemperature", DataPointType::U16).with_range(0.0, 65535.0, "K"),
+    DptInfo::new(8, 1, "Counter", DataPointType::S16).with_range(-32768.0, 32767.0, "pulses"),
+    DptInfo::new(8, 10, "PercentV16", DataPointType::S16)
+        .with_range(-327.68, 327.67, "%")
+        .with_scaling(0.01),
+    DptInfo::new(9, 1, "Temperature", DataPointType::F16).with_range(-273.0, F16::MAX, "°C"),
+    DptInfo::new(9, 2, "TemperatureDifference", DataPointType::F16).with_range(
+        F16::MIN,
+        F16::MAX,
+        "K",
+    ),
+    DptInfo::new(9, 4, "Illuminance", DataPointType::F16).with_range(0.0, F16::MAX, "lx"),
+    DptInfo::new(9, 5, "WindSpeed", DataPointType::F16).with_range(0.0, F16::MAX, "m/s"),
+    DptInfo::new(9, 6, "Pressure", DataPointType::F16).with_range(0.0, F16::MAX, "Pa"),
+    DptInfo::new(9, 7, "Humidity", DataPointType::F16).with_range(0.0, F16::MAX, "%"),
+    DptInfo::new(9, 8, "AirQuality", DataPointType::F16).with_range(0.0, F16::MAX, "ppm"),
+    DptInfo::new(10, 1, "TimeOfDay", DataPointType::Time),
+    DptInfo::new(11, 1, "Date", DataPointType::Date),
+    DptInfo::new(12, 1, "Counter", DataPointType::U32).with_range(0.0, 4_294_967_295.0, "pulses"),
+    DptInfo::new(13, 1, "Counter", DataPointType::S32).with_range(
+        -2_147_483_648.0,
+        2_147_483_647.0,
+        "pulses",
+    ),
+    DptInfo::new(13, 10, "ActiveEnergy", DataPointType::S32).with_range(
+        -2_147_483_648.0,
+        2_147_483_647.0,
+        "Wh",
+    ),
+    DptInfo::new(13, 13, "ActiveEnergyKwh", DataPointType::S32).with_range(
+        -2_147_483_648.0,
+        2_147_483_647.0,
+        "kWh",
+    ),
+    DptInfo::new(14, 19, "ElectricCurrent", DataPointType::F32).with_range(f32::MIN, f32::MAX, "A"),
+    DptInfo::new(14, 27, "ElectricPotential", DataPointType::F32).with_range(
+        f32::MIN,
+        f32::MAX,
+        "V",
+    ),
+    DptInfo::new(14, 56, "Power", DataPointType::F32).with_range(f32::MIN, f32::MAX, "W"),
+    DptInfo::new(14, 68, "Temperature", DataPointType::F32).with_range(-273.15, f32::MAX, "°C"),
+    DptInfo::new(15, 0, "AccessData", DataPointType::AccessData),
+    DptInfo::new(16, 0, "StringAscii", DataPointType::String),
+    DptInfo::new(16, 1, "String8859_1", DataPointType::String),
+    DptInfo::new(17, 1, "SceneNumber", DataPointType::SceneNumber).with_range(0.0, 63.0, ""),
+    DptInfo::new(18, 1, "SceneControl", DataPointType::SceneControl),
+    DptInfo::new(19, 1, "DateTime", DataPointType::DateTime),
+    DptInfo::new(20, 102, "HvacMode", DataPointType::N8).with_range(0.0, 4.0, ""),
+    DptInfo::new(21, 1, "StatusGen", DataPointType::B8),
+    DptInfo::new(23, 1, "OnOffAction", DataPointType::N2).with_range(0.0, 3.0, ""),
+    DptInfo::new(24, 1, "VarString8859_1", DataPointType::VarString),
+    DptInfo::new(26, 1, "SceneInfo", DataPointType::SceneInfo),
+    DptInfo::new(27, 1, "CombinedInfoOnOff", DataPointType::B32),
+    DptInfo::new(28, 1, "Utf8", DataPointType::UnicodeString),
+    DptInfo::new(29, 10, "ActiveEnergyV64", DataPointType::V64).with_range(
+        i64::MIN as f32,
+        i64::MAX as f32,
+        "Wh",
+    ),
+];
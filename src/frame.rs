@@ -1,34 +1,101 @@
-use crate::data_point::{DataPoint, DataPointAccess, DataPointLength};
-use crate::ncn51_driver::FRAME_POOL;
-use defmt::*;
-use heapless::{pool::boxed::Box, Vec};
-use num_enum::{IntoPrimitive, UnsafeFromPrimitive};
+use crate::data_point::DataPointAccess;
+use embassy_time::Instant;
+use heapless::{pool::boxed::Box, pool::boxed::BoxBlock, Vec};
+use num_enum::{IntoPrimitive, TryFromPrimitive, UnsafeFromPrimitive};
+use pool::FRAME_POOL;
+use static_cell::StaticCell;
 
-#[derive(Format, UnsafeFromPrimitive, IntoPrimitive)]
+const FRAME_BUFFER_SIZE: usize = 264;
+const FRAME_POOL_SIZE: usize = 8;
+
+mod pool {
+    #![allow(non_camel_case_types)]
+    use heapless::{box_pool, Vec};
+    box_pool!(FRAME_POOL: Vec<u8, { super::FRAME_BUFFER_SIZE }>);
+}
+
+pub type FrameBlock = BoxBlock<Vec<u8, FRAME_BUFFER_SIZE>>;
+
+/// Hands the static frame buffers over to the frame pool.
+///
+/// Has to be called before the first frame is allocated, calling it again is a no-op.
+pub fn init_frame_pool() {
+    static FRAME_BUFFER: StaticCell<[FrameBlock; FRAME_POOL_SIZE]> = StaticCell::new();
+    if let Some(blocks) = FRAME_BUFFER.try_init([const { BoxBlock::new() }; FRAME_POOL_SIZE]) {
+        extend_frame_pool(blocks);
+    }
+}
+
+/// Adds more buffers to the frame pool, e.g. for tests running several stacks at once.
+pub fn extend_frame_pool(blocks: &'static mut [FrameBlock]) {
+    for block in blocks {
+        FRAME_POOL.manage(block);
+    }
+}
+
+#[derive(Debug, UnsafeFromPrimitive, IntoPrimitive)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum FrameType {
     Standard = 1,
     Extended = 0,
 }
 
-#[derive(Format)]
+impl FrameType {
+    const MASK: u8 = 0xD3;
+    const STANDARD: u8 = 0x90;
+    const EXTENDED: u8 = 0x10;
+
+    /// Returns the frame type for an L_Data control field, `None` for anything else.
+    pub fn from_ctrl(ctrl: u8) -> Option<Self> {
+        match ctrl & Self::MASK {
+            Self::STANDARD => Some(Self::Standard),
+            Self::EXTENDED => Some(Self::Extended),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum FrameError {
     InvalidLength,
     OutOfMemory,
-    Checksum,
+    Checksum {
+        expected: u8,
+        actual: u8,
+    },
     InvalidTpdu(u8),
+    /// An APCI that isn't a known application layer service.
+    InvalidApci(u16),
+    InvalidControlField(u8),
+    TooShort(usize),
+    TooLong(usize),
+    LengthMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// A data point value that isn't valid in its format.
+    InvalidValue,
 }
 
 type FrameResult<T> = Result<T, FrameError>;
 
-#[derive(Format, UnsafeFromPrimitive, IntoPrimitive)]
+/// Odd parity over all bytes, the frame's last byte has to match this.
+fn checksum(buf: &[u8]) -> u8 {
+    !buf.iter().fold(0, |acc, &v| acc ^ v)
+}
+
+#[derive(Debug, UnsafeFromPrimitive, IntoPrimitive)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum AddressType {
     Individual = 0,
     Group = 1,
 }
 
-#[derive(Format, UnsafeFromPrimitive, IntoPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, UnsafeFromPrimitive, IntoPrimitive)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum Priority {
     Low = 0x3,
@@ -37,14 +104,15 @@ pub enum Priority {
     System = 0x0,
 }
 
-#[derive(Format, UnsafeFromPrimitive, IntoPrimitive)]
+#[derive(Debug, UnsafeFromPrimitive, IntoPrimitive)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
-enum Repeated {
+pub enum Repeated {
     Repeated = 0,
     NotRepeated = 1,
 }
 
-#[derive(PartialEq, PartialOrd, Hash, Clone)]
+#[derive(Debug, PartialEq, PartialOrd, Hash, Clone)]
 pub struct IndividualAddress(u16);
 
 impl IndividualAddress {
@@ -72,8 +140,9 @@ impl From<u16> for IndividualAddress {
     }
 }
 
-impl Format for IndividualAddress {
-    fn format(&self, fmt: Formatter) {
+#[cfg(feature = "defmt")]
+impl defmt::Format for IndividualAddress {
+    fn format(&self, fmt: defmt::Formatter) {
         defmt::write!(
             fmt,
             "{}.{}.{}",
@@ -84,7 +153,7 @@ impl Format for IndividualAddress {
     }
 }
 
-#[derive(PartialEq, PartialOrd, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct GroupAddress(u16);
 
 impl GroupAddress {
@@ -100,8 +169,9 @@ impl GroupAddress {
     }
 }
 
-impl Format for GroupAddress {
-    fn format(&self, fmt: Formatter) {
+#[cfg(feature = "defmt")]
+impl defmt::Format for GroupAddress {
+    fn format(&self, fmt: defmt::Formatter) {
         defmt::write!(
             fmt,
             "{}/{}/{}",
@@ -124,7 +194,8 @@ impl From<u16> for GroupAddress {
     }
 }
 
-#[derive(Format)]
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Address {
     Individual(IndividualAddress),
     Group(GroupAddress),
@@ -139,13 +210,15 @@ impl Address {
     }
 }
 
-#[derive(Format, IntoPrimitive)]
+#[derive(Debug, IntoPrimitive)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(usize)]
 pub enum TpciBits {
     Six = 6,
     Eight = 8,
 }
-#[derive(Format, IntoPrimitive)]
+#[derive(Debug, IntoPrimitive)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(usize)]
 pub enum ApciBits {
     Four = 6,
@@ -155,12 +228,13 @@ pub enum ApciBits {
 pub trait FrameReader {
     const CTRL: usize = 0;
     const CTRL_OFFSET: usize;
-    const SRC_ADDR_OFFSET: usize = Self::CTRL_OFFSET + 0;
+    const SRC_ADDR_OFFSET: usize = Self::CTRL_OFFSET;
     const DST_ADDR_OFFSET: usize = Self::CTRL_OFFSET + 2;
     const HEADER_LENGTH: usize = Self::CTRL_OFFSET + 6;
     const APCI_OFFSET: usize = Self::HEADER_LENGTH - 1;
     const APCI_BASE_SIZE: usize = 1;
-    const LG_FIELD: usize = 5;
+    const LG_FIELD: usize = Self::CTRL_OFFSET + 4;
+    const LG_MASK: u8 = 0xFF;
     const AT_FIELD: usize;
     const HOP_COUNT_FIELD: usize;
     const TPCI_OFFSET: usize = Self::HEADER_LENGTH - 1;
@@ -205,7 +279,7 @@ pub trait FrameReader {
         }
     }
     fn tpci_seq(&self) -> u8 {
-        (self.data()[Self::TPCI_OFFSET] >> 2) & 0x7
+        (self.data()[Self::TPCI_OFFSET] >> 2) & 0xF
     }
     fn apci(&self, bits: ApciBits) -> u16 {
         let apci: u16 = ((self.data()[Self::APCI_OFFSET] as u16 & 0x3) << 8)
@@ -216,13 +290,42 @@ pub trait FrameReader {
         }
     }
     fn apdu_data(&self) -> &[u8] {
-        &self.data()[Self::APCI_OFFSET + 1..self.length() as usize]
+        // The checksum isn't part of the APDU
+        &self.data()[Self::APCI_OFFSET + 1..self.length() as usize - 1]
     }
 
     fn checksum(&self) -> u8 {
-        !self.data().iter().fold(0, |acc, &v| acc ^ v)
+        let data = self.data();
+        checksum(&data[..data.len() - 1])
     }
 
+    /// Checks size, length field and checksum of a raw frame of this type.
+    fn validate(buf: &[u8]) -> FrameResult<()> {
+        if buf.len() < Self::MIN_FRAME_SIZE {
+            return Err(FrameError::TooShort(buf.len()));
+        }
+        if buf.len() > Self::MAX_FRAME_SIZE {
+            return Err(FrameError::TooLong(buf.len()));
+        }
+        let expected = (buf[Self::LG_FIELD] & Self::LG_MASK) as usize + Self::HEADER_LENGTH + 1;
+        if buf.len() != expected {
+            return Err(FrameError::LengthMismatch {
+                expected,
+                actual: buf.len(),
+            });
+        }
+        let (data, actual) = buf.split_at(buf.len() - 1);
+        let expected = checksum(data);
+        if actual[0] != expected {
+            return Err(FrameError::Checksum {
+                expected,
+                actual: actual[0],
+            });
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "defmt")]
     fn format(&self, fmt: defmt::Formatter) {
         defmt::write!(fmt, "Frame type: {}\n", self.frame_type());
         defmt::write!(fmt, "Priority: {}\n", self.priority());
@@ -240,6 +343,7 @@ pub trait FrameWriter: FrameReader + Sized {
     fn new(size: usize) -> FrameResult<Self>;
     fn set_addr_type(&mut self, addr_type: AddressType) {
         let at: u8 = addr_type.into();
+        self.mut_data()[Self::AT_FIELD] &= !(1 << 7);
         self.mut_data()[Self::AT_FIELD] |= at << 7;
     }
     fn set_priority(&mut self, priority: Priority) {
@@ -248,6 +352,7 @@ pub trait FrameWriter: FrameReader + Sized {
     }
     fn set_repeated(&mut self, repeated: Repeated) {
         let r: u8 = repeated.into();
+        self.mut_data()[Self::CTRL] &= !(1 << 5);
         self.mut_data()[Self::CTRL] |= r << 5;
     }
     fn set_hop_count(&mut self, hop_count: u8) {
@@ -268,24 +373,34 @@ pub trait FrameWriter: FrameReader + Sized {
             }
         }
     }
-    fn from_datapoint(datapoint: &DataPoint) -> FrameResult<Self> {
-        let size = match datapoint.length() {
-            DataPointLength::Bit(n) => {
-                if n < 6 {
-                    0
-                } else {
-                    1
-                }
-            }
-            DataPointLength::Byte(n) => n,
-        };
+    fn from_datapoint(datapoint: &impl DataPointAccess) -> FrameResult<Self> {
+        let size = datapoint.byte_length();
         let mut frame = Self::new(Self::MAX_FRAME_SIZE)?;
-        datapoint.write(
-            &mut frame.mut_data()[Self::APCI_OFFSET + 1 + size..Self::APCI_OFFSET + size + 2],
-        );
+        // Short values go into the APCI byte, longer ones follow it
+        let start = Self::APCI_OFFSET + 1 + size.min(1);
+        datapoint.write(&mut frame.mut_data()[start..Self::APCI_OFFSET + size + 2]);
         frame.set_length(Self::HEADER_LENGTH + Self::APCI_BASE_SIZE + size + 1)?;
         Ok(frame)
     }
+    /// Frame carrying `apdu`, which starts at the TPCI byte. The TPCI bits are left to the
+    /// transport layer.
+    fn from_apdu(apdu: &[u8]) -> FrameResult<Self> {
+        let mut frame = Self::new(Self::MAX_FRAME_SIZE)?;
+        frame
+            .mut_data()
+            .get_mut(Self::TPCI_OFFSET..Self::TPCI_OFFSET + apdu.len())
+            .ok_or(FrameError::TooLong(apdu.len()))?
+            .copy_from_slice(apdu);
+        frame.set_length(Self::HEADER_LENGTH + apdu.len())?;
+        Ok(frame)
+    }
+    /// Copies a raw frame of this type after running it through [`FrameReader::validate`].
+    fn parse(buf: &[u8]) -> FrameResult<Self> {
+        Self::validate(buf)?;
+        let mut frame = Self::new(buf.len())?;
+        frame.mut_data().copy_from_slice(buf);
+        Ok(frame)
+    }
     fn set_tpci(&mut self, bits: TpciBits, val: u8) {
         match bits {
             TpciBits::Six => {
@@ -295,7 +410,7 @@ pub trait FrameWriter: FrameReader + Sized {
         }
     }
     fn set_tpci_seq(&mut self, val: u8) {
-        self.mut_data()[Self::TPCI_OFFSET] |= (val & 0x7) << 2;
+        self.mut_data()[Self::TPCI_OFFSET] |= (val & 0xF) << 2;
     }
     fn set_apci(&mut self, bits: ApciBits, val: u16) {
         match bits {
@@ -319,12 +434,13 @@ impl FrameReader for StandardFrame {
     const HOP_COUNT_FIELD: usize = 5;
     const MAX_FRAME_SIZE: usize = 24;
     const MIN_FRAME_SIZE: usize = 8;
+    const LG_MASK: u8 = 0xF;
 
     fn data(&self) -> &[u8] {
         &self.0
     }
     fn length(&self) -> u8 {
-        (self.0[Self::LG_FIELD] & 0xF) + Self::HEADER_LENGTH as u8 + 1
+        (self.0[Self::LG_FIELD] & Self::LG_MASK) + Self::HEADER_LENGTH as u8 + 1
     }
 }
 
@@ -333,8 +449,10 @@ impl FrameWriter for StandardFrame {
         &mut self.0
     }
     fn set_length(&mut self, length: usize) -> FrameResult<()> {
-        if length <= Self::MAX_FRAME_SIZE || length >= Self::MIN_FRAME_SIZE {
-            self.0[Self::LG_FIELD] |= (length as u8 - Self::HEADER_LENGTH as u8 - 1) & 0xF;
+        if (Self::MIN_FRAME_SIZE..=Self::MAX_FRAME_SIZE).contains(&length) {
+            self.0[Self::LG_FIELD] &= !Self::LG_MASK;
+            self.0[Self::LG_FIELD] |=
+                (length as u8 - Self::HEADER_LENGTH as u8 - 1) & Self::LG_MASK;
             self.0
                 .resize_default(length)
                 .map_err(|_| FrameError::InvalidLength)?;
@@ -344,7 +462,8 @@ impl FrameWriter for StandardFrame {
         }
     }
     fn set_frame_type(&mut self) {
-        self.0[Self::CTRL] |= 0x90;
+        // Standard frame, not repeated
+        self.0[Self::CTRL] |= 0xB0;
     }
     fn new(size: usize) -> FrameResult<Self> {
         let mut buf = FRAME_POOL
@@ -359,12 +478,19 @@ impl FrameWriter for StandardFrame {
     }
 }
 
+#[cfg(feature = "defmt")]
 impl defmt::Format for StandardFrame {
-    fn format(&self, fmt: Formatter) {
+    fn format(&self, fmt: defmt::Formatter) {
         FrameReader::format(self, fmt);
     }
 }
 
+impl core::fmt::Debug for StandardFrame {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("StandardFrame").field(&self.data()).finish()
+    }
+}
+
 impl TryFrom<&StandardFrame> for StandardFrame {
     type Error = FrameError;
     fn try_from(value: &StandardFrame) -> Result<Self, Self::Error> {
@@ -396,8 +522,8 @@ impl FrameWriter for ExtendedFrame {
         &mut self.0
     }
     fn set_length(&mut self, length: usize) -> FrameResult<()> {
-        if length <= Self::MAX_FRAME_SIZE || length >= Self::MIN_FRAME_SIZE {
-            self.0[Self::LG_FIELD] = self.0.len() as u8 - Self::HEADER_LENGTH as u8 - 1;
+        if (Self::MIN_FRAME_SIZE..=Self::MAX_FRAME_SIZE).contains(&length) {
+            self.0[Self::LG_FIELD] = (length - Self::HEADER_LENGTH - 1) as u8;
             self.0
                 .resize_default(length)
                 .map_err(|_| FrameError::InvalidLength)?;
@@ -407,7 +533,8 @@ impl FrameWriter for ExtendedFrame {
         }
     }
     fn set_frame_type(&mut self) {
-        self.0[Self::CTRL] |= 0x10;
+        // Extended frame, not repeated
+        self.0[Self::CTRL] |= 0x30;
     }
     fn new(size: usize) -> FrameResult<Self> {
         let mut buf = FRAME_POOL
@@ -419,12 +546,19 @@ impl FrameWriter for ExtendedFrame {
     }
 }
 
+#[cfg(feature = "defmt")]
 impl defmt::Format for ExtendedFrame {
-    fn format(&self, fmt: Formatter) {
+    fn format(&self, fmt: defmt::Formatter) {
         FrameReader::format(self, fmt);
     }
 }
 
+impl core::fmt::Debug for ExtendedFrame {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("ExtendedFrame").field(&self.data()).finish()
+    }
+}
+
 impl TryFrom<&ExtendedFrame> for ExtendedFrame {
     type Error = FrameError;
     fn try_from(value: &ExtendedFrame) -> Result<Self, Self::Error> {
@@ -434,7 +568,8 @@ impl TryFrom<&ExtendedFrame> for ExtendedFrame {
     }
 }
 
-#[derive(Format)]
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Frame {
     Standard(StandardFrame),
     Extended(ExtendedFrame),
@@ -567,13 +702,30 @@ impl Frame {
             Self::Extended(f) => f.set_apci(bits, val),
         }
     }
-    pub fn from_datapoint(datapoint: &DataPoint) -> FrameResult<Self> {
+    /// Parses a raw TP1 frame including the checksum, e.g. from a log or another medium.
+    pub fn parse(buf: &[u8]) -> FrameResult<Self> {
+        let ctrl = *buf.first().ok_or(FrameError::TooShort(0))?;
+        match FrameType::from_ctrl(ctrl) {
+            Some(FrameType::Standard) => StandardFrame::parse(buf).map(|f| f.into()),
+            Some(FrameType::Extended) => ExtendedFrame::parse(buf).map(|f| f.into()),
+            None => Err(FrameError::InvalidControlField(ctrl)),
+        }
+    }
+    pub fn from_datapoint(datapoint: &impl DataPointAccess) -> FrameResult<Self> {
         if datapoint.byte_length() > 14 {
             ExtendedFrame::from_datapoint(datapoint).map(|v| v.into())
         } else {
             StandardFrame::from_datapoint(datapoint).map(|v| v.into())
         }
     }
+    /// Frame carrying `apdu`, starting at the TPCI byte, see [`crate::application_layer::Apdu`].
+    pub fn from_apdu(apdu: &[u8]) -> FrameResult<Self> {
+        if apdu.len() > 16 {
+            ExtendedFrame::from_apdu(apdu).map(|v| v.into())
+        } else {
+            StandardFrame::from_apdu(apdu).map(|v| v.into())
+        }
+    }
 }
 
 impl From<StandardFrame> for Frame {
@@ -597,3 +749,109 @@ impl TryFrom<&Frame> for Frame {
         }
     }
 }
+
+/// Acknowledgement byte a receiver sends right after a frame.
+#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum Acknowledgement {
+    Ack = 0xCC,
+    Nack = 0x0C,
+    Busy = 0xC0,
+    NackBusy = 0x00,
+}
+
+/// What a captured [`Telegram`] turned out to be.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TelegramKind {
+    Acknowledgement(Acknowledgement),
+    Frame,
+    Corrupt(FrameError),
+}
+
+/// Raw bytes of a telegram as a bus monitor saw them on the line, not validated in any way.
+pub struct Telegram {
+    data: Box<FRAME_POOL>,
+    timestamp: Instant,
+}
+
+impl Telegram {
+    /// Empty telegram whose first byte arrived at `timestamp`.
+    pub fn new(timestamp: Instant) -> FrameResult<Self> {
+        let data = FRAME_POOL
+            .alloc(Vec::new())
+            .map_err(|_| FrameError::OutOfMemory)?;
+        Ok(Self { data, timestamp })
+    }
+
+    pub fn from_slice(buf: &[u8], timestamp: Instant) -> FrameResult<Self> {
+        let mut telegram = Self::new(timestamp)?;
+        telegram
+            .data
+            .extend_from_slice(buf)
+            .map_err(|_| FrameError::TooLong(buf.len()))?;
+        Ok(telegram)
+    }
+
+    pub fn push(&mut self, byte: u8) -> FrameResult<()> {
+        self.data
+            .push(byte)
+            .map_err(|_| FrameError::TooLong(self.data.len() + 1))
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn timestamp(&self) -> Instant {
+        self.timestamp
+    }
+
+    pub fn kind(&self) -> TelegramKind {
+        if let [byte] = self.data() {
+            if let Ok(ack) = Acknowledgement::try_from(*byte) {
+                return TelegramKind::Acknowledgement(ack);
+            }
+        }
+        let ctrl = match self.data().first() {
+            Some(ctrl) => *ctrl,
+            None => return TelegramKind::Corrupt(FrameError::TooShort(0)),
+        };
+        let valid = match FrameType::from_ctrl(ctrl) {
+            Some(FrameType::Standard) => StandardFrame::validate(self.data()),
+            Some(FrameType::Extended) => ExtendedFrame::validate(self.data()),
+            None => Err(FrameError::InvalidControlField(ctrl)),
+        };
+        match valid {
+            Ok(()) => TelegramKind::Frame,
+            Err(e) => TelegramKind::Corrupt(e),
+        }
+    }
+
+    /// Parses the telegram as a frame, fails for acknowledgements and corrupt frames.
+    pub fn frame(&self) -> FrameResult<Frame> {
+        Frame::parse(self.data())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Telegram {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(
+            fmt,
+            "{} ms: {:#04X}",
+            self.timestamp.as_millis(),
+            self.data()
+        );
+    }
+}
+
+impl core::fmt::Debug for Telegram {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Telegram")
+            .field("timestamp", &self.timestamp)
+            .field("data", &self.data())
+            .finish()
+    }
+}
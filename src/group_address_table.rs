@@ -0,0 +1,97 @@
+//! Group address table, maps the TSAPs of the transport layer to group addresses.
+
+use crate::frame::GroupAddress;
+use crate::table::{Table, TooShort};
+
+/// The length byte counts the individual address in the first entry as well.
+const MAX_GROUP_ADDRESSES: usize = 254;
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GroupAddressTableError {
+    TooShort {
+        expected: usize,
+        actual: usize,
+    },
+    /// The group addresses aren't in ascending order, the entry at the index breaks it.
+    NotSorted(usize),
+}
+
+impl From<TooShort> for GroupAddressTableError {
+    fn from(err: TooShort) -> Self {
+        Self::TooShort {
+            expected: err.expected,
+            actual: err.actual,
+        }
+    }
+}
+
+/// The group addresses the device communicates on, in the layout ETS downloads: the
+/// individual address as TSAP 0, then the group addresses in ascending order.
+pub struct GroupAddressTable {
+    addresses: Table<GroupAddress, MAX_GROUP_ADDRESSES>,
+}
+
+impl GroupAddressTable {
+    const ENTRY_SIZE: usize = 2;
+
+    pub const fn new() -> Self {
+        Self {
+            addresses: Table::new(),
+        }
+    }
+
+    pub fn load(&self, buf: &[u8]) -> Result<(), GroupAddressTableError> {
+        // The length counts the individual address, which is skipped
+        let entries = buf.first().map_or(0, |len| len.saturating_sub(1)) as usize;
+        let mut last = None;
+        self.addresses.load(
+            buf,
+            1 + Self::ENTRY_SIZE,
+            entries,
+            Self::ENTRY_SIZE,
+            |i, entry| {
+                let address = GroupAddress::from(entry);
+                if last.is_some_and(|last| last >= address) {
+                    return Err(GroupAddressTableError::NotSorted(i + 1));
+                }
+                last = Some(address);
+                Ok(address)
+            },
+        )
+    }
+
+    /// Empties the table, the device then neither sends nor receives group telegrams.
+    pub fn clear(&self) {
+        self.addresses.clear();
+    }
+
+    /// Number of group addresses, not counting the individual address.
+    pub fn len(&self) -> usize {
+        self.addresses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn group_address(&self, tsap: u8) -> Option<GroupAddress> {
+        let index = (tsap as usize).checked_sub(1)?;
+        self.addresses.lock(|table| table.get(index).copied())
+    }
+
+    pub fn tsap(&self, address: &GroupAddress) -> Option<u8> {
+        self.addresses.lock(|table| {
+            table
+                .binary_search(address)
+                .ok()
+                .map(|index| index as u8 + 1)
+        })
+    }
+}
+
+impl Default for GroupAddressTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,62 +1,250 @@
-use defmt::*;
-use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
-use embassy_sync::channel::{Channel, Sender};
-use embassy_sync::lazy_lock::LazyLock;
-use embassy_sync::signal::Signal;
+//! Group objects, the values a device exchanges with others over group addresses.
 
-#[derive(PartialEq, Format)]
+use crate::data_point::*;
+use crate::frame::{FrameError, Priority};
+use heapless::Vec;
+use num_enum::TryFromPrimitive;
+
+/// Largest group object value, what fits into a standard frame.
+pub const MAX_VALUE_SIZE: usize = 14;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum GroupObjectState {
     ReadRequest,
     WriteRequest,
     Transmitting,
     Ok,
+    /// The value was changed from the bus.
     Update,
     Error,
 }
 
+/// Size of a group object's value, as encoded in the type byte of its descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum GroupObjectType {
+    Bit1 = 0,
+    Bit2 = 1,
+    Bit3 = 2,
+    Bit4 = 3,
+    Bit5 = 4,
+    Bit6 = 5,
+    Bit7 = 6,
+    Byte1 = 7,
+    Byte2 = 8,
+    Byte3 = 9,
+    Byte4 = 10,
+    Byte6 = 11,
+    Byte8 = 12,
+    Byte10 = 13,
+    Byte14 = 14,
+}
+
+impl GroupObjectType {
+    pub fn length(&self) -> DataPointLength {
+        match self {
+            Self::Byte1 => DataPointLength::Byte(1),
+            Self::Byte2 => DataPointLength::Byte(2),
+            Self::Byte3 => DataPointLength::Byte(3),
+            Self::Byte4 => DataPointLength::Byte(4),
+            Self::Byte6 => DataPointLength::Byte(6),
+            Self::Byte8 => DataPointLength::Byte(8),
+            Self::Byte10 => DataPointLength::Byte(10),
+            Self::Byte14 => DataPointLength::Byte(14),
+            bits => DataPointLength::Bit(*bits as usize + 1),
+        }
+    }
+
+    /// Number of bytes the value takes up behind the APCI, 0 for values up to 6 bits, which
+    /// are sent in the APCI byte.
+    pub fn byte_length(&self) -> usize {
+        match self.length() {
+            DataPointLength::Bit(n) if n <= 6 => 0,
+            DataPointLength::Bit(_) => 1,
+            DataPointLength::Byte(n) => n,
+        }
+    }
+
+    /// The [`DataPointType`] for values of this type, if there is a format of its size. Sizes
+    /// shared by several formats map to the plain numbers.
+    pub fn data_point_type(&self) -> Option<DataPointType> {
+        match self {
+            Self::Bit1 => Some(DataPointType::B1),
+            Self::Bit2 => Some(DataPointType::B2),
+            Self::Bit4 => Some(DataPointType::B1U3),
+            Self::Byte1 => Some(DataPointType::U8),
+            Self::Byte2 => Some(DataPointType::U16),
+            Self::Byte4 => Some(DataPointType::U32),
+            Self::Byte8 => Some(DataPointType::V64),
+            Self::Byte14 => Some(DataPointType::String),
+            _ => None,
+        }
+    }
+
+    /// Whether `data` has the size of this type.
+    pub fn matches(&self, data: &DataPoint) -> bool {
+        self.length() == data.length()
+    }
+}
+
+/// Config byte of a group object descriptor.
+///
+/// | Bit | Meaning                                      |
+/// |-----|----------------------------------------------|
+/// | 7   | Update, responses from the bus set the value |
+/// | 6   | Transmit, the value may be written to the bus|
+/// | 5   | Read on init, the value is read at startup   |
+/// | 4   | Write, writes from the bus set the value     |
+/// | 3   | Read, reads from the bus are answered        |
+/// | 2   | Communication, the object is on the bus      |
+/// | 1-0 | Priority                                     |
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct GroupObjectConfig(u8);
+
+impl GroupObjectConfig {
+    pub const fn new(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub fn priority(&self) -> Priority {
+        match self.0 & 0x03 {
+            0 => Priority::System,
+            1 => Priority::Normal,
+            2 => Priority::Urgent,
+            _ => Priority::Low,
+        }
+    }
+
+    pub fn update(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+
+    pub fn transmit(&self) -> bool {
+        self.0 & 0x40 != 0
+    }
+
+    pub fn read_on_init(&self) -> bool {
+        self.0 & 0x20 != 0
+    }
+
+    pub fn write(&self) -> bool {
+        self.0 & 0x10 != 0
+    }
+
+    pub fn read(&self) -> bool {
+        self.0 & 0x08 != 0
+    }
+
+    pub fn communication(&self) -> bool {
+        self.0 & 0x04 != 0
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct GroupObject {
+    config: GroupObjectConfig,
+    object_type: GroupObjectType,
     state: GroupObjectState,
-    signal: Signal<ThreadModeRawMutex, GroupObjectState>,
-    value: u8,
-    sender: LazyLock<
-        Sender<
-            'static,
-            ThreadModeRawMutex,
-            &'static Signal<ThreadModeRawMutex, GroupObjectState>,
-            4,
-        >,
-    >,
+    value: Vec<u8, MAX_VALUE_SIZE>,
+    /// Counts the updates from the bus, for waiting on the next one.
+    updates: u16,
 }
 
 impl GroupObject {
-    pub async fn read_value(&'static mut self) {
-        // TODO: Check initial state
-        self.state = GroupObjectState::Transmitting;
-        self.sender.get().send(&self.signal).await;
-        self.state = self.signal.wait().await;
-        if self.state != GroupObjectState::Ok {
-            info!("Invalid state: {:?}", self.state);
+    /// A group object with its value all zeros.
+    pub fn new(config: GroupObjectConfig, object_type: GroupObjectType) -> Self {
+        let mut value = Vec::new();
+        // Can't fail, no type is larger than the maximum
+        let _ = value.resize(object_type.byte_length().max(1), 0);
+        Self {
+            config,
+            object_type,
+            state: GroupObjectState::Ok,
+            value,
+            updates: 0,
         }
     }
+
+    pub fn config(&self) -> GroupObjectConfig {
+        self.config
+    }
+
+    pub fn object_type(&self) -> GroupObjectType {
+        self.object_type
+    }
+
+    pub fn state(&self) -> GroupObjectState {
+        self.state
+    }
+
+    /// The raw value, a single byte holding the bits for values up to 7 bits.
+    pub fn value(&self) -> &[u8] {
+        &self.value
+    }
+
+    /// The value as a [`DataPoint`] of the object's type, if there is a format for it.
+    pub fn data(&self) -> Option<DataPoint> {
+        let dpt = self.object_type.data_point_type()?;
+        DataPoint::from_value(dpt, &self.value).ok()
+    }
+
+    pub(crate) fn updates(&self) -> u16 {
+        self.updates
+    }
+
+    pub(crate) fn set_state(&mut self, state: GroupObjectState) {
+        if state == GroupObjectState::Update {
+            self.updates = self.updates.wrapping_add(1);
+        }
+        self.state = state;
+    }
+
+    /// Sets the value from `data`, which has to be of the object's type.
+    pub(crate) fn set_data(&mut self, data: &DataPoint) -> Result<(), FrameError> {
+        if !self.object_type.matches(data) {
+            return Err(FrameError::InvalidLength);
+        }
+        self.value.fill(0);
+        data.write(&mut self.value);
+        Ok(())
+    }
+
+    /// Sets the value from the APDU data of a received frame, starting at the APCI byte
+    /// holding the short values.
+    pub(crate) fn set_apdu(&mut self, apdu: &[u8]) -> Result<(), FrameError> {
+        match self.object_type.length() {
+            DataPointLength::Bit(n) if n <= 6 && apdu.len() == 1 => {
+                self.value[0] = apdu[0] & ((1 << n) - 1);
+            }
+            DataPointLength::Bit(n) if n > 6 && apdu.len() == 2 => {
+                self.value[0] = apdu[1] & ((1 << n) - 1);
+            }
+            DataPointLength::Byte(n) if apdu.len() == n + 1 => {
+                self.value.copy_from_slice(&apdu[1..]);
+            }
+            _ => return Err(FrameError::InvalidLength),
+        }
+        Ok(())
+    }
 }
 
-pub static GROUP_OBJECT_CHANNEL: Channel<
-    ThreadModeRawMutex,
-    &'static Signal<ThreadModeRawMutex, GroupObjectState>,
-    4,
-> = Channel::new();
-
-pub static GROUP_OBJECTS: [GroupObject; 2] = [
-    GroupObject {
-        state: GroupObjectState::Ok,
-        signal: Signal::new(),
-        value: 0,
-        sender: LazyLock::new(|| GROUP_OBJECT_CHANNEL.sender()),
-    },
-    GroupObject {
-        state: GroupObjectState::Ok,
-        signal: Signal::new(),
-        value: 1,
-        sender: LazyLock::new(|| GROUP_OBJECT_CHANNEL.sender()),
-    },
-];
+/// Writes the raw value, so a frame can be built from the object.
+impl DataPointAccess for GroupObject {
+    fn length(&self) -> DataPointLength {
+        self.object_type.length()
+    }
+    fn write(&self, buf: &mut [u8]) {
+        match self.object_type.length() {
+            DataPointLength::Bit(_) => buf[0] |= self.value[0],
+            DataPointLength::Byte(_) => buf[..self.value.len()].copy_from_slice(&self.value),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Group object association table, links the TSAPs of the group address table to the group
+//! objects (ASAPs) of the application.
+
+use crate::table::{Table, TooShort};
+use heapless::Vec;
+
+const MAX_ASSOCIATIONS: usize = 255;
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GroupObjectAssociationTableError {
+    TooShort { expected: usize, actual: usize },
+}
+
+impl From<TooShort> for GroupObjectAssociationTableError {
+    fn from(err: TooShort) -> Self {
+        Self::TooShort {
+            expected: err.expected,
+            actual: err.actual,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Association {
+    tsap: u8,
+    asap: u8,
+}
+
+/// Which group objects communicate over which group addresses, as TSAP and ASAP byte pairs.
+/// A group object sends on the TSAP of its first association.
+pub struct GroupObjectAssociationTable {
+    associations: Table<Association, MAX_ASSOCIATIONS>,
+}
+
+impl GroupObjectAssociationTable {
+    const ENTRY_SIZE: usize = 2;
+
+    pub const fn new() -> Self {
+        Self {
+            associations: Table::new(),
+        }
+    }
+
+    pub fn load(&self, buf: &[u8]) -> Result<(), GroupObjectAssociationTableError> {
+        let entries = buf.first().copied().unwrap_or_default() as usize;
+        self.associations
+            .load(buf, 1, entries, Self::ENTRY_SIZE, |_, entry| {
+                Ok(Association {
+                    tsap: entry[0],
+                    asap: entry[1],
+                })
+            })
+    }
+
+    /// Empties the table, group telegrams then don't reach any group object.
+    pub fn clear(&self) {
+        self.associations.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.associations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// All group objects associated with `tsap`, in table order.
+    pub fn asaps(&self, tsap: u8) -> Vec<u8, MAX_ASSOCIATIONS> {
+        self.associations.lock(|table| {
+            table
+                .iter()
+                .filter(|association| association.tsap == tsap)
+                .map(|association| association.asap)
+                .collect()
+        })
+    }
+
+    /// The TSAP group object `asap` sends on.
+    pub fn tsap(&self, asap: u8) -> Option<u8> {
+        self.associations.lock(|table| {
+            table
+                .iter()
+                .find(|association| association.asap == asap)
+                .map(|association| association.tsap)
+        })
+    }
+}
+
+impl Default for GroupObjectAssociationTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
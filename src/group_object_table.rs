@@ -0,0 +1,243 @@
+//! Group object table, the group objects of the application indexed by their ASAP.
+
+use crate::data_link_layer::ConStatus;
+use crate::data_point::DataPoint;
+use crate::group_object::*;
+use crate::phy::TransferError;
+use crate::request::Requests;
+use crate::table::{Table, TooShort};
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::task::Poll;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::waitqueue::MultiWakerRegistration;
+use embassy_time::{Duration, WithTimeout};
+use heapless::Vec;
+
+const MAX_GROUP_OBJECTS: usize = 255;
+/// Tasks waiting for updates at the same time, more wake up all of them early.
+const MAX_WAITERS: usize = 8;
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GroupObjectTableError {
+    TooShort {
+        expected: usize,
+        actual: usize,
+    },
+    /// The type byte of the object with the ASAP isn't valid.
+    InvalidType(usize),
+}
+
+impl From<TooShort> for GroupObjectTableError {
+    fn from(err: TooShort) -> Self {
+        Self::TooShort {
+            expected: err.expected,
+            actual: err.actual,
+        }
+    }
+}
+
+/// The group objects of the device, in the layout ETS downloads: the RAM flags pointer,
+/// then a value pointer, a [`GroupObjectConfig`] and a [`GroupObjectType`] byte per object.
+/// Values are kept in the table instead, the pointers are ignored.
+pub struct GroupObjectTable {
+    objects: Table<GroupObject, MAX_GROUP_OBJECTS>,
+    waiters: Mutex<CriticalSectionRawMutex, RefCell<MultiWakerRegistration<MAX_WAITERS>>>,
+    /// Requests for the application layer.
+    pub(crate) requests: Requests<GroupObjectReq, Result<ConStatus, TransferError>>,
+}
+
+/// What a [`GroupObjectHandle`] asks the application layer to send.
+pub(crate) enum GroupObjectReq {
+    Write(u8, DataPoint),
+    Read(u8),
+}
+
+impl GroupObjectTable {
+    const ENTRY_SIZE: usize = 3;
+
+    pub const fn new() -> Self {
+        Self {
+            objects: Table::new(),
+            waiters: Mutex::new(RefCell::new(MultiWakerRegistration::new())),
+            requests: Requests::new(),
+        }
+    }
+
+    pub fn load(&self, buf: &[u8]) -> Result<(), GroupObjectTableError> {
+        let entries = buf.first().copied().unwrap_or_default() as usize;
+        self.objects
+            .load(buf, 2, entries, Self::ENTRY_SIZE, |asap, entry| {
+                let object_type = GroupObjectType::try_from(entry[2])
+                    .map_err(|_| GroupObjectTableError::InvalidType(asap))?;
+                Ok(GroupObject::new(
+                    GroupObjectConfig::new(entry[1]),
+                    object_type,
+                ))
+            })
+    }
+
+    pub fn clear(&self) {
+        self.objects.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The group object `asap` for the application to work with.
+    pub fn object(&'static self, asap: u8) -> GroupObjectHandle {
+        GroupObjectHandle { table: self, asap }
+    }
+
+    /// A copy of the group object `asap`.
+    pub fn get(&self, asap: u8) -> Option<GroupObject> {
+        self.objects.lock(|table| table.get(asap as usize).cloned())
+    }
+
+    /// Runs `f` on the group object `asap`, if there is one, and wakes up the tasks waiting
+    /// for an update if `f` updated it.
+    pub(crate) fn update<R>(&self, asap: u8, f: impl FnOnce(&mut GroupObject) -> R) -> Option<R> {
+        let (res, updated) = self.objects.lock(|table| {
+            let object = table.get_mut(asap as usize)?;
+            let updates = object.updates();
+            let res = f(object);
+            Some((res, object.updates() != updates))
+        })?;
+        if updated {
+            self.waiters.lock(|waiters| waiters.borrow_mut().wake());
+        }
+        Some(res)
+    }
+
+    /// Sends `req` to the application layer and waits for its confirmation.
+    async fn request(&self, req: GroupObjectReq) -> Result<ConStatus, TransferError> {
+        self.requests.request(req).await
+    }
+
+    /// Waits until object `asap` doesn't wait for the response to a read anymore.
+    async fn wait_for_response(&self, asap: u8) {
+        poll_fn(|cx| {
+            if self.get(asap).map(|object| object.state()) != Some(GroupObjectState::ReadRequest) {
+                return Poll::Ready(());
+            }
+            self.waiters
+                .lock(|waiters| waiters.borrow_mut().register(cx.waker()));
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Waits until the update count of object `asap` isn't `updates` anymore.
+    async fn wait_for_update(&self, asap: u8, updates: Option<u16>) {
+        poll_fn(|cx| {
+            if self.get(asap).map(|object| object.updates()) != updates {
+                return Poll::Ready(());
+            }
+            self.waiters
+                .lock(|waiters| waiters.borrow_mut().register(cx.waker()));
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// ASAPs of the objects whose config matches `filter`.
+    pub(crate) fn filter(
+        &self,
+        filter: impl Fn(GroupObjectConfig) -> bool,
+    ) -> Vec<u8, MAX_GROUP_OBJECTS> {
+        self.objects.lock(|table| {
+            table
+                .iter()
+                .enumerate()
+                .filter(|(_, object)| filter(object.config()))
+                .map(|(asap, _)| asap as u8)
+                .collect()
+        })
+    }
+}
+
+impl Default for GroupObjectTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A group object of a [`GroupObjectTable`], obtained with [`GroupObjectTable::object`].
+#[derive(Clone, Copy)]
+pub struct GroupObjectHandle {
+    table: &'static GroupObjectTable,
+    asap: u8,
+}
+
+impl GroupObjectHandle {
+    pub fn asap(&self) -> u8 {
+        self.asap
+    }
+
+    /// A copy of the group object, `None` if the table has no object with the ASAP.
+    pub fn get(&self) -> Option<GroupObject> {
+        self.table.get(self.asap)
+    }
+
+    /// The current value, `None` if there is no object or no format for its type.
+    pub fn value(&self) -> Option<DataPoint> {
+        self.get().and_then(|object| object.data())
+    }
+
+    /// Sets the value without sending it, reads from the bus are answered with it.
+    pub fn set(&self, data: &DataPoint) -> Result<(), TransferError> {
+        self.table
+            .update(self.asap, |object| object.set_data(data))
+            .ok_or(TransferError::InvalidData(self.asap))?
+            .map_err(TransferError::FrameError)
+    }
+
+    /// Sets the value and writes it to the bus. Needs the transmit flag.
+    pub async fn write(&self, data: DataPoint) -> Result<ConStatus, TransferError> {
+        self.table
+            .request(GroupObjectReq::Write(self.asap, data))
+            .await
+    }
+
+    /// Asks the bus for the value and waits up to `timeout` for the response. Needs the
+    /// transmit flag, the response updates the value even without the update flag. Writes
+    /// to the group address coming in meanwhile aren't taken as the response.
+    ///
+    /// Fails with [`TransferError::NotAcknowledged`] right away if the read request wasn't
+    /// acknowledged, with the error of sending it if it couldn't be sent, and with
+    /// [`TransferError::TimeoutError`] if there is no response.
+    pub async fn read(&self, timeout: Duration) -> Result<Option<DataPoint>, TransferError> {
+        if self.table.request(GroupObjectReq::Read(self.asap)).await? == ConStatus::NotOk {
+            return Err(TransferError::NotAcknowledged);
+        }
+        if self
+            .table
+            .wait_for_response(self.asap)
+            .with_timeout(timeout)
+            .await
+            .is_err()
+        {
+            // Responses coming in later are only taken with the update flag again
+            self.table.update(self.asap, |object| {
+                if object.state() == GroupObjectState::ReadRequest {
+                    object.set_state(GroupObjectState::Ok);
+                }
+            });
+            return Err(TransferError::TimeoutError(0));
+        }
+        Ok(self.value())
+    }
+
+    /// Waits until the value is set from the bus, by a write or a response.
+    pub async fn wait_for_update(&self) {
+        let updates = self.get().map(|object| object.updates());
+        self.table.wait_for_update(self.asap, updates).await
+    }
+}
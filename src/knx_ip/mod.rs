@@ -0,0 +1,212 @@
+//! KNXnet/IP frame layout and the services built on it.
+
+use crate::cemi::CemiError;
+use crate::frame::IndividualAddress;
+use core::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use heapless::Vec;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+pub mod routing;
+pub mod tunnelling;
+
+pub const KNX_IP_PORT: u16 = 3671;
+pub const KNX_IP_MULTICAST_ADDR: Ipv4Addr = Ipv4Addr::new(224, 0, 23, 12);
+
+/// Enough for an extended frame with some additional info.
+pub(crate) const MAX_PACKET_SIZE: usize = 320;
+
+const HEADER_LENGTH: u8 = 0x06;
+const PROTOCOL_VERSION: u8 = 0x10;
+const HPAI_LENGTH: usize = 8;
+const IPV4_UDP: u8 = 0x01;
+
+#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u16)]
+pub enum ServiceType {
+    SearchRequest = 0x0201,
+    SearchResponse = 0x0202,
+    DescriptionRequest = 0x0203,
+    DescriptionResponse = 0x0204,
+    ConnectRequest = 0x0205,
+    ConnectResponse = 0x0206,
+    ConnectionStateRequest = 0x0207,
+    ConnectionStateResponse = 0x0208,
+    DisconnectRequest = 0x0209,
+    DisconnectResponse = 0x020A,
+    TunnellingRequest = 0x0420,
+    TunnellingAck = 0x0421,
+    RoutingIndication = 0x0530,
+    RoutingLostMessage = 0x0531,
+    RoutingBusy = 0x0532,
+}
+
+/// Status codes of responses and acknowledgements.
+pub mod status {
+    pub const E_NO_ERROR: u8 = 0x00;
+    pub const E_CONNECTION_ID: u8 = 0x21;
+    pub const E_CONNECTION_TYPE: u8 = 0x22;
+    pub const E_CONNECTION_OPTION: u8 = 0x23;
+    pub const E_NO_MORE_CONNECTIONS: u8 = 0x24;
+    pub const E_SEQUENCE_NUMBER: u8 = 0x04;
+    pub const E_TUNNELLING_LAYER: u8 = 0x29;
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum KnxIpError {
+    TooShort(usize),
+    UnsupportedVersion(u8),
+    UnknownService(u16),
+    LengthMismatch { expected: usize, actual: usize },
+    UnsupportedHpai,
+    BufferTooSmall(usize),
+    Cemi(CemiError),
+}
+
+impl From<CemiError> for KnxIpError {
+    fn from(value: CemiError) -> Self {
+        Self::Cemi(value)
+    }
+}
+
+/// What a server tells about itself in SEARCH and DESCRIPTION responses.
+pub struct DeviceInfo {
+    pub individual_address: IndividualAddress,
+    pub serial_number: [u8; 6],
+    pub mac_address: [u8; 6],
+    /// Friendly name, cut off after 30 bytes.
+    pub name: &'static str,
+}
+
+impl DeviceInfo {
+    const DIB_LENGTH: u8 = 0x36;
+    const DIB_DEVICE_INFO: u8 = 0x01;
+    const DIB_SUPP_SVC_FAMILIES: u8 = 0x02;
+    const MEDIUM_TP1: u8 = 0x02;
+    const NAME_LENGTH: usize = 30;
+
+    fn write_device_dib(&self, packet: &mut Packet) -> Result<(), KnxIpError> {
+        let mut dib = [0; Self::DIB_LENGTH as usize];
+        dib[0] = Self::DIB_LENGTH;
+        dib[1] = Self::DIB_DEVICE_INFO;
+        dib[2] = Self::MEDIUM_TP1;
+        self.individual_address.write(&mut dib[4..6]);
+        dib[8..14].copy_from_slice(&self.serial_number);
+        dib[14..18].copy_from_slice(&KNX_IP_MULTICAST_ADDR.octets());
+        dib[18..24].copy_from_slice(&self.mac_address);
+        let name = self.name.as_bytes();
+        let name = &name[..name.len().min(Self::NAME_LENGTH)];
+        dib[24..24 + name.len()].copy_from_slice(name);
+        packet.push(&dib)
+    }
+
+    fn write_service_families_dib(
+        &self,
+        packet: &mut Packet,
+        families: &[(u8, u8)],
+    ) -> Result<(), KnxIpError> {
+        packet.push(&[2 + 2 * families.len() as u8, Self::DIB_SUPP_SVC_FAMILIES])?;
+        for (family, version) in families {
+            packet.push(&[*family, *version])?;
+        }
+        Ok(())
+    }
+}
+
+/// Service families as announced in the supported service families DIB.
+pub(crate) mod service_family {
+    pub const CORE: u8 = 0x02;
+    pub const TUNNELLING: u8 = 0x04;
+}
+
+/// Splits a packet into its service type and body.
+pub fn parse_header(buf: &[u8]) -> Result<(ServiceType, &[u8]), KnxIpError> {
+    if buf.len() < HEADER_LENGTH as usize {
+        return Err(KnxIpError::TooShort(buf.len()));
+    }
+    if buf[0] != HEADER_LENGTH || buf[1] != PROTOCOL_VERSION {
+        return Err(KnxIpError::UnsupportedVersion(buf[1]));
+    }
+    let service = u16::from_be_bytes([buf[2], buf[3]]);
+    let service =
+        ServiceType::try_from(service).map_err(|e| KnxIpError::UnknownService(e.number))?;
+    let expected = u16::from_be_bytes([buf[4], buf[5]]) as usize;
+    if buf.len() != expected {
+        return Err(KnxIpError::LengthMismatch {
+            expected,
+            actual: buf.len(),
+        });
+    }
+    Ok((service, &buf[HEADER_LENGTH as usize..]))
+}
+
+/// Reads a host protocol address information block, returns the endpoint and the remaining
+/// bytes. A route back HPAI (0.0.0.0:0, used behind NAT) resolves to `remote`.
+pub(crate) fn parse_hpai(
+    buf: &[u8],
+    remote: SocketAddr,
+) -> Result<(SocketAddr, &[u8]), KnxIpError> {
+    if buf.len() < HPAI_LENGTH {
+        return Err(KnxIpError::TooShort(buf.len()));
+    }
+    if buf[0] as usize != HPAI_LENGTH || buf[1] != IPV4_UDP {
+        return Err(KnxIpError::UnsupportedHpai);
+    }
+    let ip = Ipv4Addr::new(buf[2], buf[3], buf[4], buf[5]);
+    let port = u16::from_be_bytes([buf[6], buf[7]]);
+    let endpoint = if ip.is_unspecified() || port == 0 {
+        remote
+    } else {
+        SocketAddr::V4(SocketAddrV4::new(ip, port))
+    };
+    Ok((endpoint, &buf[HPAI_LENGTH..]))
+}
+
+pub(crate) fn hpai(endpoint: &SocketAddrV4) -> [u8; HPAI_LENGTH] {
+    let ip = endpoint.ip().octets();
+    let port = endpoint.port().to_be_bytes();
+    [
+        HPAI_LENGTH as u8,
+        IPV4_UDP,
+        ip[0],
+        ip[1],
+        ip[2],
+        ip[3],
+        port[0],
+        port[1],
+    ]
+}
+
+/// An outgoing packet, the header length is filled in by [`Packet::finish`].
+pub(crate) struct Packet(Vec<u8, MAX_PACKET_SIZE>);
+
+impl Packet {
+    pub(crate) fn new(service: ServiceType) -> Self {
+        let service: u16 = service.into();
+        let service = service.to_be_bytes();
+        let mut buf = Vec::new();
+        // Always fits
+        let _ = buf.extend_from_slice(&[
+            HEADER_LENGTH,
+            PROTOCOL_VERSION,
+            service[0],
+            service[1],
+            0,
+            0,
+        ]);
+        Self(buf)
+    }
+
+    pub(crate) fn push(&mut self, data: &[u8]) -> Result<(), KnxIpError> {
+        self.0
+            .extend_from_slice(data)
+            .map_err(|_| KnxIpError::BufferTooSmall(self.0.len() + data.len()))
+    }
+
+    pub(crate) fn finish(&mut self) -> &[u8] {
+        let length = (self.0.len() as u16).to_be_bytes();
+        self.0[4..6].copy_from_slice(&length);
+        &self.0
+    }
+}
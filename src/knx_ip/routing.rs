@@ -0,0 +1,191 @@
+//! KNXnet/IP routing, a data link for devices living on an IP backbone.
+
+use super::*;
+use crate::cemi::{CemiMessage, MessageCode};
+use crate::data_link_layer::ConStatus;
+use crate::frame::{Frame, FrameError};
+use crate::phy::{KnxPhy, TransferError};
+use embassy_time::{Duration, Instant, Timer};
+use embedded_io_async::Error;
+use embedded_nal_async::UnconnectedUdp;
+
+/// Routing flow control: at most 50 indications per second and backing off on ROUTING_BUSY.
+struct FlowControl {
+    next_send: Instant,
+    paused_until: Instant,
+    busy_count: u64,
+    last_busy: Instant,
+}
+
+impl FlowControl {
+    const MIN_INTERVAL: Duration = Duration::from_millis(20);
+    const BUSY_SLOT_MS: u64 = 50;
+    const BUSY_DECAY_MS: u64 = 5;
+    const BUSY_IGNORE: Duration = Duration::from_millis(10);
+
+    const fn new() -> Self {
+        Self {
+            next_send: Instant::from_ticks(0),
+            paused_until: Instant::from_ticks(0),
+            busy_count: 0,
+            last_busy: Instant::from_ticks(0),
+        }
+    }
+
+    fn ready_at(&self) -> Instant {
+        self.next_send.max(self.paused_until)
+    }
+
+    fn sent(&mut self) {
+        self.next_send = Instant::now() + Self::MIN_INTERVAL;
+    }
+
+    /// Pauses sending for `wait` plus a random share of 50 ms per recent busy indication, so
+    /// the devices on the backbone don't resume all at once.
+    fn busy(&mut self, wait: Duration, seed: u64) {
+        let now = Instant::now();
+        // The busy counter goes down by one every 5 ms after the last pause
+        if now > self.paused_until {
+            let decay = (now - self.paused_until).as_millis() / Self::BUSY_DECAY_MS;
+            self.busy_count = self.busy_count.saturating_sub(decay);
+        }
+        // Busy indications from several routers for the same overload only count once
+        if self.busy_count == 0 || now - self.last_busy >= Self::BUSY_IGNORE {
+            self.busy_count += 1;
+        }
+        self.last_busy = now;
+        let jitter = (seed ^ now.as_ticks()) % (self.busy_count * Self::BUSY_SLOT_MS);
+        self.paused_until = self
+            .paused_until
+            .max(now + wait + Duration::from_millis(jitter));
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+fn frame_error(err: KnxIpError) -> TransferError {
+    match err {
+        KnxIpError::Cemi(CemiError::FrameError(e)) => TransferError::FrameError(e),
+        KnxIpError::BufferTooSmall(len) | KnxIpError::Cemi(CemiError::BufferTooSmall(len)) => {
+            TransferError::FrameError(FrameError::TooLong(len))
+        }
+        _ => TransferError::FrameError(FrameError::InvalidLength),
+    }
+}
+
+/// Transceiver for a KNXnet/IP routing multicast group, a drop-in replacement for the TP1
+/// driver under a [`PhyRunner`](crate::phy::PhyRunner).
+///
+/// Frames are sent as ROUTING_INDICATION to `group`, usually 224.0.23.12:3671. IP has no
+/// acknowledgements, every frame that made it onto the network is confirmed positively.
+/// Frames with the device's own source address are dropped, which filters the multicast
+/// loopback. ROUTING_BUSY pauses sending, ROUTING_LOST_MESSAGE is only logged; the device
+/// never reports being busy itself.
+pub struct RoutingPhy<S: UnconnectedUdp> {
+    socket: S,
+    local: SocketAddr,
+    group: SocketAddr,
+    address: IndividualAddress,
+    flow: FlowControl,
+    frame: Option<Frame>,
+}
+
+impl<S: UnconnectedUdp> RoutingPhy<S> {
+    pub fn new(socket: S, local: SocketAddr, group: SocketAddrV4) -> Self {
+        Self {
+            socket,
+            local,
+            group: SocketAddr::V4(group),
+            address: crate::settings::ADDRESS,
+            flow: FlowControl::new(),
+            frame: None,
+        }
+    }
+
+    fn handle_packet(&mut self, packet: &[u8]) -> Result<(), KnxIpError> {
+        let (service, body) = parse_header(packet)?;
+        match service {
+            ServiceType::RoutingIndication => {
+                let msg = CemiMessage::parse(body)?;
+                if msg.code() != MessageCode::LDataInd {
+                    info!("Ignoring cEMI message {}", msg.code());
+                } else if msg.frame().src_addr() != self.address {
+                    self.frame = Some(msg.into_frame());
+                }
+            }
+            ServiceType::RoutingBusy => {
+                if body.len() < 6 {
+                    return Err(KnxIpError::TooShort(body.len()));
+                }
+                let wait = u16::from_be_bytes([body[2], body[3]]);
+                warn!("Routing busy, waiting {} ms", wait);
+                let mut seed = [0; 2];
+                self.address.write(&mut seed);
+                self.flow.busy(
+                    Duration::from_millis(wait as u64),
+                    u16::from_be_bytes(seed) as u64,
+                );
+            }
+            ServiceType::RoutingLostMessage => {
+                if body.len() < 4 {
+                    return Err(KnxIpError::TooShort(body.len()));
+                }
+                let lost = u16::from_be_bytes([body[2], body[3]]);
+                warn!("Router lost {} messages", lost);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl<S: UnconnectedUdp> KnxPhy for RoutingPhy<S> {
+    async fn send(&mut self, frame: Frame) -> Result<ConStatus, TransferError> {
+        Timer::at(self.flow.ready_at()).await;
+        let mut cemi = [0; MAX_PACKET_SIZE];
+        let len = CemiMessage::new(MessageCode::LDataInd, frame)
+            .write(&mut cemi)
+            .map_err(|e| frame_error(e.into()))?;
+        let mut packet = Packet::new(ServiceType::RoutingIndication);
+        packet.push(&cemi[..len]).map_err(frame_error)?;
+        self.socket
+            .send(self.local, self.group, packet.finish())
+            .await
+            .map_err(|e| TransferError::NetworkError(e.kind()))?;
+        self.flow.sent();
+        Ok(ConStatus::Ok)
+    }
+
+    async fn ready_to_receive(&mut self) -> Result<(), TransferError> {
+        let mut buf = [0; MAX_PACKET_SIZE];
+        while self.frame.is_none() {
+            let (len, _, _) = self
+                .socket
+                .receive_into(&mut buf)
+                .await
+                .map_err(|e| TransferError::NetworkError(e.kind()))?;
+            if let Err(e) = self.handle_packet(&buf[..len]) {
+                info!("Invalid routing packet: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Result<Frame, TransferError> {
+        self.ready_to_receive().await?;
+        Ok(unwrap!(self.frame.take()))
+    }
+
+    async fn reset(&mut self) -> Result<(), TransferError> {
+        self.frame = None;
+        self.flow.reset();
+        Ok(())
+    }
+
+    async fn set_address(&mut self, addr: &IndividualAddress) -> Result<(), TransferError> {
+        self.address = addr.clone();
+        Ok(())
+    }
+}
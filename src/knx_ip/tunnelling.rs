@@ -0,0 +1,385 @@
+//! KNXnet/IP tunnelling server, giving one client access to the line of a [`DataLinkLayer`].
+
+use super::*;
+use crate::cemi::{CemiMessage, MessageCode};
+use crate::data_link_layer::{ConStatus, DataLinkLayer, DataServiceInd};
+use crate::frame::{Address, Frame};
+use core::net::IpAddr;
+use embassy_futures::select::{select3, Either3};
+use embassy_time::{Duration, Instant, Timer};
+use embedded_io_async::Error;
+use embedded_nal_async::UnconnectedUdp;
+
+const CONNECTION_TIMEOUT_S: u64 = 120;
+const CONNECTION_HEADER_LENGTH: u8 = 0x04;
+const TUNNEL_CONNECTION: u8 = 0x04;
+const TUNNEL_LINKLAYER: u8 = 0x02;
+const CRD_LENGTH: u8 = 0x04;
+
+struct Connection {
+    channel_id: u8,
+    local: SocketAddr,
+    data: SocketAddr,
+    seq_recv: u8,
+    seq_send: u8,
+    last_seen: Instant,
+}
+
+/// Static configuration of a [`TunnellingServer`].
+pub struct TunnellingConfig {
+    /// Control and data endpoint announced to clients.
+    pub endpoint: SocketAddrV4,
+    /// Individual address of the tunnel, used as source for client frames without one.
+    pub tunnel_address: IndividualAddress,
+    pub device: DeviceInfo,
+}
+
+/// Serves a single tunnelling connection on the line behind `data_link`.
+///
+/// Client frames are sent through the data link layer and confirmed with an L_Data.con,
+/// everything received on the line, except frames for other individual addresses, is
+/// passed to the client as L_Data.ind.
+#[cfg_attr(
+    feature = "sim",
+    doc = "A stack running on the same host can be reached by attaching both to a",
+    doc = "[`SimBus`](crate::sim_bus::SimBus)."
+)]
+///
+/// Indications are not repeated when the client misses the TUNNELLING_ACK, and the
+/// connection is dropped after 120 s without a CONNECTIONSTATE_REQUEST.
+pub struct TunnellingServer<S: UnconnectedUdp> {
+    socket: S,
+    data_link: DataLinkLayer,
+    config: TunnellingConfig,
+    connection: Option<Connection>,
+    next_channel_id: u8,
+}
+
+impl<S: UnconnectedUdp> TunnellingServer<S> {
+    pub fn new(socket: S, data_link: DataLinkLayer, config: TunnellingConfig) -> Self {
+        Self {
+            socket,
+            data_link,
+            config,
+            connection: None,
+            next_channel_id: 1,
+        }
+    }
+
+    pub async fn run(mut self) -> ! {
+        let mut buf = [0; MAX_PACKET_SIZE];
+        loop {
+            let deadline = self
+                .connection
+                .as_ref()
+                .map(|c| c.last_seen + Duration::from_secs(CONNECTION_TIMEOUT_S));
+            let timeout = async {
+                match deadline {
+                    Some(deadline) => Timer::at(deadline).await,
+                    None => core::future::pending().await,
+                }
+            };
+            match select3(
+                self.socket.receive_into(&mut buf),
+                self.data_link.receive(),
+                timeout,
+            )
+            .await
+            {
+                Either3::First(Ok((len, local, remote))) => {
+                    self.handle_packet(&buf[..len], local, remote).await
+                }
+                Either3::First(Err(e)) => error!("UDP receive failed: {}", e.kind()),
+                Either3::Second(DataServiceInd::Data(frame)) => self.indicate(frame).await,
+                Either3::Second(_) => {}
+                Either3::Third(()) => {
+                    warn!("Tunnelling connection timed out");
+                    self.connection = None;
+                }
+            }
+        }
+    }
+
+    async fn handle_packet(&mut self, packet: &[u8], local: SocketAddr, remote: SocketAddr) {
+        let (service, body) = match parse_header(packet) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("Invalid KNXnet/IP packet: {}", e);
+                return;
+            }
+        };
+        // Replies can't be sent from the multicast address a search request arrives on
+        let local = if is_unicast(local.ip()) {
+            local
+        } else {
+            SocketAddr::V4(self.config.endpoint)
+        };
+        let result = match service {
+            ServiceType::SearchRequest => self.search(body, local, remote).await,
+            ServiceType::DescriptionRequest => self.describe(body, local, remote).await,
+            ServiceType::ConnectRequest => self.connect(body, local, remote).await,
+            ServiceType::ConnectionStateRequest => self.connection_state(body, local, remote).await,
+            ServiceType::DisconnectRequest => self.disconnect(body, local, remote).await,
+            ServiceType::TunnellingRequest => self.tunnelling_request(body).await,
+            ServiceType::TunnellingAck => self.tunnelling_ack(body),
+            _ => {
+                info!("Ignoring {}", service);
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            warn!("{} failed: {}", service, e);
+        }
+    }
+
+    async fn send(&mut self, local: SocketAddr, remote: SocketAddr, packet: &mut Packet) {
+        if let Err(e) = self.socket.send(local, remote, packet.finish()).await {
+            error!("UDP send failed: {}", e.kind());
+        }
+    }
+
+    fn write_description(&self, packet: &mut Packet) -> Result<(), KnxIpError> {
+        self.config.device.write_device_dib(packet)?;
+        self.config.device.write_service_families_dib(
+            packet,
+            &[(service_family::CORE, 1), (service_family::TUNNELLING, 1)],
+        )
+    }
+
+    async fn search(
+        &mut self,
+        body: &[u8],
+        local: SocketAddr,
+        remote: SocketAddr,
+    ) -> Result<(), KnxIpError> {
+        let (discovery, _) = parse_hpai(body, remote)?;
+        let mut packet = Packet::new(ServiceType::SearchResponse);
+        packet.push(&hpai(&self.config.endpoint))?;
+        self.write_description(&mut packet)?;
+        self.send(local, discovery, &mut packet).await;
+        Ok(())
+    }
+
+    async fn describe(
+        &mut self,
+        body: &[u8],
+        local: SocketAddr,
+        remote: SocketAddr,
+    ) -> Result<(), KnxIpError> {
+        let (control, _) = parse_hpai(body, remote)?;
+        let mut packet = Packet::new(ServiceType::DescriptionResponse);
+        self.write_description(&mut packet)?;
+        self.send(local, control, &mut packet).await;
+        Ok(())
+    }
+
+    async fn connect(
+        &mut self,
+        body: &[u8],
+        local: SocketAddr,
+        remote: SocketAddr,
+    ) -> Result<(), KnxIpError> {
+        let (control, rest) = parse_hpai(body, remote)?;
+        let (data, cri) = parse_hpai(rest, remote)?;
+        if cri.len() < 3 {
+            return Err(KnxIpError::TooShort(body.len()));
+        }
+        let status = if cri[1] != TUNNEL_CONNECTION {
+            status::E_CONNECTION_TYPE
+        } else if cri[2] != TUNNEL_LINKLAYER {
+            status::E_TUNNELLING_LAYER
+        } else if self.connection.is_some() {
+            status::E_NO_MORE_CONNECTIONS
+        } else {
+            status::E_NO_ERROR
+        };
+
+        let mut packet = Packet::new(ServiceType::ConnectResponse);
+        if status != status::E_NO_ERROR {
+            warn!("Rejecting tunnelling connection: {:x}", status);
+            packet.push(&[0, status])?;
+            self.send(local, control, &mut packet).await;
+            return Ok(());
+        }
+
+        let channel_id = self.next_channel_id;
+        self.next_channel_id = self.next_channel_id.wrapping_add(1).max(1);
+        self.connection = Some(Connection {
+            channel_id,
+            local,
+            data,
+            seq_recv: 0,
+            seq_send: 0,
+            last_seen: Instant::now(),
+        });
+        info!("Tunnelling connection {} opened", channel_id);
+
+        let mut crd = [CRD_LENGTH, TUNNEL_CONNECTION, 0, 0];
+        self.config.tunnel_address.write(&mut crd[2..]);
+        packet.push(&[channel_id, status])?;
+        packet.push(&hpai(&self.config.endpoint))?;
+        packet.push(&crd)?;
+        self.send(local, control, &mut packet).await;
+        Ok(())
+    }
+
+    /// Checks the channel of a request, refreshing the connection's heartbeat.
+    fn check_channel(&mut self, channel_id: u8) -> u8 {
+        match self.connection.as_mut() {
+            Some(connection) if connection.channel_id == channel_id => {
+                connection.last_seen = Instant::now();
+                status::E_NO_ERROR
+            }
+            _ => status::E_CONNECTION_ID,
+        }
+    }
+
+    async fn connection_state(
+        &mut self,
+        body: &[u8],
+        local: SocketAddr,
+        remote: SocketAddr,
+    ) -> Result<(), KnxIpError> {
+        let (channel_id, hpai) = body
+            .split_at_checked(2)
+            .ok_or(KnxIpError::TooShort(body.len()))?;
+        let (control, _) = parse_hpai(hpai, remote)?;
+        let status = self.check_channel(channel_id[0]);
+        let mut packet = Packet::new(ServiceType::ConnectionStateResponse);
+        packet.push(&[channel_id[0], status])?;
+        self.send(local, control, &mut packet).await;
+        Ok(())
+    }
+
+    async fn disconnect(
+        &mut self,
+        body: &[u8],
+        local: SocketAddr,
+        remote: SocketAddr,
+    ) -> Result<(), KnxIpError> {
+        let (channel_id, hpai) = body
+            .split_at_checked(2)
+            .ok_or(KnxIpError::TooShort(body.len()))?;
+        let (control, _) = parse_hpai(hpai, remote)?;
+        let status = self.check_channel(channel_id[0]);
+        if status == status::E_NO_ERROR {
+            info!("Tunnelling connection {} closed", channel_id[0]);
+            self.connection = None;
+        }
+        let mut packet = Packet::new(ServiceType::DisconnectResponse);
+        packet.push(&[channel_id[0], status])?;
+        self.send(local, control, &mut packet).await;
+        Ok(())
+    }
+
+    async fn tunnelling_request(&mut self, body: &[u8]) -> Result<(), KnxIpError> {
+        if body.len() < CONNECTION_HEADER_LENGTH as usize {
+            return Err(KnxIpError::TooShort(body.len()));
+        }
+        let (channel_id, seq) = (body[1], body[2]);
+        if self.check_channel(channel_id) != status::E_NO_ERROR {
+            warn!("Tunnelling request for unknown channel {}", channel_id);
+            return Ok(());
+        }
+        let Some(connection) = self.connection.as_mut() else {
+            return Ok(());
+        };
+        let (local, data) = (connection.local, connection.data);
+        let duplicate = seq == connection.seq_recv.wrapping_sub(1);
+        if seq != connection.seq_recv && !duplicate {
+            warn!(
+                "Discarding tunnelling request {}, expected {}",
+                seq, connection.seq_recv
+            );
+            return Ok(());
+        }
+
+        let mut packet = Packet::new(ServiceType::TunnellingAck);
+        packet.push(&[
+            CONNECTION_HEADER_LENGTH,
+            channel_id,
+            seq,
+            status::E_NO_ERROR,
+        ])?;
+        self.send(local, data, &mut packet).await;
+        if duplicate {
+            return Ok(());
+        }
+        if let Some(connection) = self.connection.as_mut() {
+            connection.seq_recv = connection.seq_recv.wrapping_add(1);
+        }
+
+        let msg = CemiMessage::parse(&body[CONNECTION_HEADER_LENGTH as usize..])?;
+        if msg.code() != MessageCode::LDataReq {
+            info!("Ignoring cEMI message {}", msg.code());
+            return Ok(());
+        }
+        let mut frame = msg.into_frame();
+        if frame.src_addr() == IndividualAddress::new(0) {
+            frame.set_src_addr(&self.config.tunnel_address);
+        }
+        let confirmation = match Frame::try_from(&frame) {
+            Ok(copy) => Some(copy),
+            Err(e) => {
+                warn!("No frame left for L_Data.con: {}", e);
+                None
+            }
+        };
+        let con_status = self.data_link.send(frame).await.unwrap_or(ConStatus::NotOk);
+        if let Some(frame) = confirmation {
+            let mut con = CemiMessage::new(MessageCode::LDataCon, frame);
+            con.set_con_status(con_status);
+            self.send_cemi(&con).await?;
+        }
+        Ok(())
+    }
+
+    fn tunnelling_ack(&mut self, body: &[u8]) -> Result<(), KnxIpError> {
+        if body.len() < CONNECTION_HEADER_LENGTH as usize {
+            return Err(KnxIpError::TooShort(body.len()));
+        }
+        if self.check_channel(body[1]) == status::E_NO_ERROR && body[3] != status::E_NO_ERROR {
+            warn!("Tunnelling request {} not accepted: {:x}", body[2], body[3]);
+        }
+        Ok(())
+    }
+
+    async fn indicate(&mut self, frame: Frame) {
+        if self.connection.is_none() {
+            return;
+        }
+        if let Address::Individual(addr) = frame.dst_addr() {
+            if addr != self.config.tunnel_address {
+                return;
+            }
+        }
+        let ind = CemiMessage::new(MessageCode::LDataInd, frame);
+        if let Err(e) = self.send_cemi(&ind).await {
+            warn!("L_Data.ind failed: {}", e);
+        }
+    }
+
+    async fn send_cemi(&mut self, msg: &CemiMessage) -> Result<(), KnxIpError> {
+        let Some(connection) = self.connection.as_mut() else {
+            return Ok(());
+        };
+        let seq = connection.seq_send;
+        connection.seq_send = connection.seq_send.wrapping_add(1);
+        let (channel_id, local, data) = (connection.channel_id, connection.local, connection.data);
+
+        let mut cemi = [0; MAX_PACKET_SIZE];
+        let len = msg.write(&mut cemi)?;
+        let mut packet = Packet::new(ServiceType::TunnellingRequest);
+        packet.push(&[CONNECTION_HEADER_LENGTH, channel_id, seq, 0])?;
+        packet.push(&cemi[..len])?;
+        self.send(local, data, &mut packet).await;
+        Ok(())
+    }
+}
+
+fn is_unicast(addr: IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(ip) => !ip.is_multicast() && !ip.is_broadcast() && !ip.is_unspecified(),
+        IpAddr::V6(ip) => !ip.is_multicast() && !ip.is_unspecified(),
+    }
+}
@@ -0,0 +1,25 @@
+#![no_std]
+
+// This mod MUST go first, so that the others see its macros.
+pub(crate) mod fmt;
+
+pub mod application_layer;
+pub mod cemi;
+pub mod data_link_layer;
+pub mod data_point;
+pub mod dpt;
+pub mod frame;
+pub mod group_address_table;
+pub mod group_object;
+pub mod group_object_association_table;
+pub mod group_object_table;
+pub mod knx_ip;
+pub mod ncn51_driver;
+pub mod network_layer;
+pub mod phy;
+pub(crate) mod request;
+pub mod settings;
+#[cfg(feature = "sim")]
+pub mod sim_bus;
+pub(crate) mod table;
+pub mod transport_layer;
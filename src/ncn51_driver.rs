@@ -1,55 +1,123 @@
+use crate::data_link_layer::ConStatus;
 use crate::frame::*;
-
-use crate::UartResources;
-use core::panic;
-use defmt::*;
+use crate::phy::{KnxPhy, TransferError};
+use crate::request::Requests;
 use embassy_futures::select::{select, Either};
-use embassy_nrf::gpio::{Level, Output, OutputDrive};
-use embassy_nrf::peripherals::SERIAL0;
-use embassy_nrf::{bind_interrupts, uarte};
-use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
-use embassy_sync::channel::Channel;
-use embassy_sync::signal::Signal;
-use embassy_time::{Duration, WithTimeout};
-use heapless::Vec;
-use heapless::{box_pool, pool::boxed::BoxBlock};
-use static_cell::StaticCell;
-
-type Serial = uarte::Uarte<'static, SERIAL0>;
-
-//TODO: Add "bytes_read" information
-#[derive(Format)]
-enum TransferError {
-    TimeoutError(usize),
-    ReadError(uarte::Error),
-    FrameError(FrameError),
-    InvalidData(u8),
+use embassy_time::{Duration, Instant, WithTimeout};
+use embedded_io_async::{Error, ErrorKind, Read, ReadExactError, Write};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+/// Operation mode reported in the system state.
+#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum OperationMode {
+    PowerUp = 0,
+    PowerUpStop = 1,
+    Sync = 2,
+    Stop = 3,
+    Normal = 4,
+}
+
+/// Error flags from a U_State.ind.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct State(u8);
+
+impl State {
+    pub fn slave_collision(&self) -> bool {
+        self.0 & 0x80 != 0
+    }
+
+    /// Checksum, length or parity error on a received frame.
+    pub fn receive_error(&self) -> bool {
+        self.0 & 0x40 != 0
+    }
+
+    /// A sent byte didn't make it onto the bus unchanged.
+    pub fn transmit_error(&self) -> bool {
+        self.0 & 0x20 != 0
+    }
+
+    /// The host violated the UART protocol.
+    pub fn protocol_error(&self) -> bool {
+        self.0 & 0x10 != 0
+    }
+
+    pub fn thermal_warning(&self) -> bool {
+        self.0 & 0x08 != 0
+    }
 }
 
-impl From<uarte::Error> for TransferError {
-    fn from(err: uarte::Error) -> Self {
-        TransferError::ReadError(err)
+/// Payload of a U_SystemStat.ind.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SystemState(u8);
+
+impl SystemState {
+    pub fn mode(&self) -> Option<OperationMode> {
+        OperationMode::try_from(self.0 & 0x07).ok()
+    }
+
+    pub fn raw(&self) -> u8 {
+        self.0
     }
 }
 
-impl From<FrameError> for TransferError {
-    fn from(err: FrameError) -> Self {
-        TransferError::FrameError(err)
+/// Options of U_Configure.req. The driver itself expects the frame end marker and CRC off.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Configuration {
+    pub auto_polling: bool,
+    pub crc_ccitt: bool,
+    pub frame_end_marker: bool,
+}
+
+impl Configuration {
+    fn bits(&self) -> u8 {
+        (self.auto_polling as u8) << 2 | (self.crc_ccitt as u8) << 1 | self.frame_end_marker as u8
     }
 }
 
-/*struct Reset {}
+#[derive(Debug, Clone, Copy, IntoPrimitive)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum Register {
+    Watchdog = 0,
+    AnalogControl0 = 1,
+    AnalogControl1 = 2,
+    AnalogStatus0 = 3,
+}
 
-impl Reset {}
-*/
-#[allow(dead_code)]
-pub enum CtrlMsg {
-    /*Reset,
+enum CtrlMsg {
+    Reset,
+    State,
+    SystemState,
+    SetAddress(IndividualAddress),
+    SetRepetition { busy: u8, nack: u8 },
+    Configure(Configuration),
+    ReadRegister(Register),
+    WriteRegister(Register, u8),
+    StopMode,
+    ExitStopMode,
+}
+
+enum CtrlResponse {
+    Done,
+    State(State),
+    SystemState(SystemState),
+    Register(u8),
+}
+
+/// Indication the driver waits for after a control request.
+#[derive(Clone, Copy, PartialEq)]
+enum Pending {
+    Reset,
     State,
-    Configure,
-    SystemStat,
+    SystemState,
+    SystemStateData,
+    Register,
     StopMode,
-    IntRegRd,*/
 }
 
 #[allow(dead_code)]
@@ -75,144 +143,219 @@ mod commands {
     pub const U_L_DATA_END_REQ: u8 = 0x40;
 }
 
-#[derive(Format)]
-pub enum ConStatus {
-    Ok,
-    NotOk,
+mod indications {
+    pub const U_RESET_IND: u8 = 0x03;
+    pub const U_STATE_IND: u8 = 0x07;
+    pub const U_STATE_MASK: u8 = 0x07;
+    pub const U_STOP_MODE_IND: u8 = 0x2B;
+    pub const U_SYSTEM_STAT_IND: u8 = 0x4B;
 }
 
 #[allow(dead_code)]
+#[allow(clippy::upper_case_acronyms)]
 enum AckTypes {
     NACK = 0x4,
     BUSY = 0x2,
     ACK = 0x1,
 }
 
-bind_interrupts!(struct Irqs {
-    SERIAL0 => uarte::InterruptHandler<SERIAL0>;
-});
-
-const MAX_FRAME_SIZE: usize = 264;
-const CHANNEL_SIZE: usize = 8;
-box_pool!(FRAME_POOL: Vec<u8, MAX_FRAME_SIZE>);
-//box_pool!(FRAME_POOL: dyn FrameWriter);
-pub static CTRL_CHANNEL: Channel<ThreadModeRawMutex, CtrlMsg, CHANNEL_SIZE> = Channel::new();
-pub static CON_SIGNAL: Signal<ThreadModeRawMutex, ConStatus> = Signal::new();
-pub static FRAME_CHANNEL_TX: Channel<ThreadModeRawMutex, Frame, CHANNEL_SIZE> = Channel::new();
-pub static FRAME_CHANNEL_RX: Channel<ThreadModeRawMutex, Frame, CHANNEL_SIZE> = Channel::new();
-
-pub struct NCN51Driver {
-    uarte: Serial,
-    led: Output<'static>,
+/// Control requests for a driver created with [`NCN51Driver::with_control`].
+pub struct CtrlChannels {
+    /// Requests with the deadline their [`NCN51Control`] waits for the answer until.
+    requests: Requests<(CtrlMsg, Instant), Result<CtrlResponse, TransferError>>,
+}
+
+impl CtrlChannels {
+    pub const fn new() -> Self {
+        Self {
+            requests: Requests::new(),
+        }
+    }
+}
+
+impl Default for CtrlChannels {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Controls the NCN5121 while its driver is running under a [`PhyRunner`](crate::phy::PhyRunner).
+///
+/// Requests are served between frames, one not answered within 200 ms fails with
+/// [`TransferError::TimeoutError`].
+pub struct NCN51Control {
+    channels: &'static CtrlChannels,
+}
+
+impl NCN51Control {
+    const TIMEOUT_MS: u64 = 200;
+
+    pub fn new(channels: &'static CtrlChannels) -> Self {
+        Self { channels }
+    }
+
+    async fn request(&self, msg: CtrlMsg) -> Result<CtrlResponse, TransferError> {
+        let deadline = Instant::now() + Duration::from_millis(Self::TIMEOUT_MS);
+        self.channels
+            .requests
+            .request((msg, deadline))
+            .with_deadline(deadline)
+            .await
+            .map_err(|_| TransferError::TimeoutError(0))?
+    }
+
+    async fn command(&self, msg: CtrlMsg) -> Result<(), TransferError> {
+        self.request(msg).await.map(|_| ())
+    }
+
+    /// Resets the chip and waits until it is back up.
+    pub async fn reset(&self) -> Result<(), TransferError> {
+        self.command(CtrlMsg::Reset).await
+    }
+
+    pub async fn state(&self) -> Result<State, TransferError> {
+        match self.request(CtrlMsg::State).await? {
+            CtrlResponse::State(state) => Ok(state),
+            _ => Err(TransferError::InvalidData(0)),
+        }
+    }
+
+    pub async fn system_state(&self) -> Result<SystemState, TransferError> {
+        match self.request(CtrlMsg::SystemState).await? {
+            CtrlResponse::SystemState(state) => Ok(state),
+            _ => Err(TransferError::InvalidData(0)),
+        }
+    }
+
+    /// Sets the address the chip acknowledges frames for on its own.
+    pub async fn set_address(&self, addr: &IndividualAddress) -> Result<(), TransferError> {
+        self.command(CtrlMsg::SetAddress(addr.clone())).await
+    }
+
+    /// Sets how often a frame is repeated after a BUSY or NACK, at most 7 times each.
+    pub async fn set_repetition(&self, busy: u8, nack: u8) -> Result<(), TransferError> {
+        self.command(CtrlMsg::SetRepetition {
+            busy: busy.min(7),
+            nack: nack.min(7),
+        })
+        .await
+    }
+
+    pub async fn configure(&self, config: Configuration) -> Result<(), TransferError> {
+        self.command(CtrlMsg::Configure(config)).await
+    }
+
+    pub async fn read_register(&self, register: Register) -> Result<u8, TransferError> {
+        match self.request(CtrlMsg::ReadRegister(register)).await? {
+            CtrlResponse::Register(value) => Ok(value),
+            _ => Err(TransferError::InvalidData(0)),
+        }
+    }
+
+    pub async fn write_register(&self, register: Register, value: u8) -> Result<(), TransferError> {
+        self.command(CtrlMsg::WriteRegister(register, value)).await
+    }
+
+    /// Puts the chip into stop mode, it ignores the bus until [`Self::exit_stop_mode`].
+    pub async fn enter_stop_mode(&self) -> Result<(), TransferError> {
+        self.command(CtrlMsg::StopMode).await
+    }
+
+    pub async fn exit_stop_mode(&self) -> Result<(), TransferError> {
+        self.command(CtrlMsg::ExitStopMode).await
+    }
+}
+
+/// Length of a frame according to its length field, once enough of it arrived.
+fn expected_length<T: FrameReader>(data: &[u8]) -> Option<usize> {
+    data.get(T::LG_FIELD)
+        .map(|lg| (lg & T::LG_MASK) as usize + T::HEADER_LENGTH + 1)
 }
 
-impl NCN51Driver {
+fn uart_error<E: Error>(err: ReadExactError<E>) -> TransferError {
+    match err {
+        ReadExactError::UnexpectedEof => TransferError::UartError(ErrorKind::Other),
+        ReadExactError::Other(e) => TransferError::UartError(e.kind()),
+    }
+}
+
+/// Driver for the NCN5121 KNX transceiver, connected through any async UART.
+///
+/// The UART has to be configured for 38400 baud, 8 data bits and even parity.
+pub struct NCN51Driver<U> {
+    uart: U,
+    address: IndividualAddress,
+    ctrl: Option<u8>,
+    timestamp: Instant,
+    control: Option<&'static CtrlChannels>,
+    /// Control request taken off the channel, waiting to be written.
+    request: Option<(u8, (CtrlMsg, Instant))>,
+    pending: Option<Pending>,
+    /// Number of the request `pending` answers.
+    pending_id: u8,
+    /// When the answer to `pending` isn't waited for anymore.
+    pending_deadline: Instant,
+    busmon: bool,
+}
+
+impl<U: Read + Write> NCN51Driver<U> {
     const BUS_SILENCE_US: u64 = 2600;
-    const FRAME_TYPE_MASK: u8 = 0xd3;
-    const FRAME_TYPE_STD: u8 = 0x90;
-    const FRAME_TYPE_EXT: u8 = 0x10;
-
-    pub fn new(resources: UartResources) -> Self {
-        let led_rx = Output::new(resources.led, Level::Low, OutputDrive::Standard);
-        let mut config = uarte::Config::default();
-        config.parity = uarte::Parity::INCLUDED;
-        config.baudrate = uarte::Baudrate::BAUD38400;
-        let uart = uarte::Uarte::new(resources.serial, Irqs, resources.rx, resources.tx, config);
-
-        static FRAME_BUFFER: StaticCell<[BoxBlock<Vec<u8, MAX_FRAME_SIZE>>; CHANNEL_SIZE]> =
-            StaticCell::new();
-        let blocks = FRAME_BUFFER.init([const { BoxBlock::new() }; CHANNEL_SIZE]);
-        for block in blocks {
-            FRAME_POOL.manage(block);
+    const RESET_TIMEOUT_MS: u64 = 50;
+
+    pub fn new(uart: U) -> Self {
+        Self {
+            uart,
+            address: crate::settings::ADDRESS,
+            ctrl: None,
+            timestamp: Instant::from_ticks(0),
+            control: None,
+            request: None,
+            pending: None,
+            pending_id: 0,
+            pending_deadline: Instant::from_ticks(0),
+            busmon: false,
         }
+    }
 
+    /// Like [`Self::new`], also serving the requests of an [`NCN51Control`] on `control`.
+    pub fn with_control(uart: U, control: &'static CtrlChannels) -> Self {
         Self {
-            uarte: uart,
-            led: led_rx,
+            control: Some(control),
+            ..Self::new(uart)
         }
     }
 
-    fn is_frame_start(&self, ctrl: u8) -> bool {
-        [Self::FRAME_TYPE_STD, Self::FRAME_TYPE_EXT].contains(&(ctrl & Self::FRAME_TYPE_MASK))
+    async fn write(&mut self, buf: &[u8]) -> Result<(), TransferError> {
+        self.uart
+            .write_all(buf)
+            .await
+            .map_err(|e| TransferError::UartError(e.kind()))
     }
 
-    async fn ack(&mut self, ack: AckTypes) -> Result<(), TransferError> {
-        self.uarte
-            .write(&[commands::U_ACKN_REQ | ack as u8])
-            .await?;
-        Ok(())
+    async fn read(&mut self, buf: &mut [u8]) -> Result<(), TransferError> {
+        self.uart.read_exact(buf).await.map_err(uart_error)
     }
 
-    pub async fn run(mut self) -> ! {
-        loop {
-            let mut buf = [0; 1];
-            match select(
-                self.uarte.read(&mut buf),
-                FRAME_CHANNEL_RX.ready_to_receive(),
-            )
-            .await
-            {
-                Either::First(ret) => {
-                    if let Err(e) = ret {
-                        info!("Reception error: {}", e);
-                    } else if self.is_frame_start(buf[0]) {
-                        match self.receive_frame(buf[0]).await {
-                            Ok(frame) => {
-                                self.led.toggle();
-                                FRAME_CHANNEL_TX.send(frame).await;
-                            }
-                            Err(e) => {
-                                info!("Reception error: {}", e);
-                                self.read_with_timeout_ignore_errors().await;
-                                //TODO: Handle NACK
-
-                                /*if let Err(e) = self.ack(AckTypes::NACK).await {
-                                    info!("Failed to send NACK: {}", e);
-                                }*/
-                            }
-                        }
-                    } else {
-                        info!("Unexpected byte: {:x}", buf[0]);
-                        // unexpected byte
-                    }
-                }
-                Either::Second(_) => {
-                    let frame = FRAME_CHANNEL_RX.receive().await;
-                    match self.send_frame(frame).await {
-                        Ok(con_status) => {
-                            CON_SIGNAL.signal(con_status);
-                        }
-                        Err(e) => {
-                            error!("Transmission error: {}", e);
-                            CON_SIGNAL.signal(ConStatus::NotOk);
-                        }
-                    }
-                }
-            }
-        }
+    async fn ack(&mut self, ack: AckTypes) -> Result<(), TransferError> {
+        self.write(&[commands::U_ACKN_REQ | ack as u8]).await
     }
 
     async fn send_frame(&mut self, mut frame: Frame) -> Result<ConStatus, TransferError> {
         let buf = frame.data();
         let checksum = frame.checksum();
-        let mut cmd = [0; 1];
-        cmd[0] = commands::U_L_DATA_START_REQ;
-        self.uarte.write_from_ram(&mut cmd).await?;
-        self.uarte.write_from_ram(&buf[0..1]).await?;
-        for i in 1..buf.len() - 1 {
-            cmd[0] = commands::U_L_DATA_CONT_REQ + i as u8;
-            self.uarte.write_from_ram(&mut cmd).await?;
-            self.uarte.write_from_ram(&buf[i..i + 1]).await?;
+        self.write(&[commands::U_L_DATA_START_REQ, buf[0]]).await?;
+        for (i, byte) in buf.iter().enumerate().take(buf.len() - 1).skip(1) {
+            self.write(&[commands::U_L_DATA_CONT_REQ + i as u8, *byte])
+                .await?;
         }
-        cmd[0] = commands::U_L_DATA_END_REQ | (buf.len() as u8 - 1);
-        self.uarte.write_from_ram(&mut cmd).await?;
-        cmd[0] = checksum;
-        self.uarte.write_from_ram(&mut cmd).await?;
+        self.write(&[commands::U_L_DATA_END_REQ | (buf.len() as u8 - 1), checksum])
+            .await?;
         let rx_buf = frame.mut_data();
         for i in 0..rx_buf.len() {
             self.read_with_timeout(&mut rx_buf[i..i + 1]).await?;
         }
-        self.uarte.read(&mut cmd).await?;
+        let mut cmd = [0; 1];
+        self.read(&mut cmd).await?;
         if cmd[0] & 0x7f != 0xb {
             error!("Invalid L_Data.con: {:x}", cmd[0]);
             return Err(TransferError::InvalidData(cmd[0]));
@@ -242,39 +385,32 @@ impl NCN51Driver {
                 Err(TransferError::TimeoutError(bytes)) => Ok(bytes),
                 Err(e) => Err(e),
             }?;
-        if bytes_read < T::MIN_FRAME_SIZE
-            || bytes_read > T::MAX_FRAME_SIZE
-            || bytes_read != frame.length() as usize
-        {
-            return Err(TransferError::FrameError(FrameError::InvalidLength));
-        }
-        if frame.data()[bytes_read] != frame.checksum() {
-            return Err(TransferError::FrameError(FrameError::Checksum));
-        }
+        T::validate(&frame.data()[..bytes_read])?;
         frame.set_length(bytes_read)?;
 
         Ok(frame)
     }
 
     async fn receive_frame(&mut self, ctrl: u8) -> Result<Frame, TransferError> {
-        match ctrl & Self::FRAME_TYPE_MASK {
-            Self::FRAME_TYPE_STD => {
+        match FrameType::from_ctrl(ctrl) {
+            Some(FrameType::Standard) => {
                 let frame = self.receive_frame_int(ctrl).await?;
                 Ok(Frame::Standard(frame))
             }
-            Self::FRAME_TYPE_EXT => {
+            Some(FrameType::Extended) => {
                 let frame = self.receive_frame_int(ctrl).await?;
                 Ok(Frame::Extended(frame))
             }
-            _ => panic!(),
+            None => Err(TransferError::FrameError(FrameError::InvalidControlField(
+                ctrl,
+            ))),
         }
     }
 
     async fn ack_if_addressed(&mut self, dst_addr: &Address) -> Result<(), TransferError> {
         if match dst_addr {
-            Address::Individual(ref addr) => addr == &crate::settings::ADDRESS,
+            Address::Individual(ref addr) => addr == &self.address,
             Address::Group(_) => true,
-            _ => false,
         } {
             self.ack(AckTypes::ACK).await
         } else {
@@ -282,15 +418,110 @@ impl NCN51Driver {
         }
     }
 
+    /// Writes a control request, returns the indication answering it if there is one.
+    async fn start_ctrl(&mut self, msg: CtrlMsg) -> Result<Option<Pending>, TransferError> {
+        match msg {
+            CtrlMsg::Reset => {
+                self.write(&[commands::U_RESET_REQ]).await?;
+                Ok(Some(Pending::Reset))
+            }
+            CtrlMsg::State => {
+                self.write(&[commands::U_STATE_REQ]).await?;
+                Ok(Some(Pending::State))
+            }
+            CtrlMsg::SystemState => {
+                self.write(&[commands::U_SYSTEM_STATE_REQ]).await?;
+                Ok(Some(Pending::SystemState))
+            }
+            CtrlMsg::SetAddress(addr) => {
+                self.set_address(&addr).await?;
+                Ok(None)
+            }
+            CtrlMsg::SetRepetition { busy, nack } => {
+                self.write(&[commands::U_SET_REPETITION_REQ, busy << 4 | nack, 0, 0])
+                    .await?;
+                Ok(None)
+            }
+            CtrlMsg::Configure(config) => {
+                self.write(&[commands::U_CONFIGURE_REQ | config.bits()])
+                    .await?;
+                Ok(None)
+            }
+            CtrlMsg::ReadRegister(register) => {
+                self.write(&[commands::U_INT_REG_RD_REQ | u8::from(register)])
+                    .await?;
+                Ok(Some(Pending::Register))
+            }
+            CtrlMsg::WriteRegister(register, value) => {
+                self.write(&[commands::U_INT_REG_WR_REQ | u8::from(register), value])
+                    .await?;
+                Ok(None)
+            }
+            CtrlMsg::StopMode => {
+                self.write(&[commands::U_STOP_MODE_REQ]).await?;
+                Ok(Some(Pending::StopMode))
+            }
+            CtrlMsg::ExitStopMode => {
+                self.write(&[commands::U_EXIT_STOP_MODE_REQ]).await?;
+                Ok(Some(Pending::Reset))
+            }
+        }
+    }
+
+    /// The indication a control request waits for, until its [`NCN51Control`] gave up on it.
+    fn pending(&mut self) -> Option<Pending> {
+        if self.pending.is_some() && Instant::now() >= self.pending_deadline {
+            warn!("Transceiver didn't answer in time");
+            self.pending = None;
+        }
+        self.pending
+    }
+
+    /// Sorts out a byte received between frames, returns the answer to a pending request.
+    fn indication(&mut self, byte: u8) -> Option<CtrlResponse> {
+        let response = match (self.pending(), byte) {
+            (Some(Pending::Register), value) => CtrlResponse::Register(value),
+            (Some(Pending::SystemStateData), value) => {
+                CtrlResponse::SystemState(SystemState(value))
+            }
+            (Some(Pending::SystemState), indications::U_SYSTEM_STAT_IND) => {
+                self.pending = Some(Pending::SystemStateData);
+                return None;
+            }
+            (Some(Pending::State), state)
+                if state & indications::U_STATE_MASK == indications::U_STATE_IND =>
+            {
+                CtrlResponse::State(State(state))
+            }
+            (Some(Pending::Reset), indications::U_RESET_IND)
+            | (Some(Pending::StopMode), indications::U_STOP_MODE_IND) => CtrlResponse::Done,
+            (_, state) if state & indications::U_STATE_MASK == indications::U_STATE_IND => {
+                warn!("Transceiver state: {:x}", state);
+                return None;
+            }
+            (_, indications::U_RESET_IND) => {
+                warn!("Unexpected transceiver reset");
+                return None;
+            }
+            _ => {
+                info!("Unexpected byte: {:x}", byte);
+                return None;
+            }
+        };
+        self.pending = None;
+        Some(response)
+    }
+
     async fn read_with_timeout(&mut self, buf: &mut [u8]) -> Result<usize, TransferError> {
         let mut read = 0;
         for i in 0..buf.len() {
-            self.uarte
-                .read(&mut buf[i..i + 1])
+            self.uart
+                .read_exact(&mut buf[i..i + 1])
                 .with_timeout(Duration::from_micros(Self::BUS_SILENCE_US))
                 .await
-                .map_err(|_| TransferError::TimeoutError(read))??;
-            read = read + 1;
+                .map_err(|_| TransferError::TimeoutError(read))?
+                .map_err(uart_error)?;
+            read += 1;
         }
         Ok(read)
     }
@@ -301,16 +532,167 @@ impl NCN51Driver {
         warn!("Dumping data until timeout ignoreing errors...");
         loop {
             match self
-                .uarte
-                .read(&mut buf)
+                .uart
+                .read_exact(&mut buf)
                 .with_timeout(Duration::from_micros(Self::BUS_SILENCE_US))
                 .await
             {
                 Ok(_) => {
-                    read = read + 1;
+                    read += 1;
                 }
                 Err(_) => return read,
             }
         }
     }
 }
+
+impl<U: Read + Write> KnxPhy for NCN51Driver<U> {
+    async fn send(&mut self, frame: Frame) -> Result<ConStatus, TransferError> {
+        self.send_frame(frame).await
+    }
+
+    /// Also takes control requests, they are written in [`Self::serve_request`].
+    async fn ready_to_receive(&mut self) -> Result<(), TransferError> {
+        while self.ctrl.is_none() && self.request.is_none() {
+            let mut buf = [0; 1];
+            if self.busmon {
+                // Anything goes, acknowledgements and corrupt frames included
+                self.read(&mut buf).await?;
+                self.ctrl = Some(buf[0]);
+                self.timestamp = Instant::now();
+                break;
+            }
+            match self.control {
+                Some(control) => {
+                    match select(self.read(&mut buf), control.requests.receive()).await {
+                        Either::First(res) => res?,
+                        Either::Second(request) => {
+                            self.request = Some(request);
+                            break;
+                        }
+                    }
+                }
+                None => self.read(&mut buf).await?,
+            }
+            // Register values and the system state can look like anything
+            let data = matches!(
+                self.pending(),
+                Some(Pending::Register | Pending::SystemStateData)
+            );
+            if !data && FrameType::from_ctrl(buf[0]).is_some() {
+                self.ctrl = Some(buf[0]);
+                self.timestamp = Instant::now();
+            } else if let Some(response) = self.indication(buf[0]) {
+                if let Some(control) = self.control {
+                    control.requests.confirm(self.pending_id, Ok(response));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn serve_request(&mut self) -> bool {
+        let (Some((id, (msg, deadline))), Some(control)) = (self.request.take(), self.control)
+        else {
+            return false;
+        };
+        match self.start_ctrl(msg).await {
+            Ok(None) => control.requests.confirm(id, Ok(CtrlResponse::Done)),
+            Ok(pending) => {
+                self.pending = pending;
+                self.pending_id = id;
+                self.pending_deadline = deadline;
+            }
+            Err(e) => control.requests.confirm(id, Err(e)),
+        }
+        true
+    }
+
+    async fn receive(&mut self) -> Result<Frame, TransferError> {
+        self.ready_to_receive().await?;
+        while self.serve_request().await {
+            self.ready_to_receive().await?;
+        }
+        let ctrl = unwrap!(self.ctrl.take());
+        let ret = self.receive_frame(ctrl).await;
+        if ret.is_err() {
+            self.read_with_timeout_ignore_errors().await;
+            //TODO: Handle NACK
+
+            /*if let Err(e) = self.ack(AckTypes::NACK).await {
+                info!("Failed to send NACK: {}", e);
+            }*/
+        }
+        ret
+    }
+
+    async fn reset(&mut self) -> Result<(), TransferError> {
+        self.ctrl = None;
+        self.pending = None;
+        self.busmon = false;
+        self.write(&[commands::U_RESET_REQ]).await?;
+        let mut buf = [0; 1];
+        loop {
+            self.read(&mut buf)
+                .with_timeout(Duration::from_millis(Self::RESET_TIMEOUT_MS))
+                .await
+                .map_err(|_| TransferError::TimeoutError(0))??;
+            if buf[0] == indications::U_RESET_IND {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn set_address(&mut self, addr: &IndividualAddress) -> Result<(), TransferError> {
+        let mut cmd = [commands::U_SET_ADDRESS_REQ, 0, 0, 0];
+        addr.write(&mut cmd[1..]);
+        self.write(&cmd).await?;
+        self.address = addr.clone();
+        Ok(())
+    }
+
+    /// The chip only leaves bus monitor mode through a reset, the address is set again after it.
+    async fn set_busmon(&mut self, enabled: bool) -> Result<(), TransferError> {
+        if enabled {
+            self.ctrl = None;
+            self.write(&[commands::U_BUSMON_REQ]).await?;
+            self.busmon = true;
+        } else if self.busmon {
+            self.reset().await?;
+            let address = self.address.clone();
+            self.set_address(&address).await?;
+        }
+        Ok(())
+    }
+
+    /// Frames end after their length field says so or at the first pause, everything else
+    /// is a single byte.
+    async fn receive_telegram(&mut self) -> Result<Telegram, TransferError> {
+        self.ready_to_receive().await?;
+        while self.serve_request().await {
+            self.ready_to_receive().await?;
+        }
+        let ctrl = unwrap!(self.ctrl.take());
+        let mut telegram = Telegram::new(self.timestamp)?;
+        telegram.push(ctrl)?;
+        let frame_type = match FrameType::from_ctrl(ctrl) {
+            Some(frame_type) => frame_type,
+            None => return Ok(telegram),
+        };
+        let mut buf = [0; 1];
+        loop {
+            let expected = match frame_type {
+                FrameType::Standard => expected_length::<StandardFrame>(telegram.data()),
+                FrameType::Extended => expected_length::<ExtendedFrame>(telegram.data()),
+            };
+            if expected.is_some_and(|len| telegram.data().len() >= len) {
+                return Ok(telegram);
+            }
+            match self.read_with_timeout(&mut buf).await {
+                Ok(_) => telegram.push(buf[0])?,
+                Err(TransferError::TimeoutError(_)) => return Ok(telegram),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
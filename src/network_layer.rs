@@ -1,6 +1,6 @@
-use crate::data_link_layer::{DataLinkLayer, DataServiceInd};
+use crate::data_link_layer::{ConStatus, DataLinkLayer, DataServiceInd};
 use crate::frame::*;
-use defmt::*;
+use crate::phy::TransferError;
 
 pub struct NetworkLayer {
     data_link: DataLinkLayer,
@@ -9,9 +9,13 @@ pub struct NetworkLayer {
 pub enum NetworkServiceReq {
     DataGroup(Frame),
     DataIndividual(Frame),
+    DataBroadcast(Frame),
+    /// TP1 has no system broadcasts, they go out the same as broadcasts.
+    DataSystemBroadcast(Frame),
 }
 
-#[derive(Format)]
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum NetworkServiceInd {
     DataIndividual(Frame),
     DataGroup(Frame),
@@ -21,16 +25,27 @@ pub enum NetworkServiceInd {
 
 impl NetworkLayer {
     pub fn new(data_link: DataLinkLayer) -> Self {
-        Self {
-            data_link: data_link,
-        }
+        Self { data_link }
+    }
+
+    /// The individual address of the device, see [`DataLinkLayer::address`].
+    pub fn address(&self) -> IndividualAddress {
+        self.data_link.address()
+    }
+
+    pub fn set_address(&self, address: IndividualAddress) {
+        self.data_link.set_address(address)
     }
 
     pub async fn receive(&self) -> NetworkServiceInd {
         loop {
             match self.data_link.receive().await {
                 DataServiceInd::Data(frame) => match frame.dst_addr() {
-                    Address::Individual(_) => return NetworkServiceInd::DataIndividual(frame),
+                    // Frames for other devices are only acknowledged by them
+                    Address::Individual(addr) if addr == self.address() => {
+                        return NetworkServiceInd::DataIndividual(frame)
+                    }
+                    Address::Individual(_) => {}
                     Address::Group(ref addr) => {
                         if addr == &GroupAddress::new(0) {
                             return NetworkServiceInd::DataBroadcast(frame);
@@ -38,7 +53,6 @@ impl NetworkLayer {
                             return NetworkServiceInd::DataGroup(frame);
                         }
                     }
-                    _ => {}
                 },
                 DataServiceInd::SystemBroadcast(frame) => {
                     return NetworkServiceInd::DataSystemBroadcast(frame)
@@ -48,10 +62,12 @@ impl NetworkLayer {
         }
     }
 
-    pub async fn send(&self, req: NetworkServiceReq) {
+    pub async fn send(&self, req: NetworkServiceReq) -> Result<ConStatus, TransferError> {
         match req {
             NetworkServiceReq::DataGroup(frame) => self.data_link.send(frame).await,
             NetworkServiceReq::DataIndividual(frame) => self.data_link.send(frame).await,
+            NetworkServiceReq::DataBroadcast(frame) => self.data_link.send(frame).await,
+            NetworkServiceReq::DataSystemBroadcast(frame) => self.data_link.send(frame).await,
         }
     }
 }
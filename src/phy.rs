@@ -0,0 +1,163 @@
+use crate::data_link_layer::ConStatus;
+use crate::frame::*;
+use crate::request::Requests;
+use embassy_futures::select::{select4, Either4};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
+
+const CHANNEL_SIZE: usize = 8;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TransferError {
+    TimeoutError(usize),
+    UartError(embedded_io_async::ErrorKind),
+    NetworkError(embedded_io_async::ErrorKind),
+    FrameError(FrameError),
+    InvalidData(u8),
+    /// The transceiver can't do what was asked, e.g. listen as a bus monitor.
+    Unsupported,
+    /// The request went out, but nobody on the bus acknowledged it.
+    NotAcknowledged,
+}
+
+impl From<FrameError> for TransferError {
+    fn from(err: FrameError) -> Self {
+        TransferError::FrameError(err)
+    }
+}
+
+/// A KNX transceiver the data link layer can put frames on the bus with.
+#[allow(async_fn_in_trait)]
+pub trait KnxPhy {
+    /// Sends an L_Data.req and waits for the matching L_Data.con.
+    async fn send(&mut self, frame: Frame) -> Result<ConStatus, TransferError>;
+    /// Waits until a frame starts arriving or a request for [`Self::serve_request`] is
+    /// taken. Has to be cancel safe.
+    async fn ready_to_receive(&mut self) -> Result<(), TransferError>;
+    /// Serves the request [`Self::ready_to_receive`] took, if there is one, e.g. a control
+    /// request of the transceiver's own. Returns whether there was, no frame is received
+    /// then.
+    async fn serve_request(&mut self) -> bool {
+        false
+    }
+    /// Receives the next frame from the bus.
+    async fn receive(&mut self) -> Result<Frame, TransferError>;
+    /// Resets the transceiver into its normal operating state.
+    async fn reset(&mut self) -> Result<(), TransferError>;
+    /// Sets the individual address the transceiver acknowledges frames for.
+    async fn set_address(&mut self, addr: &IndividualAddress) -> Result<(), TransferError>;
+    /// Switches bus monitor mode on or off. A bus monitor listens without acknowledging or
+    /// sending anything.
+    async fn set_busmon(&mut self, _enabled: bool) -> Result<(), TransferError> {
+        Err(TransferError::Unsupported)
+    }
+    /// Receives the next telegram in bus monitor mode, instead of [`Self::receive`].
+    async fn receive_telegram(&mut self) -> Result<Telegram, TransferError> {
+        Err(TransferError::Unsupported)
+    }
+}
+
+/// Frame queues between the [`DataLinkLayer`](crate::data_link_layer::DataLinkLayer)
+/// and the [`PhyRunner`] driving the transceiver.
+pub struct PhyChannels {
+    pub(crate) rx: Channel<CriticalSectionRawMutex, Frame, CHANNEL_SIZE>,
+    /// Frames to send, confirmed with their L_Data.con.
+    pub(crate) tx: Requests<Frame, Result<ConStatus, TransferError>>,
+    pub(crate) busmon: Channel<CriticalSectionRawMutex, Telegram, CHANNEL_SIZE>,
+    pub(crate) busmon_mode: Signal<CriticalSectionRawMutex, bool>,
+    pub(crate) address: Signal<CriticalSectionRawMutex, IndividualAddress>,
+}
+
+impl PhyChannels {
+    pub const fn new() -> Self {
+        Self {
+            rx: Channel::new(),
+            tx: Requests::new(),
+            busmon: Channel::new(),
+            busmon_mode: Signal::new(),
+            address: Signal::new(),
+        }
+    }
+}
+
+impl Default for PhyChannels {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct PhyRunner<P: KnxPhy> {
+    phy: P,
+    channels: &'static PhyChannels,
+}
+
+impl<P: KnxPhy> PhyRunner<P> {
+    pub fn new(phy: P, channels: &'static PhyChannels) -> Self {
+        Self { phy, channels }
+    }
+
+    pub async fn run(mut self) -> ! {
+        if let Err(e) = self.phy.reset().await {
+            error!("Transceiver reset failed: {}", e);
+        }
+        if let Err(e) = self.phy.set_address(&crate::settings::ADDRESS).await {
+            error!("Setting the transceiver address failed: {}", e);
+        }
+        let mut busmon = false;
+        loop {
+            match select4(
+                self.phy.ready_to_receive(),
+                self.channels.tx.ready_to_receive(),
+                self.channels.busmon_mode.wait(),
+                self.channels.address.wait(),
+            )
+            .await
+            {
+                Either4::First(Ok(())) => {
+                    // Requests are served here, where they can't be cancelled halfway
+                    if self.phy.serve_request().await {
+                        continue;
+                    }
+                    if busmon {
+                        match self.phy.receive_telegram().await {
+                            Ok(telegram) => self.channels.busmon.send(telegram).await,
+                            Err(e) => info!("Reception error: {}", e),
+                        }
+                    } else {
+                        match self.phy.receive().await {
+                            Ok(frame) => self.channels.rx.send(frame).await,
+                            Err(e) => info!("Reception error: {}", e),
+                        }
+                    }
+                }
+                Either4::First(Err(e)) => info!("Reception error: {}", e),
+                Either4::Second(_) => {
+                    let (id, frame) = self.channels.tx.receive().await;
+                    if busmon {
+                        warn!("Can't send in bus monitor mode");
+                        self.channels
+                            .tx
+                            .confirm(id, Err(TransferError::Unsupported));
+                        continue;
+                    }
+                    let con = self.phy.send(frame).await;
+                    if let Err(e) = &con {
+                        error!("Transmission error: {}", e);
+                    }
+                    self.channels.tx.confirm(id, con);
+                }
+                Either4::Third(enabled) => match self.phy.set_busmon(enabled).await {
+                    Ok(()) => busmon = enabled,
+                    Err(e) => error!("Switching bus monitor mode failed: {}", e),
+                },
+                Either4::Fourth(address) => {
+                    if let Err(e) = self.phy.set_address(&address).await {
+                        error!("Setting the transceiver address failed: {}", e);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::mutex::Mutex;
+use embassy_sync::signal::Signal;
+
+/// Requests to a task that confirms each of them, one request at a time.
+///
+/// Requests are numbered and confirmations carry the number of their request. A requester
+/// may stop waiting, e.g. after a timeout, and its confirmation comes in late then. The
+/// next request skips it instead of taking it for its own.
+pub(crate) struct Requests<Req, Con> {
+    req: Channel<CriticalSectionRawMutex, (u8, Req), 1>,
+    con: Signal<CriticalSectionRawMutex, (u8, Con)>,
+    /// Number of the last request, locked until it's confirmed.
+    last: Mutex<CriticalSectionRawMutex, u8>,
+}
+
+impl<Req, Con> Requests<Req, Con> {
+    pub const fn new() -> Self {
+        Self {
+            req: Channel::new(),
+            con: Signal::new(),
+            last: Mutex::new(0),
+        }
+    }
+
+    /// Sends `req` and waits for its confirmation.
+    pub async fn request(&self, req: Req) -> Con {
+        let mut id = self.last.lock().await;
+        *id = id.wrapping_add(1);
+        self.req.send((*id, req)).await;
+        loop {
+            let (con_id, con) = self.con.wait().await;
+            if con_id == *id {
+                return con;
+            }
+        }
+    }
+
+    /// Waits until there is a request, without taking it.
+    pub async fn ready_to_receive(&self) {
+        self.req.ready_to_receive().await
+    }
+
+    /// Takes the next request, with the number to [`Self::confirm`] it with.
+    pub async fn receive(&self) -> (u8, Req) {
+        self.req.receive().await
+    }
+
+    pub fn confirm(&self, id: u8, con: Con) {
+        self.con.signal((id, con));
+    }
+}
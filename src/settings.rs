@@ -1,3 +1,55 @@
 use crate::frame::IndividualAddress;
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
 
+/// Individual address the device starts with, until it's programmed.
 pub const ADDRESS: IndividualAddress = IndividualAddress::from_parts(1, 1, 120);
+
+/// Programming mode of the device, usually switched with its programming button and shown
+/// with its programming LED. Only in programming mode the device reports and changes its
+/// individual address on A_IndividualAddress_Read and A_IndividualAddress_Write.
+pub struct ProgrammingMode {
+    enabled: Mutex<CriticalSectionRawMutex, Cell<bool>>,
+    changed: Signal<CriticalSectionRawMutex, bool>,
+}
+
+impl ProgrammingMode {
+    pub const fn new() -> Self {
+        Self {
+            enabled: Mutex::new(Cell::new(false)),
+            changed: Signal::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.lock(|enabled| enabled.get())
+    }
+
+    pub fn set(&self, enabled: bool) {
+        self.enabled.lock(|cell| cell.set(enabled));
+        self.changed.signal(enabled);
+    }
+
+    /// Switches programming mode on or off, e.g. when the button is pressed.
+    pub fn toggle(&self) {
+        let enabled = self.enabled.lock(|enabled| {
+            enabled.set(!enabled.get());
+            enabled.get()
+        });
+        self.changed.signal(enabled);
+    }
+
+    /// Waits until programming mode is switched and returns whether it's on now, e.g. to
+    /// drive the LED.
+    pub async fn wait(&self) -> bool {
+        self.changed.wait().await
+    }
+}
+
+impl Default for ProgrammingMode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,243 @@
+//! In-memory TP1 line for running several stacks against each other without a transceiver.
+
+use crate::data_link_layer::ConStatus;
+use crate::frame::*;
+use crate::phy::{KnxPhy, TransferError};
+use core::cell::RefCell;
+use core::cmp::Ordering;
+use embassy_futures::yield_now;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::channel::Channel;
+use heapless::Vec;
+
+const PORT_QUEUE_SIZE: usize = 8;
+const MONITOR_QUEUE_SIZE: usize = 16;
+
+struct PortState {
+    attached: bool,
+    echo: bool,
+    address: Option<IndividualAddress>,
+}
+
+struct SimPort {
+    rx: Channel<CriticalSectionRawMutex, Frame, PORT_QUEUE_SIZE>,
+    state: Mutex<CriticalSectionRawMutex, RefCell<PortState>>,
+}
+
+impl SimPort {
+    const fn new() -> Self {
+        Self {
+            rx: Channel::new(),
+            state: Mutex::new(RefCell::new(PortState {
+                attached: false,
+                echo: false,
+                address: None,
+            })),
+        }
+    }
+
+    fn acks(&self, dst_addr: &Address) -> bool {
+        self.state.lock(|state| {
+            let state = state.borrow();
+            state.attached
+                && match dst_addr {
+                    Address::Individual(addr) => state.address.as_ref() == Some(addr),
+                    Address::Group(_) => true,
+                }
+        })
+    }
+}
+
+struct BusState<const N: usize> {
+    busy: bool,
+    pending: Vec<(usize, Frame), N>,
+}
+
+/// A simulated TP1 line with `N` transceiver ports.
+///
+/// Frames queued at the same time are arbitrated like on the wire: system and urgent frames
+/// go first, then the lower frame content wins bit by bit. Every frame is delivered to all
+/// other attached ports. Like on a real line the sender sees its own frames as well, they're
+/// echoed to the ports that ask for it with [`SimPhy::set_echo`]; the stack doesn't expect
+/// them, so ports don't by default. Ports acknowledge group frames and individual frames for
+/// their own address; unacknowledged frames are repeated with the repeat flag cleared before
+/// the sender gets a negative L_Data.con.
+pub struct SimBus<const N: usize> {
+    ports: [SimPort; N],
+    state: Mutex<CriticalSectionRawMutex, RefCell<BusState<N>>>,
+    monitor: Channel<CriticalSectionRawMutex, Frame, MONITOR_QUEUE_SIZE>,
+}
+
+impl<const N: usize> SimBus<N> {
+    const MAX_REPETITIONS: usize = 3;
+
+    pub const fn new() -> Self {
+        Self {
+            ports: [const { SimPort::new() }; N],
+            state: Mutex::new(RefCell::new(BusState {
+                busy: false,
+                pending: Vec::new(),
+            })),
+            monitor: Channel::new(),
+        }
+    }
+
+    /// Attaches a transceiver to port `index`.
+    pub fn port(&self, index: usize) -> SimPhy<'_, N> {
+        self.ports[index].state.lock(|state| {
+            state.borrow_mut().attached = true;
+        });
+        SimPhy { bus: self, index }
+    }
+
+    /// Returns the next telegram seen on the line, including repetitions.
+    ///
+    /// Telegrams are dropped when nobody keeps up with reading them.
+    pub async fn monitor(&self) -> Frame {
+        self.monitor.receive().await
+    }
+
+    fn arbitrate(a: &Frame, b: &Frame) -> Ordering {
+        // System and urgent frames only wait 50 bit times for the line, the others 53.
+        let class = |frame: &Frame| match frame.priority() {
+            Priority::System | Priority::Urgent => 0,
+            Priority::Normal | Priority::Low => 1,
+        };
+        // A 0 bit is dominant, so the lower frame content wins.
+        class(a).cmp(&class(b)).then(a.data().cmp(b.data()))
+    }
+
+    fn queue(&self, index: usize, frame: Frame) {
+        self.state.lock(|state| {
+            let mut state = state.borrow_mut();
+            state.pending.retain(|(i, _)| *i != index);
+            if state.pending.push((index, frame)).is_err() {
+                panic!("Port {} out of range", index);
+            }
+        });
+    }
+
+    fn try_acquire(&self, index: usize) -> Option<Frame> {
+        self.state.lock(|state| {
+            let mut state = state.borrow_mut();
+            if state.busy {
+                return None;
+            }
+            let winner = state
+                .pending
+                .iter()
+                .enumerate()
+                .min_by(|(_, (_, a)), (_, (_, b))| Self::arbitrate(a, b))
+                .map(|(pos, (i, _))| (pos, *i))?;
+            if winner.1 != index {
+                return None;
+            }
+            state.busy = true;
+            Some(state.pending.swap_remove(winner.0).1)
+        })
+    }
+
+    fn release(&self) {
+        self.state.lock(|state| {
+            state.borrow_mut().busy = false;
+        });
+    }
+
+    fn deliver(&self, frame: &Frame, to: impl Iterator<Item = usize>) {
+        for i in to {
+            match Frame::try_from(frame) {
+                Ok(copy) => {
+                    if self.ports[i].rx.try_send(copy).is_err() {
+                        warn!("Port {} overrun, frame dropped", i);
+                    }
+                }
+                Err(e) => warn!("Frame copy for port {} failed: {}", i, e),
+            }
+        }
+    }
+
+    fn transmit(&self, index: usize, frame: &Frame) -> bool {
+        let attached =
+            |i: &usize| *i != index && self.ports[*i].state.lock(|s| s.borrow().attached);
+        let echo = self.ports[index].state.lock(|s| s.borrow().echo);
+        self.deliver(
+            frame,
+            (0..N).filter(|i| attached(i) || (*i == index && echo)),
+        );
+        if let Ok(copy) = Frame::try_from(frame) {
+            let _ = self.monitor.try_send(copy);
+        }
+        let dst_addr = frame.dst_addr();
+        (0..N)
+            .filter(attached)
+            .any(|i| self.ports[i].acks(&dst_addr))
+    }
+}
+
+impl<const N: usize> Default for SimBus<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Transceiver attached to one port of a [`SimBus`].
+pub struct SimPhy<'a, const N: usize> {
+    bus: &'a SimBus<N>,
+    index: usize,
+}
+
+impl<const N: usize> SimPhy<'_, N> {
+    /// Receives the frames sent from this port as well, repetitions included.
+    pub fn set_echo(&mut self, enabled: bool) {
+        self.bus.ports[self.index].state.lock(|state| {
+            state.borrow_mut().echo = enabled;
+        });
+    }
+}
+
+impl<const N: usize> KnxPhy for SimPhy<'_, N> {
+    async fn send(&mut self, frame: Frame) -> Result<ConStatus, TransferError> {
+        self.bus.queue(self.index, frame);
+        let mut frame = loop {
+            // Give everybody else sending at the same time the chance to join arbitration.
+            yield_now().await;
+            if let Some(frame) = self.bus.try_acquire(self.index) {
+                break frame;
+            }
+        };
+        let mut con_status = ConStatus::NotOk;
+        for repetition in 0..=SimBus::<N>::MAX_REPETITIONS {
+            if repetition > 0 {
+                frame.set_repeated(Repeated::Repeated);
+            }
+            if self.bus.transmit(self.index, &frame) {
+                con_status = ConStatus::Ok;
+                break;
+            }
+        }
+        self.bus.release();
+        Ok(con_status)
+    }
+
+    async fn ready_to_receive(&mut self) -> Result<(), TransferError> {
+        self.bus.ports[self.index].rx.ready_to_receive().await;
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Result<Frame, TransferError> {
+        Ok(self.bus.ports[self.index].rx.receive().await)
+    }
+
+    async fn reset(&mut self) -> Result<(), TransferError> {
+        self.bus.ports[self.index].rx.clear();
+        Ok(())
+    }
+
+    async fn set_address(&mut self, addr: &IndividualAddress) -> Result<(), TransferError> {
+        self.bus.ports[self.index].state.lock(|state| {
+            state.borrow_mut().address = Some(addr.clone());
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+use core::cell::RefCell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use heapless::Vec;
+
+/// The buffer a table is loaded from ends before its last entry.
+pub(crate) struct TooShort {
+    pub expected: usize,
+    pub actual: usize,
+}
+
+/// Entries of a table the device loads from memory, behind a length byte.
+pub(crate) struct Table<T, const N: usize> {
+    entries: Mutex<CriticalSectionRawMutex, RefCell<Vec<T, N>>>,
+}
+
+impl<T, const N: usize> Table<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            entries: Mutex::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    /// Replaces the table with `entries` entries of `size` bytes each, starting at `offset`
+    /// in `buf`. `buf` may have trailing bytes, the table is left untouched if it ends
+    /// early or `parse` fails on an entry.
+    pub fn load<E: From<TooShort>>(
+        &self,
+        buf: &[u8],
+        offset: usize,
+        entries: usize,
+        size: usize,
+        mut parse: impl FnMut(usize, &[u8]) -> Result<T, E>,
+    ) -> Result<(), E> {
+        let expected = offset + size * entries;
+        if entries > 0 && buf.len() < expected {
+            return Err(TooShort {
+                expected,
+                actual: buf.len(),
+            }
+            .into());
+        }
+        let mut table = Vec::new();
+        for (i, entry) in buf
+            .get(offset..expected)
+            .unwrap_or_default()
+            .chunks_exact(size)
+            .enumerate()
+        {
+            // Can't fail, the length byte doesn't allow more entries
+            let _ = table.push(parse(i, entry)?);
+        }
+        self.entries.lock(|entries| entries.replace(table));
+        Ok(())
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock(|entries| entries.borrow_mut().clear());
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock(|entries| entries.borrow().len())
+    }
+
+    /// Runs `f` on the entries.
+    pub fn lock<R>(&self, f: impl FnOnce(&mut Vec<T, N>) -> R) -> R {
+        self.entries.lock(|entries| f(&mut entries.borrow_mut()))
+    }
+}
@@ -1,34 +1,50 @@
+use crate::data_link_layer::ConStatus;
 use crate::frame::*;
+use crate::group_address_table::GroupAddressTable;
 use crate::network_layer::{NetworkLayer, NetworkServiceInd, NetworkServiceReq};
-use core::cell::RefCell;
-use defmt::*;
-use embassy_futures::select::{select, Either};
-use embassy_time::{Duration, Ticker};
+use crate::phy::TransferError;
+use core::future::Future;
+use embassy_futures::select::{select3, Either3};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{Duration, Instant, Timer};
 use futures::future;
+use heapless::Vec;
+
+/// Broadcasts go to group address 0.
+const BROADCAST: Address = Address::Group(GroupAddress::new(0));
 
 pub struct TransportLayer {
     network: NetworkLayer,
-    connection: RefCell<Connection>,
+    group_addresses: &'static GroupAddressTable,
+    /// Locked while a frame of the connection is sent, requests and events wait for each
+    /// other instead of changing it halfway.
+    connection: Mutex<NoopRawMutex, Connection>,
 }
 
-/*pub enum TSAP {
-
-}*/
-
-#[derive(Format)]
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum TransportServiceInd {
     DataBroadcast(Frame),
     DataSystemBroadcast(Frame),
-    DataGroup(Frame),
+    /// Group telegram for an address in the group address table, with its TSAP.
+    DataGroup(u8, Frame),
     DataTagGroup(Frame),
     DataIndividual(Frame),
     DataConnected(Frame),
-    Connect(Frame),
-    Disconnect(Frame),
-    ACK(Frame),
-    NAK(Frame),
+    /// T_Connect.ind, the device opened a connection to us.
+    Connect(IndividualAddress),
+    /// T_Disconnect.ind, the connection to the device is closed.
+    Disconnect(IndividualAddress),
+    /// T_Data_Connected.con, whether the peer acknowledged the last
+    /// [`TransportServiceReq::DataConnected`].
+    DataConnectedCon(ConStatus),
 }
 
+/// The indications of one transport layer event, in the order they happened. Most events
+/// have one or none, an acknowledgement has two when the request waiting for it fails.
+pub type TransportIndications = Vec<TransportServiceInd, 2>;
+
 pub struct DataGroupReq {
     tsap: u8,
     frame: Frame,
@@ -36,89 +52,152 @@ pub struct DataGroupReq {
 
 impl DataGroupReq {
     pub fn new(tsap: u8, frame: Frame) -> Self {
-        Self {
-            tsap: tsap,
-            frame: frame,
-        }
+        Self { tsap, frame }
     }
-    fn info_frame(mut self, dst_address: GroupAddress) -> Frame {
-        self.frame.set_dst_addr(&Address::Group(dst_address));
-        self.frame.set_src_addr(&crate::settings::ADDRESS);
-        self.frame.set_tpci(TpciBits::Six, 0x0);
-        self.frame
+    fn info_frame(self, src_addr: &IndividualAddress, dst_address: GroupAddress) -> Frame {
+        data_frame(self.frame, src_addr, &Address::Group(dst_address))
     }
 }
 
-/*impl Info<network_layer::DataGroupReq> for DataGroupReq {
-
-}*/
+/// Addresses a frame of one of the connectionless T_Data services from `src_addr` to
+/// `dst_addr`.
+fn data_frame(mut frame: Frame, src_addr: &IndividualAddress, dst_addr: &Address) -> Frame {
+    frame.set_dst_addr(dst_addr);
+    frame.set_src_addr(src_addr);
+    frame.set_tpci(TpciBits::Six, 0x0);
+    frame
+}
 
 pub enum TransportServiceReq {
     DataGroupReq(DataGroupReq),
+    /// T_Data_Individual.req, sends the APDU of the frame to the device without a connection.
+    DataIndividual(IndividualAddress, Frame),
+    /// T_Data_Broadcast.req, sends the APDU of the frame to all devices.
+    DataBroadcast(Frame),
+    /// T_Data_SystemBroadcast.req, sends the APDU of the frame to all devices of the system.
+    /// TP1 has no system broadcasts, the frame goes out as a broadcast and is received as
+    /// [`TransportServiceInd::DataBroadcast`].
+    DataSystemBroadcast(Frame),
+    /// T_Connect.req, opens a connection to the device. Confirmed with [`ConStatus::Ok`] once
+    /// the connection is open.
+    Connect(IndividualAddress),
+    /// T_Disconnect.req, closes the connection.
+    Disconnect,
+    /// T_Data_Connected.req, sends the APDU of the frame on the open connection. Confirmed
+    /// with [`ConStatus::Ok`] once the frame is sent or queued, the peer's acknowledgement is
+    /// indicated with [`TransportServiceInd::DataConnectedCon`].
+    DataConnected(Frame),
 }
 
 impl TransportLayer {
-    pub fn new(network: NetworkLayer) -> Self {
+    pub fn new(network: NetworkLayer, group_addresses: &'static GroupAddressTable) -> Self {
         Self {
             network,
-            connection: RefCell::new(Connection::new()),
+            group_addresses,
+            connection: Mutex::new(Connection::new()),
         }
     }
 
-    pub async fn send(&self, req: TransportServiceReq) {
+    /// The individual address of the device, see [`NetworkLayer::address`].
+    pub fn address(&self) -> IndividualAddress {
+        self.network.address()
+    }
+
+    pub fn set_address(&self, address: IndividualAddress) {
+        self.network.set_address(address)
+    }
+
+    pub async fn send(&self, req: TransportServiceReq) -> Result<ConStatus, TransferError> {
         match req {
             TransportServiceReq::DataGroupReq(req) => {
-                // Hardcode this for now
-                let dst_address = GroupAddress::from_parts(1, 1, 98);
+                let Some(dst_address) = self.group_addresses.group_address(req.tsap) else {
+                    warn!("No group address for TSAP {}", req.tsap);
+                    return Ok(ConStatus::NotOk);
+                };
                 self.network
-                    .send(NetworkServiceReq::DataGroup(req.info_frame(dst_address)))
-                    .await;
+                    .send(NetworkServiceReq::DataGroup(
+                        req.info_frame(&self.network.address(), dst_address),
+                    ))
+                    .await
+            }
+            TransportServiceReq::DataIndividual(address, frame) => {
+                let frame = data_frame(
+                    frame,
+                    &self.network.address(),
+                    &Address::Individual(address),
+                );
+                self.network
+                    .send(NetworkServiceReq::DataIndividual(frame))
+                    .await
+            }
+            TransportServiceReq::DataBroadcast(frame) => {
+                let frame = data_frame(frame, &self.network.address(), &BROADCAST);
+                self.network
+                    .send(NetworkServiceReq::DataBroadcast(frame))
+                    .await
+            }
+            TransportServiceReq::DataSystemBroadcast(frame) => {
+                let frame = data_frame(frame, &self.network.address(), &BROADCAST);
+                self.network
+                    .send(NetworkServiceReq::DataSystemBroadcast(frame))
+                    .await
+            }
+            TransportServiceReq::Connect(address) => {
+                self.connection
+                    .lock()
+                    .await
+                    .connect_req(address, &self.network)
+                    .await
+            }
+            TransportServiceReq::Disconnect => {
+                self.connection
+                    .lock()
+                    .await
+                    .disconnect_req(&self.network)
+                    .await
+            }
+            TransportServiceReq::DataConnected(frame) => {
+                self.connection
+                    .lock()
+                    .await
+                    .data_connected_req(frame, &self.network)
+                    .await
             }
         }
     }
     pub async fn receive_ind(
         &self,
         ind: NetworkServiceInd,
-    ) -> Result<Option<TransportServiceInd>, FrameError> {
-        match ind {
+    ) -> Result<TransportIndications, FrameError> {
+        let ind = match ind {
             NetworkServiceInd::DataIndividual(frame) => {
                 let tpci = frame.tpci(TpciBits::Eight);
-                if tpci >> 2 == 0 {
-                    Ok(Some(TransportServiceInd::DataIndividual(frame)))
+                let pdu = if tpci >> 2 == 0 {
+                    return Ok(single(Some(TransportServiceInd::DataIndividual(frame))));
                 } else if tpci >> 6 == 1 {
                     info!("T_DataConnected");
-                    self.connection
-                        .borrow_mut()
-                        .handle_ind(TransportServiceInd::DataConnected(frame), &self.network)
-                        .await
+                    Pdu::DataConnected
                 } else if tpci == 0x80 {
                     info!("T_Connect");
-                    self.connection
-                        .borrow_mut()
-                        .handle_ind(TransportServiceInd::Connect(frame), &self.network)
-                        .await
+                    Pdu::Connect
                 } else if tpci == 0x81 {
                     info!("T_Disconnect");
-                    self.connection
-                        .borrow_mut()
-                        .handle_ind(TransportServiceInd::Disconnect(frame), &self.network)
-                        .await
-                } else if tpci & 0x3c == 0xc2 {
+                    Pdu::Disconnect
+                } else if tpci & 0xc3 == 0xc2 {
                     info!("T_ACK");
-                    self.connection
-                        .borrow_mut()
-                        .handle_ind(TransportServiceInd::ACK(frame), &self.network)
-                        .await
-                } else if tpci & 0x3c == 0xc4 {
+                    Pdu::Ack
+                } else if tpci & 0xc3 == 0xc3 {
                     info!("T_NACK");
-                    self.connection
-                        .borrow_mut()
-                        .handle_ind(TransportServiceInd::NAK(frame), &self.network)
-                        .await
-                    //let _seq = (tpci >> 2) & 0xF;
+                    Pdu::Nak
                 } else {
-                    Err(FrameError::InvalidTpdu(tpci))
-                }
+                    return Err(FrameError::InvalidTpdu(tpci));
+                };
+                return self
+                    .connection
+                    .lock()
+                    .await
+                    .handle_pdu(pdu, frame, &self.network)
+                    .await;
             }
             NetworkServiceInd::DataBroadcast(frame) => {
                 let tpci = frame.tpci(TpciBits::Six);
@@ -132,7 +211,16 @@ impl TransportLayer {
                 let tpci = frame.tpci(TpciBits::Six);
                 info!("DataGroup: {:x}", tpci);
                 match tpci {
-                    0 => Ok(Some(TransportServiceInd::DataGroup(frame))),
+                    0 => {
+                        let Address::Group(address) = frame.dst_addr() else {
+                            unreachable!()
+                        };
+                        // Telegrams for addresses the device doesn't use are dropped
+                        Ok(self
+                            .group_addresses
+                            .tsap(&address)
+                            .map(|tsap| TransportServiceInd::DataGroup(tsap, frame)))
+                    }
                     1 => Ok(Some(TransportServiceInd::DataTagGroup(frame))),
                     _ => Err(FrameError::InvalidTpdu(tpci)),
                 }
@@ -140,70 +228,141 @@ impl TransportLayer {
             NetworkServiceInd::DataSystemBroadcast(frame) => {
                 Ok(Some(TransportServiceInd::DataSystemBroadcast(frame)))
             }
-        }
+        };
+        ind.map(single)
+    }
+
+    /// Waits for the next frame from the network layer or the next timeout of the
+    /// connection. Cancel safe, the event is handled with [`Self::handle`].
+    pub async fn next_event(&self) -> TransportEvent {
+        let (connection_timeout, ack_timeout) = {
+            let connection = self.connection.lock().await;
+            (connection.connection_timeout, connection.ack_timeout)
+        };
+        let event = select3(
+            self.network.receive(),
+            expire(connection_timeout),
+            expire(ack_timeout),
+        )
+        .await;
+        TransportEvent(match event {
+            Either3::First(ind) => Event::Network(ind),
+            Either3::Second(_) => Event::ConnectionTimeout,
+            Either3::Third(_) => Event::AckTimeout,
+        })
     }
 
-    pub async fn receive(&self) -> Result<TransportServiceInd, FrameError> {
+    /// Runs the connection state machine on `event`, returns the indications for the user.
+    /// Sends frames, so it shouldn't be cancelled.
+    pub async fn handle(&self, event: TransportEvent) -> Result<TransportIndications, FrameError> {
+        let ind = match event.0 {
+            Event::Network(ind) => return self.receive_ind(ind).await,
+            Event::ConnectionTimeout => {
+                let mut connection = self.connection.lock().await;
+                connection.connection_timeout = None;
+                connection
+                    .handle_timeout(Events::ConnectionTimeout_E16, &self.network)
+                    .await
+            }
+            Event::AckTimeout => {
+                let mut connection = self.connection.lock().await;
+                connection.ack_timeout = None;
+                let event = if connection.rep_count < Connection::MAX_REP_COUNT {
+                    Events::AckTimeout_E17
+                } else {
+                    Events::AckTimeout_E18
+                };
+                connection.handle_timeout(event, &self.network).await
+            }
+        };
+        ind.map(single)
+    }
+
+    /// Waits for and handles events until there are indications for the user. Not cancel
+    /// safe, use [`Self::next_event`] and [`Self::handle`] to wait on something else as well.
+    pub async fn receive(&self) -> Result<TransportIndications, FrameError> {
         loop {
-            let ret = match select(
-                self.network.receive(),
-                self.connection
-                    .borrow_mut()
-                    .connection_timeout
-                    .as_mut()
-                    .map_or(future::Either::Left(core::future::pending()), |f| {
-                        future::Either::Right(f.next())
-                    }),
-            )
-            .await
-            {
-                Either::First(ind) => self.receive_ind(ind).await,
-                Either::Second(_) => self.connection.borrow_mut().connection_timeout().await,
-            }?;
-            if let Some(ind) = ret {
-                return Ok(ind);
+            let event = self.next_event().await;
+            let inds = self.handle(event).await?;
+            if !inds.is_empty() {
+                return Ok(inds);
             }
         }
     }
 }
 
-#[allow(dead_code)]
+/// The indications of an event that has at most one.
+fn single(ind: Option<TransportServiceInd>) -> TransportIndications {
+    ind.into_iter().collect()
+}
+
+/// A frame or timeout for the transport layer, from [`TransportLayer::next_event`].
+pub struct TransportEvent(Event);
+
+enum Event {
+    Network(NetworkServiceInd),
+    ConnectionTimeout,
+    AckTimeout,
+}
+
+/// Waits until `deadline` has passed, forever if there is none.
+fn expire(deadline: Option<Instant>) -> impl Future<Output = ()> {
+    match deadline {
+        Some(deadline) => future::Either::Right(Timer::at(deadline)),
+        None => future::Either::Left(core::future::pending()),
+    }
+}
+
+/// Connection control PDUs, the inputs of the connection state machine.
+enum Pdu {
+    Connect,
+    Disconnect,
+    DataConnected,
+    Ack,
+    Nak,
+}
+
+/// Events from the bus and the timers of the connection state machine, as numbered by the
+/// KNX transport layer spec. "Same address" is the address of the connection partner.
+///
+/// The requests of the user are events as well, they're handled by their own functions. The
+/// confirmations of the PDUs other than T_Connect don't change the state, they're only logged.
 #[allow(non_camel_case_types)]
 enum Events {
     ConnectReqSameAddress_E00,
     ConnectReqNewAddress_E01,
     DisconnectReqSameAddress_E02,
     DisconnectReqNewAddress_E03,
+    /// With the expected sequence number.
     DataConnected_E04,
+    /// With the previous sequence number, a repetition.
     DataConnected_E05,
+    /// With any other sequence number.
     DataConnected_E06,
     DataConnectedNewAddress_E07,
+    /// For the frame waiting to be acknowledged.
     Ack_E08,
     Ack_E09,
     AckNewAddress_E10,
     Nak_E11,
+    /// For the frame waiting to be acknowledged, which may be repeated.
     Nak_E12,
+    /// For the frame waiting to be acknowledged, which was repeated too often.
     Nak_E13,
     NakNewAddress_E14,
-    DataConnected_E15,
-    E16,
-    E17,
-    E18,
-    E19,
-    E20,
-    E21,
-    E22,
-    E23,
-    E24,
-    E25,
-    E26,
-    E27,
+    ConnectionTimeout_E16,
+    /// The frame waiting to be acknowledged may be repeated.
+    AckTimeout_E17,
+    /// The frame waiting to be acknowledged was repeated too often.
+    AckTimeout_E18,
 }
 
 enum States {
     Closed,
     OpenIdle,
+    /// Waiting for the acknowledgement of a T_Data_Connected PDU.
     OpenWait,
+    /// Waiting for the confirmation of our T_Connect PDU.
     Connecting,
 }
 
@@ -213,13 +372,23 @@ struct Connection {
     seq_no_recv: u8,
     rep_count: u8,
     src_addr: Option<IndividualAddress>,
-    connection_timeout: Option<Ticker>,
+    /// Closes the connection when nothing happened on it for a while.
+    connection_timeout: Option<Instant>,
+    /// Repeats the T_Data_Connected PDU waiting to be acknowledged.
+    ack_timeout: Option<Instant>,
+    /// The T_Data_Connected PDU waiting to be acknowledged, for repetitions.
     stored_frame: Option<Frame>,
+    /// A T_Data_Connected.req that came in while waiting for an acknowledgement.
+    pending_frame: Option<Frame>,
 }
 
+#[allow(non_snake_case)]
 impl Connection {
     const MAX_REP_COUNT: u8 = 3;
     const CONNECTION_TIMEOUT_SEC: u64 = 6;
+    const ACK_TIMEOUT_SEC: u64 = 3;
+    const SEQ_MASK: u8 = 0xF;
+
     fn new() -> Self {
         Connection {
             state: States::Closed,
@@ -228,83 +397,59 @@ impl Connection {
             rep_count: 0,
             src_addr: None,
             connection_timeout: None,
+            ack_timeout: None,
             stored_frame: None,
+            pending_frame: None,
         }
     }
 
-    async fn handle_ind(
+    async fn handle_pdu(
         &mut self,
-        ind: TransportServiceInd,
+        pdu: Pdu,
+        frame: Frame,
         network: &NetworkLayer,
-    ) -> Result<Option<TransportServiceInd>, FrameError> {
-        let (event, frame) = match ind {
-            TransportServiceInd::Connect(frame) => match &self.src_addr {
-                Some(addr) if addr == &frame.src_addr() => {
-                    (Events::ConnectReqSameAddress_E00, frame)
-                }
-                _ => (Events::ConnectReqNewAddress_E01, frame),
-            },
-            TransportServiceInd::Disconnect(frame) => match &self.src_addr {
-                Some(addr) if addr == &frame.src_addr() => {
-                    (Events::DisconnectReqSameAddress_E02, frame)
-                }
-                _ => (Events::DisconnectReqNewAddress_E03, frame),
-            },
-            TransportServiceInd::DataConnected(frame) => match &self.src_addr {
-                Some(addr) if addr == &frame.src_addr() => match frame.tpci_seq() {
-                    val if val == self.seq_no_recv => (Events::DataConnected_E04, frame),
-                    val if val == self.seq_no_recv - 1 => (Events::DataConnected_E05, frame),
-                    _ => (Events::DataConnected_E06, frame),
-                },
-                _ => (Events::DataConnectedNewAddress_E07, frame),
-            },
-            TransportServiceInd::ACK(frame) => match &self.src_addr {
-                Some(addr) if addr == &frame.src_addr() => {
-                    if frame.tpci_seq() == self.seq_no_send {
-                        (Events::Ack_E08, frame)
-                    } else {
-                        (Events::Ack_E09, frame)
-                    }
-                }
-                _ => (Events::AckNewAddress_E10, frame),
-            },
-            TransportServiceInd::NAK(frame) => match &self.src_addr {
-                Some(addr) if addr == &frame.src_addr() => {
-                    if frame.tpci_seq() == self.seq_no_send {
-                        if self.rep_count < Self::MAX_REP_COUNT {
-                            (Events::Nak_E12, frame)
-                        } else {
-                            (Events::Nak_E13, frame)
-                        }
-                    } else {
-                        (Events::Nak_E11, frame)
-                    }
-                }
-                _ => (Events::NakNewAddress_E14, frame),
-            },
-            _ => {
-                error!("Invalid IND");
-                self::panic!("");
+    ) -> Result<TransportIndications, FrameError> {
+        let same_address = self.src_addr.as_ref() == Some(&frame.src_addr());
+        let seq = frame.tpci_seq();
+        let event = match pdu {
+            Pdu::Connect if same_address => Events::ConnectReqSameAddress_E00,
+            Pdu::Connect => Events::ConnectReqNewAddress_E01,
+            Pdu::Disconnect if same_address => Events::DisconnectReqSameAddress_E02,
+            Pdu::Disconnect => Events::DisconnectReqNewAddress_E03,
+            Pdu::DataConnected if !same_address => Events::DataConnectedNewAddress_E07,
+            Pdu::DataConnected if seq == self.seq_no_recv => Events::DataConnected_E04,
+            Pdu::DataConnected if seq == self.seq_no_recv.wrapping_sub(1) & Self::SEQ_MASK => {
+                Events::DataConnected_E05
             }
+            Pdu::DataConnected => Events::DataConnected_E06,
+            Pdu::Ack if !same_address => Events::AckNewAddress_E10,
+            Pdu::Ack if seq == self.seq_no_send => Events::Ack_E08,
+            Pdu::Ack => Events::Ack_E09,
+            Pdu::Nak if !same_address => Events::NakNewAddress_E14,
+            Pdu::Nak if seq != self.seq_no_send => Events::Nak_E11,
+            Pdu::Nak if self.rep_count < Self::MAX_REP_COUNT => Events::Nak_E12,
+            Pdu::Nak => Events::Nak_E13,
         };
         self.handle_event(event, frame, network).await
     }
 
+    /// The state table for PDUs received from the bus.
     async fn handle_event(
         &mut self,
         event: Events,
         frame: Frame,
         network: &NetworkLayer,
-    ) -> Result<Option<TransportServiceInd>, FrameError> {
-        match (event, &self.state) {
+    ) -> Result<TransportIndications, FrameError> {
+        let mut inds = TransportIndications::new();
+        let ind = match (event, &self.state) {
             (Events::ConnectReqSameAddress_E00, States::Closed) => {
                 self.state = States::OpenIdle;
                 self.new_connection_A1(frame)
             }
-
+            (Events::ConnectReqSameAddress_E00, States::Connecting) => Ok(None),
             (Events::ConnectReqSameAddress_E00, _) => {
                 self.state = States::Closed;
-                self.disconnect_A6(frame, network).await
+                self.disconnect_A6(network).await
             }
             (Events::ConnectReqNewAddress_E01, States::Closed) => {
                 self.state = States::OpenIdle;
@@ -314,49 +459,201 @@ impl Connection {
             (Events::DisconnectReqSameAddress_E02, States::Closed) => Ok(None),
             (Events::DisconnectReqSameAddress_E02, _) => {
                 self.state = States::Closed;
-                self.notify_disconect_A5(frame)
+                self.notify_disconnect_A5()
             }
             (Events::DisconnectReqNewAddress_E03, _) => Ok(None),
-            (Events::DataConnected_E04, States::Closed) => self.reject_A10(frame, network).await,
-            (Events::DataConnected_E04, _) => self.ack_data_A2(frame, network).await,
-            (Events::DataConnected_E05, States::Closed) => self.reject_A10(frame, network).await,
-            (Events::DataConnected_E05, _) => self.ack_A3(frame, network).await,
+
+            // Nothing but a T_Connect from whoever is talking to a closed connection
+            (_, States::Closed) => self.reject_A10(frame, network).await,
+            (Events::DataConnectedNewAddress_E07, _)
+            | (Events::AckNewAddress_E10, _)
+            | (Events::NakNewAddress_E14, _) => self.reject_A10(frame, network).await,
+
+            (Events::DataConnected_E04, States::OpenIdle | States::OpenWait) => {
+                self.ack_data_A2(frame, network).await
+            }
+            (Events::DataConnected_E05, States::OpenIdle | States::OpenWait) => {
+                self.ack_A3(frame, network).await
+            }
+            (Events::DataConnected_E06, States::OpenIdle | States::OpenWait) => {
+                self.nak_A4(frame, network).await
+            }
+            (Events::Ack_E08, States::OpenWait) => {
+                self.state = States::OpenIdle;
+                inds.extend(self.confirm_data_A8()?);
+                // A T_Data_Connected.req that had to wait goes out now, it was already
+                // confirmed as queued so a failure needs a confirmation of its own
+                if let Some(frame) = self.pending_frame.take() {
+                    if let Err(e) = self.data_connected_req(frame, network).await {
+                        warn!("Sending the waiting T_Data_Connected failed: {}", e);
+                        unwrap!(inds.push(TransportServiceInd::DataConnectedCon(ConStatus::NotOk)));
+                    }
+                }
+                Ok(None)
+            }
+            (Events::Nak_E12, States::OpenWait) => {
+                self.repeat_data_A9(network).await?;
+                Ok(None)
+            }
+            // A protocol error of the partner, the connection can't be trusted anymore
+            (_, _) => {
+                self.state = States::Closed;
+                self.disconnect_A6(network).await
+            }
+        }?;
+        inds.extend(ind);
+        Ok(inds)
+    }
+
+    /// The state table for the timers.
+    async fn handle_timeout(
+        &mut self,
+        event: Events,
+        network: &NetworkLayer,
+    ) -> Result<Option<TransportServiceInd>, FrameError> {
+        match (event, &self.state) {
+            (Events::ConnectionTimeout_E16, States::Closed) => Ok(None),
+            (Events::ConnectionTimeout_E16, _) => {
+                self.state = States::Closed;
+                self.disconnect_A6(network).await
+            }
+            (Events::AckTimeout_E17, States::OpenWait) => {
+                self.repeat_data_A9(network).await?;
+                Ok(None)
+            }
+            (Events::AckTimeout_E18, States::OpenWait) => {
+                self.state = States::Closed;
+                self.disconnect_A6(network).await
+            }
+            // A timer that isn't needed in this state anymore
             (_, _) => Ok(None),
         }
     }
 
-    async fn send_response_frame(
-        &self,
+    /// T_Connect.req from the user (E25), followed by the confirmation of the T_Connect PDU
+    /// (E19 and E20).
+    async fn connect_req(
+        &mut self,
+        address: IndividualAddress,
+        network: &NetworkLayer,
+    ) -> Result<ConStatus, TransferError> {
+        let States::Closed = self.state else {
+            warn!("T_Connect.req on an open connection");
+            return Ok(ConStatus::NotOk);
+        };
+        self.state = States::Connecting;
+        match self.connect_A12(address, network).await {
+            Ok(ConStatus::Ok) => {
+                self.state = States::OpenIdle;
+                Ok(self.connect_con_A13())
+            }
+            con => {
+                self.state = States::Closed;
+                self.close();
+                con.map(|_| ConStatus::NotOk)
+            }
+        }
+    }
+
+    /// T_Disconnect.req from the user (E26).
+    async fn disconnect_req(&mut self, network: &NetworkLayer) -> Result<ConStatus, TransferError> {
+        match self.state {
+            States::Closed => Ok(self.disconnect_con_A15()),
+            _ => {
+                self.state = States::Closed;
+                self.disconnect_A14(network).await
+            }
+        }
+    }
+
+    /// T_Data_Connected.req from the user (E15).
+    async fn data_connected_req(
+        &mut self,
+        frame: Frame,
+        network: &NetworkLayer,
+    ) -> Result<ConStatus, TransferError> {
+        match self.state {
+            States::Closed => Ok(ConStatus::NotOk),
+            // Only waits for the acknowledgement once there is a frame to repeat
+            States::OpenIdle => {
+                self.send_data_A7(frame, network).await?;
+                self.state = States::OpenWait;
+                Ok(ConStatus::Ok)
+            }
+            States::OpenWait | States::Connecting => Ok(self.store_req_A11(frame)),
+        }
+    }
+
+    fn control_frame(
+        network: &NetworkLayer,
         dst_addr: IndividualAddress,
         seq: u8,
-        network: &NetworkLayer,
         tpci: u8,
-    ) -> Result<(), FrameError> {
+    ) -> Result<Frame, FrameError> {
         let mut frame = StandardFrame::new(StandardFrame::MIN_FRAME_SIZE)?;
         frame.set_priority(Priority::System);
         frame.set_dst_addr(&Address::Individual(dst_addr));
-        frame.set_src_addr(&crate::settings::ADDRESS);
+        frame.set_src_addr(&network.address());
         frame.set_hop_count(7);
         frame.set_tpci(TpciBits::Eight, tpci);
         frame.set_tpci_seq(seq);
-        network
-            .send(NetworkServiceReq::DataIndividual(Frame::Standard(frame)))
-            .await;
+        Ok(Frame::Standard(frame))
+    }
+
+    async fn send_response_frame(
+        &self,
+        dst_addr: IndividualAddress,
+        seq: u8,
+        network: &NetworkLayer,
+        tpci: u8,
+    ) -> Result<(), FrameError> {
+        let frame = Self::control_frame(network, dst_addr, seq, tpci)?;
+        Self::check_con(network.send(NetworkServiceReq::DataIndividual(frame)).await);
         Ok(())
     }
 
+    /// Nobody waits for the confirmation of connection control frames, a lost one shows up
+    /// as a timeout of the peer instead.
+    fn check_con(con: Result<ConStatus, TransferError>) {
+        match con {
+            Ok(ConStatus::Ok) => {}
+            Ok(ConStatus::NotOk) => warn!("Transport layer frame not acknowledged"),
+            Err(e) => warn!("Sending transport layer frame failed: {}", e),
+        }
+    }
+
+    fn restart_connection_timeout(&mut self) {
+        self.connection_timeout =
+            Some(Instant::now() + Duration::from_secs(Self::CONNECTION_TIMEOUT_SEC));
+    }
+
+    fn restart_ack_timeout(&mut self) {
+        self.ack_timeout = Some(Instant::now() + Duration::from_secs(Self::ACK_TIMEOUT_SEC));
+    }
+
+    /// Stops the timers and resets the connection for the next one, returns the address of
+    /// the partner it had.
+    fn close(&mut self) -> Option<IndividualAddress> {
+        self.connection_timeout = None;
+        self.ack_timeout = None;
+        self.stored_frame = None;
+        self.pending_frame = None;
+        self.seq_no_send = 0;
+        self.seq_no_recv = 0;
+        self.rep_count = 0;
+        self.src_addr.take()
+    }
+
     fn new_connection_A1(
         &mut self,
         frame: Frame,
     ) -> Result<Option<TransportServiceInd>, FrameError> {
-        // A1
+        let address = frame.src_addr();
         self.seq_no_recv = 0;
         self.seq_no_send = 0;
-        self.src_addr = Some(frame.src_addr());
-        self.connection_timeout = Some(Ticker::every(Duration::from_secs(
-            Self::CONNECTION_TIMEOUT_SEC,
-        )));
-        Ok(Some(TransportServiceInd::Connect(frame)))
+        self.src_addr = Some(address.clone());
+        self.restart_connection_timeout();
+        Ok(Some(TransportServiceInd::Connect(address)))
     }
 
     async fn ack_data_A2(
@@ -364,13 +661,10 @@ impl Connection {
         frame: Frame,
         network: &NetworkLayer,
     ) -> Result<Option<TransportServiceInd>, FrameError> {
-        // A2
         self.send_response_frame(frame.src_addr(), self.seq_no_recv, network, 0xc2)
             .await?;
-        self.connection_timeout = Some(Ticker::every(Duration::from_secs(
-            Self::CONNECTION_TIMEOUT_SEC,
-        )));
-        self.seq_no_recv = self.seq_no_recv + 1;
+        self.restart_connection_timeout();
+        self.seq_no_recv = (self.seq_no_recv + 1) & Self::SEQ_MASK;
         Ok(Some(TransportServiceInd::DataConnected(frame)))
     }
 
@@ -379,12 +673,9 @@ impl Connection {
         frame: Frame,
         network: &NetworkLayer,
     ) -> Result<Option<TransportServiceInd>, FrameError> {
-        // A3
         self.send_response_frame(frame.src_addr(), frame.tpci_seq(), network, 0xc2)
             .await?;
-        self.connection_timeout = Some(Ticker::every(Duration::from_secs(
-            Self::CONNECTION_TIMEOUT_SEC,
-        )));
+        self.restart_connection_timeout();
         Ok(None)
     }
 
@@ -395,29 +686,22 @@ impl Connection {
     ) -> Result<Option<TransportServiceInd>, FrameError> {
         self.send_response_frame(frame.src_addr(), frame.tpci_seq(), network, 0xc3)
             .await?;
-        self.connection_timeout = None;
+        self.restart_connection_timeout();
         Ok(None)
     }
 
-    fn notify_disconect_A5(
-        &mut self,
-        frame: Frame,
-    ) -> Result<Option<TransportServiceInd>, FrameError> {
-        //A5
-        self.connection_timeout = None;
-        Ok(Some(TransportServiceInd::Disconnect(frame)))
+    fn notify_disconnect_A5(&mut self) -> Result<Option<TransportServiceInd>, FrameError> {
+        Ok(self.close().map(TransportServiceInd::Disconnect))
     }
 
     async fn disconnect_A6(
         &mut self,
-        frame: Frame,
         network: &NetworkLayer,
     ) -> Result<Option<TransportServiceInd>, FrameError> {
-        // A6
-        self.send_response_frame(frame.src_addr(), 0, network, 0x81)
-            .await?;
-        self.connection_timeout = None;
-        Ok(Some(TransportServiceInd::Disconnect(frame)))
+        if let Some(address) = self.src_addr.clone() {
+            self.send_response_frame(address, 0, network, 0x81).await?;
+        }
+        self.notify_disconnect_A5()
     }
 
     async fn send_data_A7(
@@ -426,40 +710,36 @@ impl Connection {
         network: &NetworkLayer,
     ) -> Result<(), FrameError> {
         frame.set_dst_addr(&Address::Individual(unwrap!(self.src_addr.clone())));
-        frame.set_src_addr(&crate::settings::ADDRESS);
+        frame.set_src_addr(&network.address());
         frame.set_hop_count(7);
         frame.set_tpci(TpciBits::Six, 0x10);
         frame.set_tpci_seq(self.seq_no_send);
         self.stored_frame = Some(Frame::try_from(&frame)?);
-        network.send(NetworkServiceReq::DataIndividual(frame)).await;
+        Self::check_con(network.send(NetworkServiceReq::DataIndividual(frame)).await);
         self.rep_count = 0;
-        self.connection_timeout = Some(Ticker::every(Duration::from_secs(
-            Self::CONNECTION_TIMEOUT_SEC,
-        )));
-        // Start ACK timer?
+        self.restart_ack_timeout();
+        self.restart_connection_timeout();
         Ok(())
     }
 
-    async fn confirm_data_A8(&mut self, frame: Frame) -> Result<(), FrameError> {
-        // Stop ACK timer
-        self.seq_no_send += 1;
-        // Send CON
-        self.connection_timeout = Some(Ticker::every(Duration::from_secs(
-            Self::CONNECTION_TIMEOUT_SEC,
-        )));
-        Ok(())
+    fn confirm_data_A8(&mut self) -> Result<Option<TransportServiceInd>, FrameError> {
+        self.ack_timeout = None;
+        self.stored_frame = None;
+        self.seq_no_send = (self.seq_no_send + 1) & Self::SEQ_MASK;
+        self.restart_connection_timeout();
+        Ok(Some(TransportServiceInd::DataConnectedCon(ConStatus::Ok)))
     }
 
     async fn repeat_data_A9(&mut self, network: &NetworkLayer) -> Result<(), FrameError> {
         let stored_frame = Frame::try_from(unwrap!(self.stored_frame.as_ref()))?;
-        network
-            .send(NetworkServiceReq::DataIndividual(stored_frame))
-            .await;
+        Self::check_con(
+            network
+                .send(NetworkServiceReq::DataIndividual(stored_frame))
+                .await,
+        );
         self.rep_count += 1;
-        self.connection_timeout = Some(Ticker::every(Duration::from_secs(
-            Self::CONNECTION_TIMEOUT_SEC,
-        )));
-        // Start ACK timer
+        self.restart_ack_timeout();
+        self.restart_connection_timeout();
         Ok(())
     }
 
@@ -468,15 +748,46 @@ impl Connection {
         frame: Frame,
         network: &NetworkLayer,
     ) -> Result<Option<TransportServiceInd>, FrameError> {
-        // A10
         self.send_response_frame(frame.src_addr(), 0, network, 0x81)
             .await?;
         Ok(None)
     }
 
-    //async fn connect_A12(&mut self, )
+    /// Keeps the request until the connection is idle again, only one can wait.
+    fn store_req_A11(&mut self, frame: Frame) -> ConStatus {
+        if self.pending_frame.is_some() {
+            warn!("T_Data_Connected.req dropped, another one is waiting");
+            return ConStatus::NotOk;
+        }
+        self.pending_frame = Some(frame);
+        ConStatus::Ok
+    }
 
-    async fn connection_timeout(&mut self) -> Result<Option<TransportServiceInd>, FrameError> {
-        Ok(None)
+    async fn connect_A12(
+        &mut self,
+        address: IndividualAddress,
+        network: &NetworkLayer,
+    ) -> Result<ConStatus, TransferError> {
+        self.src_addr = Some(address.clone());
+        self.seq_no_send = 0;
+        self.seq_no_recv = 0;
+        self.restart_connection_timeout();
+        let frame = Self::control_frame(network, address, 0, 0x80)?;
+        network.send(NetworkServiceReq::DataIndividual(frame)).await
+    }
+
+    fn connect_con_A13(&mut self) -> ConStatus {
+        ConStatus::Ok
+    }
+
+    async fn disconnect_A14(&mut self, network: &NetworkLayer) -> Result<ConStatus, TransferError> {
+        let address = unwrap!(self.close());
+        let frame = Self::control_frame(network, address, 0, 0x81)?;
+        network.send(NetworkServiceReq::DataIndividual(frame)).await
+    }
+
+    fn disconnect_con_A15(&mut self) -> ConStatus {
+        self.close();
+        ConStatus::Ok
     }
 }
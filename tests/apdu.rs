@@ -0,0 +1,253 @@
+use embassy_knx::application_layer::{Apdu, GroupValue};
+use embassy_knx::frame::*;
+use heapless::Vec;
+
+mod common;
+
+/// The APDU bytes of `apdu` from the TPCI byte on.
+fn encode(apdu: &Apdu) -> std::vec::Vec<u8> {
+    let frame = apdu.to_frame().unwrap();
+    let tpci = frame.tpci(TpciBits::Eight);
+    [&[tpci][..], frame.apdu_data()].concat()
+}
+
+/// Why the APDU bytes, from the TPCI byte on, can't be decoded.
+fn decode_error(apdu: &[u8]) -> Option<FrameError> {
+    Apdu::from_frame(&Frame::from_apdu(apdu).unwrap()).err()
+}
+
+#[test]
+fn services_are_encoded_as_specified() {
+    common::init();
+    for (apdu, encoded) in [
+        (Apdu::GroupValueRead, &[0x00, 0x00][..]),
+        (Apdu::GroupValueWrite(GroupValue::Short(1)), &[0x00, 0x81]),
+        (
+            Apdu::GroupValueResponse(GroupValue::Long(Vec::from_slice(&[0x0C, 0x1A]).unwrap())),
+            &[0x00, 0x40, 0x0C, 0x1A],
+        ),
+        (
+            Apdu::IndividualAddressWrite(IndividualAddress::from_parts(1, 1, 5)),
+            &[0x00, 0xC0, 0x11, 0x05],
+        ),
+        (Apdu::IndividualAddressRead, &[0x01, 0x00]),
+        (
+            Apdu::MemoryRead {
+                number: 3,
+                address: 0x0060,
+            },
+            &[0x02, 0x03, 0x00, 0x60],
+        ),
+        (
+            Apdu::DeviceDescriptorRead { descriptor_type: 0 },
+            &[0x03, 0x00],
+        ),
+        (
+            Apdu::DeviceDescriptorResponse {
+                descriptor_type: 0,
+                descriptor: &[0x07, 0xB0],
+            },
+            &[0x03, 0x40, 0x07, 0xB0],
+        ),
+        (Apdu::Restart, &[0x03, 0x80]),
+        (
+            Apdu::PropertyValueRead {
+                object_index: 0,
+                pid: 0x0B,
+                count: 1,
+                start_index: 1,
+            },
+            &[0x03, 0xD5, 0x00, 0x0B, 0x10, 0x01],
+        ),
+        (
+            Apdu::AuthorizeRequest { key: 0xFFFF_FFFF },
+            &[0x03, 0xD1, 0x00, 0xFF, 0xFF, 0xFF, 0xFF],
+        ),
+        (
+            Apdu::SystemNetworkParameterRead {
+                object_type: 0,
+                pid: 11,
+                operand: &[0x01],
+            },
+            &[0x01, 0xC8, 0x00, 0x00, 0x00, 0xB0, 0x01],
+        ),
+    ] {
+        assert_eq!(encode(&apdu), encoded, "{apdu:?}");
+    }
+}
+
+#[test]
+fn services_survive_a_frame() {
+    common::init();
+    let long_data = [0x5A; 40];
+    for apdu in [
+        Apdu::GroupValueRead,
+        Apdu::GroupValueWrite(GroupValue::Short(0x3F)),
+        Apdu::GroupValueWrite(GroupValue::Long(Vec::from_slice(&[1, 2, 3]).unwrap())),
+        Apdu::IndividualAddressResponse,
+        Apdu::AdcRead {
+            channel: 2,
+            count: 8,
+        },
+        Apdu::AdcResponse {
+            channel: 2,
+            count: 8,
+            sum: 0x1234,
+        },
+        Apdu::SystemNetworkParameterResponse {
+            object_type: 0,
+            pid: 0xFFF,
+            data: &[1, 2],
+        },
+        Apdu::SystemNetworkParameterWrite {
+            object_type: 3,
+            pid: 1,
+            value: &[],
+        },
+        Apdu::MemoryResponse {
+            address: 0x4000,
+            data: &[],
+        },
+        Apdu::MemoryWrite {
+            address: 0x4000,
+            data: &long_data,
+        },
+        Apdu::UserMemoryRead {
+            number: 15,
+            address: 0xF_ABCD,
+        },
+        Apdu::UserMemoryWrite {
+            address: 0x1_0000,
+            data: &[9; 15],
+        },
+        Apdu::UserManufacturerInfoRead,
+        Apdu::UserManufacturerInfoResponse {
+            manufacturer_id: 0x83,
+            data: 0x0102,
+        },
+        Apdu::FunctionPropertyCommand {
+            object_index: 1,
+            pid: 2,
+            data: &[3],
+        },
+        Apdu::FunctionPropertyStateResponse {
+            object_index: 1,
+            pid: 2,
+            data: &[0, 4],
+        },
+        Apdu::MasterReset {
+            erase_code: 2,
+            channel: 0,
+        },
+        Apdu::RestartResponse {
+            error_code: 0,
+            process_time: 5,
+        },
+        Apdu::KeyWrite {
+            level: 1,
+            key: 0x1234_5678,
+        },
+        Apdu::KeyResponse { level: 1 },
+        Apdu::PropertyValueResponse {
+            object_index: 0,
+            pid: 0x0B,
+            count: 1,
+            start_index: 0xFFF,
+            data: &[0x00, 0x83, 0x12, 0x34, 0x56, 0x78],
+        },
+        Apdu::PropertyValueWrite {
+            object_index: 1,
+            pid: 54,
+            count: 15,
+            start_index: 1,
+            data: &long_data,
+        },
+        Apdu::PropertyDescriptionRead {
+            object_index: 0,
+            pid: 0,
+            property_index: 3,
+        },
+        Apdu::PropertyDescriptionResponse {
+            object_index: 0,
+            pid: 0x0B,
+            property_index: 3,
+            write_enable: true,
+            property_type: 0x11,
+            max_elements: 0xFFF,
+            access: 0x32,
+        },
+        Apdu::NetworkParameterRead {
+            object_type: 0,
+            pid: 0x0B,
+            test_info: &[0x01],
+        },
+        Apdu::NetworkParameterWrite {
+            object_type: 0,
+            pid: 0x0B,
+            value: &[0xAA, 0xBB],
+        },
+        Apdu::IndividualAddressSerialNumberRead {
+            serial_number: [0, 0x83, 1, 2, 3, 4],
+        },
+        Apdu::IndividualAddressSerialNumberResponse {
+            serial_number: [0, 0x83, 1, 2, 3, 4],
+            domain_address: 0,
+        },
+        Apdu::IndividualAddressSerialNumberWrite {
+            serial_number: [0, 0x83, 1, 2, 3, 4],
+            address: IndividualAddress::from_parts(1, 2, 3),
+        },
+        Apdu::LinkRead {
+            group_object: 4,
+            start_index: 1,
+        },
+        Apdu::LinkResponse {
+            group_object: 4,
+            sending_address: 1,
+            start_index: 1,
+            addresses: &[0x09, 0x60, 0x09, 0x61],
+        },
+        Apdu::LinkWrite {
+            group_object: 4,
+            flags: 0x01,
+            address: GroupAddress::from_parts(1, 1, 96),
+        },
+    ] {
+        let frame = apdu.to_frame().unwrap();
+        assert_eq!(Apdu::from_frame(&frame), Ok(apdu));
+    }
+}
+
+#[test]
+fn malformed_services_are_rejected() {
+    common::init();
+    assert_eq!(
+        decode_error(&[0x03, 0xFF]),
+        Some(FrameError::InvalidApci(0x3FF))
+    );
+    // A_PropertyValue_Read with a byte missing
+    assert_eq!(
+        decode_error(&[0x03, 0xD5, 0x00, 0x0B, 0x10]),
+        Some(FrameError::TooShort(3))
+    );
+    // A_Memory_Response with fewer bytes than announced
+    assert_eq!(
+        decode_error(&[0x02, 0x42, 0x00, 0x60, 0xAA]),
+        Some(FrameError::LengthMismatch {
+            expected: 2,
+            actual: 1
+        })
+    );
+    assert_eq!(
+        decode_error(&[0x01, 0x00, 0x00]),
+        Some(FrameError::LengthMismatch {
+            expected: 0,
+            actual: 1
+        })
+    );
+    let too_much = Apdu::MemoryWrite {
+        address: 0,
+        data: &[0; 64],
+    };
+    assert_eq!(too_much.to_frame().err(), Some(FrameError::TooLong(64)));
+}
@@ -0,0 +1,120 @@
+use embassy_knx::cemi::*;
+use embassy_knx::data_link_layer::ConStatus;
+use embassy_knx::frame::*;
+
+/// L_Data.ind of GroupValueWrite(1) from 1.1.1 to 1/1/98 with a relative timestamp.
+const GROUP_WRITE_IND: [u8; 15] = [
+    0x29, 0x04, 0x04, 0x02, 0x12, 0x34, 0xBC, 0xE0, 0x11, 0x01, 0x09, 0x62, 0x01, 0x00, 0x81,
+];
+/// The same telegram on TP1.
+const GROUP_WRITE_TP1: [u8; 9] = [0xBC, 0x11, 0x01, 0x09, 0x62, 0xE1, 0x00, 0x81, 0x58];
+
+#[test]
+fn decode_standard_indication() {
+    init_frame_pool();
+    let msg = CemiMessage::parse(&GROUP_WRITE_IND).unwrap();
+
+    assert_eq!(msg.code(), MessageCode::LDataInd);
+    assert!(!msg.ack_request());
+    assert_eq!(
+        msg.additional_info()
+            .get(additional_info_type::TIMESTAMP_RELATIVE),
+        Some(&[0x12, 0x34][..])
+    );
+    assert_eq!(msg.additional_info().iter().count(), 1);
+    assert!(matches!(msg.frame(), Frame::Standard(_)));
+    assert_eq!(msg.frame().data(), &GROUP_WRITE_TP1);
+}
+
+#[test]
+fn encode_round_trip() {
+    init_frame_pool();
+    let msg = CemiMessage::parse(&GROUP_WRITE_IND).unwrap();
+    let mut buf = [0; 32];
+    let len = msg.write(&mut buf).unwrap();
+    assert_eq!(&buf[..len], &GROUP_WRITE_IND);
+
+    assert!(matches!(
+        msg.write(&mut buf[..14]),
+        Err(CemiError::BufferTooSmall(15))
+    ));
+}
+
+#[test]
+fn encode_request_from_frame() {
+    init_frame_pool();
+    let frame = Frame::parse(&GROUP_WRITE_TP1).unwrap();
+    let mut msg = CemiMessage::new(MessageCode::LDataReq, frame);
+    assert!(msg.ack_request());
+
+    let mut buf = [0; 32];
+    let len = msg.write(&mut buf).unwrap();
+    assert_eq!(
+        &buf[..len],
+        &[0x11, 0x00, 0xBE, 0xE0, 0x11, 0x01, 0x09, 0x62, 0x01, 0x00, 0x81]
+    );
+
+    msg.set_con_status(ConStatus::NotOk);
+    let len = msg.write(&mut buf).unwrap();
+    buf[0] = MessageCode::LDataCon.into();
+    let con = CemiMessage::parse(&buf[..len]).unwrap();
+    assert_eq!(con.code(), MessageCode::LDataCon);
+    assert!(matches!(con.con_status(), ConStatus::NotOk));
+    assert_eq!(con.frame().data(), &GROUP_WRITE_TP1);
+}
+
+#[test]
+fn long_payload_becomes_extended_frame() {
+    init_frame_pool();
+    let mut buf = [0; 30];
+    buf[..9].copy_from_slice(&[0x29, 0x00, 0xBC, 0xE0, 0x11, 0x01, 0x09, 0x62, 20]);
+    buf[9] = 0x00;
+    buf[10] = 0x80;
+    buf[11..30].copy_from_slice(b"embassy-knx extends");
+
+    let msg = CemiMessage::parse(&buf).unwrap();
+    let frame = msg.frame();
+    assert!(matches!(frame, Frame::Extended(_)));
+    assert_eq!(frame.length(), 29);
+    assert_eq!(frame.src_addr(), IndividualAddress::from_parts(1, 1, 1));
+    assert!(
+        matches!(frame.dst_addr(), Address::Group(addr) if addr == GroupAddress::from_parts(1, 1, 98))
+    );
+    assert_eq!(frame.hop_count(), 6);
+    assert!(Frame::parse(frame.data()).is_ok());
+
+    let mut out = [0; 32];
+    let len = msg.write(&mut out).unwrap();
+    assert_eq!(
+        &out[..len],
+        &[&[0x29, 0x00, 0x3C][..], &buf[3..]].concat()[..]
+    );
+}
+
+#[test]
+fn decode_rejects_invalid_messages() {
+    init_frame_pool();
+    assert!(matches!(
+        CemiMessage::parse(&[0x29]),
+        Err(CemiError::TooShort(1))
+    ));
+    assert!(matches!(
+        CemiMessage::parse(&[0x2B, 0x00]),
+        Err(CemiError::UnknownMessageCode(0x2B))
+    ));
+
+    let mut buf = GROUP_WRITE_IND;
+    buf[3] = 0x03;
+    assert!(matches!(
+        CemiMessage::parse(&buf),
+        Err(CemiError::InvalidAdditionalInfo)
+    ));
+
+    assert!(matches!(
+        CemiMessage::parse(&GROUP_WRITE_IND[..14]),
+        Err(CemiError::LengthMismatch {
+            expected: 15,
+            actual: 14
+        })
+    ));
+}
@@ -0,0 +1,100 @@
+use embassy_knx::frame::{extend_frame_pool, init_frame_pool, FrameBlock};
+use heapless::pool::boxed::BoxBlock;
+use std::sync::Once;
+
+/// Sets up a frame pool big enough for several stacks running in parallel tests.
+pub fn init() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        init_frame_pool();
+        let blocks: &'static mut [FrameBlock; 128] =
+            Box::leak(Box::new([const { BoxBlock::new() }; 128]));
+        extend_frame_pool(blocks);
+    });
+}
+
+/// Runs the stack up to the transport layer on port 0 of a two port `SimBus` until the test
+/// future completes.
+///
+/// Declares the statics `BUS`, `PHY_CHANNELS` and `TABLE`, and binds the transport layer and
+/// port 1 to the two names given. The block evaluates to the test future.
+#[macro_export]
+macro_rules! run_transport {
+    ($transport:ident, $tool:ident, $test:block) => {{
+        use ::embassy_futures::block_on;
+        use ::embassy_futures::select::{select, Either};
+        use ::embassy_knx::data_link_layer::DataLinkLayer;
+        use ::embassy_knx::group_address_table::GroupAddressTable;
+        use ::embassy_knx::network_layer::NetworkLayer;
+        use ::embassy_knx::phy::{PhyChannels, PhyRunner};
+        use ::embassy_knx::sim_bus::SimBus;
+        use ::embassy_knx::transport_layer::TransportLayer;
+
+        static BUS: SimBus<2> = SimBus::new();
+        static PHY_CHANNELS: PhyChannels = PhyChannels::new();
+        static TABLE: GroupAddressTable = GroupAddressTable::new();
+        $crate::common::init();
+
+        let runner = PhyRunner::new(BUS.port(0), &PHY_CHANNELS);
+        let $transport =
+            TransportLayer::new(NetworkLayer::new(DataLinkLayer::new(&PHY_CHANNELS)), &TABLE);
+        let mut $tool = BUS.port(1);
+        let test = $test;
+        match block_on(select(runner.run(), test)) {
+            Either::Second(()) => {}
+            _ => unreachable!(),
+        }
+    }};
+}
+
+/// Runs a device stack on port 0 of a two port `SimBus` until the test future completes.
+///
+/// Declares the statics of the device for the test: `BUS`, `PHY_CHANNELS`, `IND`, `RES`,
+/// `TABLE`, `ASSOCIATION_TABLE`, `OBJECT_TABLE` and `PROGRAMMING_MODE`. The argument is a
+/// block evaluating to the test future, the statements before the future set up the tables.
+#[macro_export]
+macro_rules! run_device {
+    ($test:block) => {{
+        use ::embassy_futures::block_on;
+        use ::embassy_futures::select::{select3, Either3};
+        use ::embassy_knx::application_layer::{
+            ApplicationLayer, ApplicationServiceInd, ApplicationServiceRes,
+        };
+        use ::embassy_knx::data_link_layer::DataLinkLayer;
+        use ::embassy_knx::group_address_table::GroupAddressTable;
+        use ::embassy_knx::group_object_association_table::GroupObjectAssociationTable;
+        use ::embassy_knx::group_object_table::GroupObjectTable;
+        use ::embassy_knx::network_layer::NetworkLayer;
+        use ::embassy_knx::phy::{PhyChannels, PhyRunner};
+        use ::embassy_knx::settings::ProgrammingMode;
+        use ::embassy_knx::sim_bus::SimBus;
+        use ::embassy_knx::transport_layer::TransportLayer;
+        use ::embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+        use ::embassy_sync::channel::Channel;
+
+        static BUS: SimBus<2> = SimBus::new();
+        static PHY_CHANNELS: PhyChannels = PhyChannels::new();
+        static IND: Channel<CriticalSectionRawMutex, ApplicationServiceInd, 4> = Channel::new();
+        static RES: Channel<CriticalSectionRawMutex, ApplicationServiceRes, 4> = Channel::new();
+        static TABLE: GroupAddressTable = GroupAddressTable::new();
+        static ASSOCIATION_TABLE: GroupObjectAssociationTable = GroupObjectAssociationTable::new();
+        static OBJECT_TABLE: GroupObjectTable = GroupObjectTable::new();
+        static PROGRAMMING_MODE: ProgrammingMode = ProgrammingMode::new();
+        $crate::common::init();
+
+        let test = $test;
+        let runner = PhyRunner::new(BUS.port(0), &PHY_CHANNELS);
+        let application = ApplicationLayer::with_programming_mode(
+            TransportLayer::new(NetworkLayer::new(DataLinkLayer::new(&PHY_CHANNELS)), &TABLE),
+            &ASSOCIATION_TABLE,
+            &OBJECT_TABLE,
+            RES.receiver(),
+            IND.sender(),
+            &PROGRAMMING_MODE,
+        );
+        match block_on(select3(runner.run(), application.run(), test)) {
+            Either3::Third(()) => {}
+            _ => unreachable!(),
+        }
+    }};
+}
@@ -0,0 +1,248 @@
+use embassy_knx::application_layer::GroupValue;
+use embassy_knx::data_point::String;
+use embassy_knx::data_point::*;
+use embassy_knx::frame::{Frame, FrameError};
+
+mod common;
+
+#[test]
+fn short_values_are_read_from_the_apci_byte() {
+    // A_GroupValue_Write with the value in the low bits of the APCI byte
+    assert_eq!(
+        DataPoint::read(DataPointType::B1, &[0x81]),
+        Ok(DataPoint::B1(B1::new(true)))
+    );
+    assert_eq!(
+        DataPoint::read(DataPointType::B2, &[0x83]),
+        Ok(DataPoint::B2(B2::new(3)))
+    );
+    // Bits beyond the format's size are ignored
+    assert_eq!(
+        DataPoint::read(DataPointType::B1, &[0x82]),
+        Ok(DataPoint::B1(B1::new(false)))
+    );
+    assert_eq!(
+        DataPoint::read(DataPointType::B1, &[0x80, 0x01]),
+        Err(FrameError::LengthMismatch {
+            expected: 1,
+            actual: 2
+        })
+    );
+}
+
+#[test]
+fn long_values_follow_the_apci_byte() {
+    assert_eq!(
+        DataPoint::read(DataPointType::U8, &[0x80, 0xA5]),
+        Ok(DataPoint::U8(U8::new(0xA5)))
+    );
+    assert_eq!(
+        DataPoint::read(DataPointType::U8, &[0x81]),
+        Err(FrameError::LengthMismatch {
+            expected: 2,
+            actual: 1
+        })
+    );
+    assert_eq!(
+        DataPoint::read(DataPointType::U8, &[0x80, 0xA5, 0x00]),
+        Err(FrameError::LengthMismatch {
+            expected: 2,
+            actual: 3
+        })
+    );
+    assert!(DataPoint::read(DataPointType::U8, &[]).is_err());
+}
+
+/// Encodes `data` into a frame and decodes it back from the frame's APDU.
+fn round_trip(data: DataPoint) {
+    let dpt = data.data_point_type();
+    let frame = Frame::from_datapoint(&data).unwrap();
+    assert_eq!(DataPoint::read(dpt, frame.apdu_data()), Ok(data.clone()));
+    let value = GroupValue::from_apdu(frame.apdu_data()).unwrap();
+    assert_eq!(value.read(dpt), Ok(data));
+}
+
+/// The bytes following the APCI byte that `data` is sent as.
+fn encode(data: &DataPoint) -> Vec<u8> {
+    Frame::from_datapoint(data).unwrap().apdu_data()[1..].to_vec()
+}
+
+#[test]
+fn values_survive_a_frame() {
+    common::init();
+    for data in [
+        DataPoint::B1(B1::new(true)),
+        DataPoint::B2(B2::new(2)),
+        DataPoint::B1U3(B1U3::new(true, 5)),
+        DataPoint::Char(Char::new(b'K')),
+        DataPoint::U8(U8::new(0x42)),
+        DataPoint::V8(V8::new(-100)),
+        DataPoint::StatusMode(StatusMode::new(0x15, 2)),
+        DataPoint::U16(U16::new(0xBEEF)),
+        DataPoint::S16(S16::new(-12345)),
+        DataPoint::F16(F16::new(21.5)),
+        DataPoint::Time(Time::new(3, 23, 59, 58)),
+        DataPoint::Date(Date::new(2024, 2, 29)),
+        DataPoint::U32(U32::new(4_000_000_000)),
+        DataPoint::S32(S32::new(-2_000_000_000)),
+        DataPoint::F32(F32::new(-1.5e-3)),
+        DataPoint::AccessData(AccessData::new(123456, AccessData::PERMISSION, 3)),
+        DataPoint::String(String::new(b"KNX is OK").unwrap()),
+        DataPoint::SceneNumber(SceneNumber::new(63)),
+        DataPoint::SceneControl(SceneControl::new(true, 12)),
+        DataPoint::DateTime(
+            DateTime::new(2024, 12, 24, 18, 30, 0)
+                .with_day_of_week(2)
+                .with_flags(DateTime::SUMMER_TIME | DateTime::CLOCK_QUALITY),
+        ),
+        DataPoint::N8(N8::new(4)),
+        DataPoint::B8(B8::new(0b1010_0101)),
+        DataPoint::N2(N2::new(3)),
+        DataPoint::VarString(VarString::new(b"Living room").unwrap()),
+        DataPoint::SceneInfo(SceneInfo::new(true, 7)),
+        DataPoint::B32(B32::new(0x0003_0001)),
+        DataPoint::UnicodeString(UnicodeString::new("Küche").unwrap()),
+        DataPoint::V64(V64::new(i64::MIN)),
+    ] {
+        round_trip(data);
+    }
+}
+
+#[test]
+fn values_are_encoded_as_specified() {
+    common::init();
+    assert_eq!(
+        encode(&DataPoint::Time(Time::new(1, 12, 34, 56))),
+        [0x2C, 0x22, 0x38]
+    );
+    assert_eq!(
+        encode(&DataPoint::Date(Date::new(1999, 12, 31))),
+        [0x1F, 0x0C, 0x63]
+    );
+    assert_eq!(
+        DataPoint::read(DataPointType::Date, &[0x80, 0x01, 0x01, 0x59]),
+        Ok(DataPoint::Date(Date::new(2089, 1, 1)))
+    );
+    assert_eq!(
+        encode(&DataPoint::AccessData(AccessData::new(
+            123456,
+            AccessData::ERROR | AccessData::ENCRYPTED,
+            5
+        ))),
+        [0x12, 0x34, 0x56, 0x95]
+    );
+    assert_eq!(
+        encode(&DataPoint::String(String::new(b"KNX").unwrap())),
+        [b'K', b'N', b'X', 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+    );
+    assert_eq!(
+        encode(&DataPoint::VarString(VarString::new(b"KNX").unwrap())),
+        [b'K', b'N', b'X', 0]
+    );
+    assert_eq!(
+        encode(&DataPoint::DateTime(
+            DateTime::new(2000, 1, 2, 3, 4, 5)
+                .with_day_of_week(7)
+                .with_flags(DateTime::WORKING_DAY)
+        )),
+        [100, 1, 2, 0xE3, 4, 5, 0x40, 0x00]
+    );
+    assert_eq!(encode(&DataPoint::S16(S16::new(-2))), [0xFF, 0xFE]);
+
+    let status = StatusMode::new(0x10, 1);
+    assert_eq!(encode(&DataPoint::StatusMode(status.clone())), [0x82]);
+    assert_eq!((status.status(), status.mode()), (0x10, Some(1)));
+    let dimming = DataPoint::B1U3(B1U3::new(false, 1));
+    assert_eq!(Frame::from_datapoint(&dimming).unwrap().apdu_data(), [0x01]);
+}
+
+#[test]
+fn invalid_strings_are_rejected() {
+    assert!(String::new(b"Fifteen letters").is_err());
+    assert!(VarString::new(b"Fourteen chars").is_err());
+    assert_eq!(UnicodeString::new("A\0B"), Err(FrameError::InvalidValue));
+    assert_eq!(
+        DataPoint::read(DataPointType::UnicodeString, &[0x80, 0xC3, 0x28, 0x00]),
+        Err(FrameError::InvalidValue)
+    );
+    assert_eq!(
+        DataPoint::read(DataPointType::VarString, &[0x80]),
+        Err(FrameError::LengthMismatch {
+            expected: 2,
+            actual: 1
+        })
+    );
+}
+
+#[test]
+fn group_values_have_to_match_the_form() {
+    assert_eq!(
+        GroupValue::Short(1).read(DataPointType::U8),
+        Err(FrameError::InvalidLength)
+    );
+    assert_eq!(
+        GroupValue::from_apdu(&[0x40, 0x01])
+            .unwrap()
+            .read(DataPointType::B1),
+        Err(FrameError::InvalidLength)
+    );
+}
+
+#[test]
+fn f16_matches_the_reference_vectors() {
+    common::init();
+    for (value, encoded) in [
+        (0.0, 0x0000),
+        (0.01, 0x0001),
+        (-0.01, 0x87FF),
+        (20.47, 0x07FF),
+        (-20.48, 0x8000),
+        (21.0, 0x0C1A),
+        (-30.0, 0x8A24),
+        (F16::MAX, 0x7FFE),
+        (F16::MIN, 0xF800),
+    ] {
+        let data = DataPoint::F16(F16::new(value));
+        assert_eq!(encode(&data), u16::to_be_bytes(encoded), "{value}");
+        let decoded = DataPoint::read(DataPointType::F16, &[&[0x80][..], &encode(&data)].concat());
+        match decoded {
+            Ok(DataPoint::F16(f16)) => assert_eq!(f16.value(), Some(value)),
+            other => panic!("{other:?}"),
+        }
+    }
+}
+
+#[test]
+fn f16_rounds_to_the_nearest_mantissa() {
+    common::init();
+    for (value, encoded) in [
+        // Half a hundredth rounds away from zero
+        (0.125, 0x000D),
+        (-0.125, 0x87F3),
+        // 2047.5 hundredths don't fit the mantissa, 1023.75 at exponent 1 round up
+        (20.475, 0x0C00),
+        (1e9, 0x7FFE),
+        (f32::INFINITY, 0x7FFE),
+        (-1e9, 0xF800),
+        (f32::NAN, 0x7FFF),
+    ] {
+        let f16 = F16::new(value);
+        assert_eq!(
+            encode(&DataPoint::F16(f16)),
+            u16::to_be_bytes(encoded),
+            "{value}"
+        );
+    }
+    assert_eq!(F16::new(20.475).value(), Some(20.48));
+}
+
+#[test]
+fn f16_invalid_value_has_no_value() {
+    assert_eq!(
+        DataPoint::read(DataPointType::F16, &[0x80, 0x7F, 0xFF]),
+        Ok(DataPoint::F16(F16::INVALID))
+    );
+    assert!(!F16::INVALID.is_valid());
+    assert_eq!(F16::INVALID.value(), None);
+    assert!(F16::new(F16::MAX).is_valid());
+}
@@ -0,0 +1,73 @@
+use embassy_knx::data_point::*;
+use embassy_knx::dpt::Dpt;
+use embassy_knx::frame::FrameError;
+
+#[test]
+fn types_are_looked_up_by_number() {
+    let switch = Dpt::new(1, 1).info().unwrap();
+    assert_eq!(switch.name(), "Switch");
+    assert_eq!(switch.format(), DataPointType::B1);
+    let up_down = Dpt::new(1, 8).info().unwrap();
+    assert_eq!(up_down.name(), "UpDown");
+    assert_eq!(up_down.format(), DataPointType::B1);
+
+    let temperature = Dpt::new(9, 1).info().unwrap();
+    assert_eq!(temperature.unit(), "°C");
+    assert_eq!(temperature.range(), Some((-273.0, F16::MAX)));
+    assert_eq!(Dpt::new(10, 1).info().unwrap().range(), None);
+    assert!(Dpt::new(9, 999).info().is_none());
+}
+
+#[test]
+fn scaled_types_map_onto_the_raw_values() {
+    let percent = Dpt::new(5, 1).info().unwrap();
+    assert_eq!(percent.encode(100.0), Ok(DataPoint::U8(U8::new(255))));
+    assert_eq!(percent.encode(50.0), Ok(DataPoint::U8(U8::new(128))));
+    assert_eq!(percent.encode(0.0), Ok(DataPoint::U8(U8::new(0))));
+    assert_eq!(percent.decode(&DataPoint::U8(U8::new(255))), Ok(100.0));
+    // A_GroupValue_Write with 0x33, a fifth of the range
+    assert_eq!(percent.read(&[0x80, 0x33]), Ok(20.0));
+
+    let angle = Dpt::new(5, 3).info().unwrap();
+    assert_eq!(angle.decode(&DataPoint::U8(U8::new(255))), Ok(360.0));
+
+    let difference = Dpt::new(8, 10).info().unwrap();
+    assert_eq!(difference.encode(-1.5), Ok(DataPoint::S16(S16::new(-150))));
+    assert_eq!(difference.decode(&DataPoint::S16(S16::new(250))), Ok(2.5));
+}
+
+#[test]
+fn unscaled_types_pass_the_value_through() {
+    let temperature = Dpt::new(9, 1).info().unwrap();
+    assert_eq!(temperature.encode(21.0), Ok(DataPoint::F16(F16::new(21.0))));
+    assert_eq!(temperature.read(&[0x80, 0x0C, 0x1A]), Ok(21.0));
+    assert_eq!(
+        temperature.read(&[0x80, 0x7F, 0xFF]),
+        Err(FrameError::InvalidValue)
+    );
+
+    let counter = Dpt::new(7, 1).info().unwrap();
+    assert_eq!(counter.encode(1234.4), Ok(DataPoint::U16(U16::new(1234))));
+    let switch = Dpt::new(1, 1).info().unwrap();
+    assert_eq!(switch.encode(1.0), Ok(DataPoint::B1(B1::new(true))));
+    assert_eq!(switch.read(&[0x80]), Ok(0.0));
+}
+
+#[test]
+fn invalid_values_are_rejected() {
+    let percent = Dpt::new(5, 1).info().unwrap();
+    assert_eq!(percent.encode(100.5), Err(FrameError::InvalidValue));
+    assert_eq!(percent.encode(f32::NAN), Err(FrameError::InvalidValue));
+    assert_eq!(
+        percent.decode(&DataPoint::U16(U16::new(1))),
+        Err(FrameError::InvalidValue)
+    );
+    let temperature = Dpt::new(9, 1).info().unwrap();
+    assert_eq!(temperature.encode(-300.0), Err(FrameError::InvalidValue));
+    let time = Dpt::new(10, 1).info().unwrap();
+    assert_eq!(time.encode(1.0), Err(FrameError::InvalidValue));
+    assert_eq!(
+        time.decode(&DataPoint::Time(Time::new(1, 2, 3, 4))),
+        Err(FrameError::InvalidValue)
+    );
+}
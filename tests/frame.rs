@@ -0,0 +1,113 @@
+use embassy_knx::data_point::*;
+use embassy_knx::frame::*;
+
+#[test]
+fn group_value_response_from_datapoint() {
+    init_frame_pool();
+    let mut frame = Frame::from_datapoint(&DataPoint::B1(B1::new(true))).unwrap();
+    frame.set_priority(Priority::Normal);
+    frame.set_hop_count(6);
+    frame.set_src_addr(&IndividualAddress::from_parts(1, 1, 120));
+    frame.set_dst_addr(&Address::Group(GroupAddress::from_parts(1, 1, 98)));
+    frame.set_apci(ApciBits::Four, 0x1);
+
+    assert!(matches!(frame, Frame::Standard(_)));
+    assert_eq!(frame.length(), 9);
+    assert!(matches!(frame.priority(), Priority::Normal));
+    assert_eq!(frame.hop_count(), 6);
+    assert_eq!(frame.src_addr(), IndividualAddress::from_parts(1, 1, 120));
+    assert!(
+        matches!(frame.dst_addr(), Address::Group(addr) if addr == GroupAddress::from_parts(1, 1, 98))
+    );
+    assert_eq!(frame.tpci(TpciBits::Six), 0);
+    assert_eq!(frame.apci(ApciBits::Four), 0x1);
+    assert_eq!(frame.data()[7] & 0x3F, 1);
+}
+
+#[test]
+fn copied_frame_keeps_contents() {
+    init_frame_pool();
+    let mut frame = Frame::from_datapoint(&DataPoint::U8(U8::new(0xA5))).unwrap();
+    frame.set_dst_addr(&Address::Individual(IndividualAddress::from_parts(1, 1, 7)));
+
+    let copy = Frame::try_from(&frame).unwrap();
+    assert_eq!(copy.data(), frame.data());
+    assert_eq!(copy.checksum(), frame.checksum());
+}
+
+/// GroupValueWrite(1) from 1.1.1 to 1/1/98 as seen on the line.
+const GROUP_WRITE: [u8; 9] = [0xBC, 0x11, 0x01, 0x09, 0x62, 0xE1, 0x00, 0x81, 0x58];
+
+#[test]
+fn parse_standard_frame() {
+    init_frame_pool();
+    let frame = Frame::parse(&GROUP_WRITE).unwrap();
+
+    assert!(matches!(frame, Frame::Standard(_)));
+    assert_eq!(frame.data(), &GROUP_WRITE);
+    assert!(matches!(frame.priority(), Priority::Low));
+    assert_eq!(frame.hop_count(), 6);
+    assert_eq!(frame.src_addr(), IndividualAddress::from_parts(1, 1, 1));
+    assert!(
+        matches!(frame.dst_addr(), Address::Group(addr) if addr == GroupAddress::from_parts(1, 1, 98))
+    );
+    assert_eq!(frame.apci(ApciBits::Four), 0x2);
+    assert_eq!(frame.checksum(), GROUP_WRITE[8]);
+}
+
+#[test]
+fn parse_extended_frame() {
+    init_frame_pool();
+    let mut buf = [0; 30];
+    buf[..8].copy_from_slice(&[0x3C, 0xE0, 0x11, 0x01, 0x09, 0x62, 21, 0x00]);
+    buf[8] = 0x80;
+    buf[9..28].copy_from_slice(b"embassy-knx extends");
+    buf[29] = !buf[..29].iter().fold(0, |acc, &v| acc ^ v);
+
+    let frame = Frame::parse(&buf).unwrap();
+    assert!(matches!(frame, Frame::Extended(_)));
+    assert_eq!(frame.src_addr(), IndividualAddress::from_parts(1, 1, 1));
+    assert!(
+        matches!(frame.dst_addr(), Address::Group(addr) if addr == GroupAddress::from_parts(1, 1, 98))
+    );
+    assert_eq!(frame.length(), 30);
+    assert_eq!(frame.apci(ApciBits::Four), 0x2);
+}
+
+#[test]
+fn parse_rejects_invalid_frames() {
+    init_frame_pool();
+    assert!(matches!(Frame::parse(&[]), Err(FrameError::TooShort(0))));
+    assert!(matches!(
+        Frame::parse(&[0x00; 9]),
+        Err(FrameError::InvalidControlField(0x00))
+    ));
+    assert!(matches!(
+        Frame::parse(&GROUP_WRITE[..7]),
+        Err(FrameError::TooShort(7))
+    ));
+    assert!(matches!(
+        Frame::parse(&[0xBC; 25]),
+        Err(FrameError::TooLong(25))
+    ));
+
+    let mut buf = [0; 10];
+    buf[..9].copy_from_slice(&GROUP_WRITE);
+    assert!(matches!(
+        Frame::parse(&buf),
+        Err(FrameError::LengthMismatch {
+            expected: 9,
+            actual: 10
+        })
+    ));
+
+    let mut buf = GROUP_WRITE;
+    buf[8] ^= 0x01;
+    assert!(matches!(
+        Frame::parse(&buf),
+        Err(FrameError::Checksum {
+            expected: 0x58,
+            actual: 0x59
+        })
+    ));
+}
@@ -0,0 +1,52 @@
+use embassy_knx::frame::GroupAddress;
+use embassy_knx::group_address_table::*;
+
+#[test]
+fn addresses_resolve_in_both_directions() {
+    let table = GroupAddressTable::new();
+    assert!(table.is_empty());
+    table
+        .load(&[0x04, 0x11, 0x78, 0x08, 0x01, 0x09, 0x62, 0x7F, 0xFF, 0xAA])
+        .unwrap();
+
+    assert_eq!(table.len(), 3);
+    assert_eq!(table.group_address(0), None);
+    assert_eq!(
+        table.group_address(1),
+        Some(GroupAddress::from_parts(1, 0, 1))
+    );
+    assert_eq!(
+        table.group_address(3),
+        Some(GroupAddress::from_parts(15, 7, 255))
+    );
+    assert_eq!(table.group_address(4), None);
+    assert_eq!(table.tsap(&GroupAddress::from_parts(1, 1, 98)), Some(2));
+    assert_eq!(table.tsap(&GroupAddress::from_parts(1, 1, 99)), None);
+
+    table.clear();
+    assert_eq!(table.tsap(&GroupAddress::from_parts(1, 1, 98)), None);
+}
+
+#[test]
+fn invalid_tables_are_rejected() {
+    let table = GroupAddressTable::new();
+    table.load(&[0x02, 0x11, 0x78, 0x09, 0x62]).unwrap();
+
+    assert_eq!(
+        table.load(&[0x03, 0x11, 0x78, 0x09, 0x62]),
+        Err(GroupAddressTableError::TooShort {
+            expected: 7,
+            actual: 5
+        })
+    );
+    assert_eq!(
+        table.load(&[0x04, 0x11, 0x78, 0x08, 0x01, 0x09, 0x62, 0x09, 0x62]),
+        Err(GroupAddressTableError::NotSorted(3))
+    );
+    // The previous table stays in place
+    assert_eq!(table.tsap(&GroupAddress::from_parts(1, 1, 98)), Some(1));
+
+    // Only the individual address
+    table.load(&[0x01, 0x11, 0x78]).unwrap();
+    assert!(table.is_empty());
+}
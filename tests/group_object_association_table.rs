@@ -0,0 +1,31 @@
+use embassy_knx::group_object_association_table::*;
+
+#[test]
+fn tsaps_and_asaps_are_linked() {
+    let table = GroupObjectAssociationTable::new();
+    assert!(table.is_empty());
+    table
+        .load(&[0x04, 0x01, 0x00, 0x03, 0x01, 0x03, 0x02, 0x02, 0x02, 0xFF])
+        .unwrap();
+
+    assert_eq!(table.len(), 4);
+    assert_eq!(table.asaps(3), [1, 2]);
+    assert_eq!(table.asaps(2), [2]);
+    assert!(table.asaps(4).is_empty());
+    // A group object sends on its first association
+    assert_eq!(table.tsap(2), Some(3));
+    assert_eq!(table.tsap(0), Some(1));
+    assert_eq!(table.tsap(5), None);
+
+    assert_eq!(
+        table.load(&[0x02, 0x01, 0x00, 0x03]),
+        Err(GroupObjectAssociationTableError::TooShort {
+            expected: 5,
+            actual: 4
+        })
+    );
+    assert_eq!(table.len(), 4);
+
+    table.clear();
+    assert_eq!(table.tsap(0), None);
+}
@@ -0,0 +1,72 @@
+use embassy_knx::frame::Priority;
+use embassy_knx::group_object::*;
+use embassy_knx::group_object_table::*;
+
+#[test]
+fn descriptors_are_loaded() {
+    let table = GroupObjectTable::new();
+    assert!(table.is_empty());
+    table
+        .load(&[0x02, 0x50, 0x51, 0x5F, 0x00, 0x52, 0xB5, 0x08])
+        .unwrap();
+    assert_eq!(table.len(), 2);
+
+    let switch = table.get(0).unwrap();
+    let config = switch.config();
+    assert!(config.communication() && config.read() && config.write() && config.transmit());
+    assert!(!config.update() && !config.read_on_init());
+    assert_eq!(config.priority(), Priority::Low);
+    assert_eq!(switch.object_type(), GroupObjectType::Bit1);
+    assert_eq!(switch.value(), [0]);
+    assert_eq!(switch.state(), GroupObjectState::Ok);
+
+    let counter = table.get(1).unwrap();
+    let config = counter.config();
+    assert!(config.update() && config.read_on_init() && config.write());
+    assert!(!config.read() && !config.transmit());
+    assert_eq!(config.priority(), Priority::Normal);
+    assert_eq!(counter.object_type(), GroupObjectType::Byte2);
+    assert_eq!(counter.value(), [0, 0]);
+
+    assert!(table.get(2).is_none());
+}
+
+#[test]
+fn invalid_tables_are_rejected() {
+    let table = GroupObjectTable::new();
+    table.load(&[0x01, 0x50, 0x51, 0x5F, 0x00]).unwrap();
+
+    assert_eq!(
+        table.load(&[0x02, 0x50, 0x51, 0x5F, 0x00, 0x52, 0x5F]),
+        Err(GroupObjectTableError::TooShort {
+            expected: 8,
+            actual: 7
+        })
+    );
+    assert_eq!(
+        table.load(&[0x02, 0x50, 0x51, 0x5F, 0x00, 0x52, 0x5F, 0x0F]),
+        Err(GroupObjectTableError::InvalidType(1))
+    );
+    assert_eq!(table.len(), 1);
+}
+
+#[test]
+fn types_follow_the_descriptor_codes() {
+    let table = GroupObjectTable::new();
+    table
+        .load(&[
+            0x03, 0x50, 0x51, 0x5F, 0x05, 0x52, 0x5F, 0x06, 0x53, 0x5F, 0x0E,
+        ])
+        .unwrap();
+    let types = [
+        (GroupObjectType::Bit6, 0, 1),
+        (GroupObjectType::Bit7, 1, 1),
+        (GroupObjectType::Byte14, 14, 14),
+    ];
+    for (asap, (object_type, byte_length, value_length)) in types.into_iter().enumerate() {
+        let object = table.get(asap as u8).unwrap();
+        assert_eq!(object.object_type(), object_type);
+        assert_eq!(object_type.byte_length(), byte_length);
+        assert_eq!(object.value().len(), value_length);
+    }
+}
@@ -0,0 +1,260 @@
+mod common;
+
+use core::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use embassy_futures::block_on;
+use embassy_futures::select::{select, select3, Either, Either3};
+use embassy_knx::data_link_layer::DataLinkLayer;
+use embassy_knx::data_point::*;
+use embassy_knx::frame::*;
+use embassy_knx::knx_ip::tunnelling::{TunnellingConfig, TunnellingServer};
+use embassy_knx::knx_ip::DeviceInfo;
+use embassy_knx::phy::{KnxPhy, PhyChannels, PhyRunner};
+use embassy_knx::sim_bus::SimBus;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embedded_io_async::ErrorKind;
+use embedded_nal_async::UnconnectedUdp;
+
+type Datagrams = Channel<CriticalSectionRawMutex, (Vec<u8>, SocketAddr), 8>;
+
+const SERVER: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 3671);
+const MULTICAST: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(224, 0, 23, 12), 3671);
+const CLIENT: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 50000));
+const CLIENT_HPAI: [u8; 8] = [0x08, 0x01, 127, 0, 0, 1, 0xC3, 0x50];
+const TUNNEL_ADDRESS: IndividualAddress = IndividualAddress::from_parts(1, 1, 100);
+
+/// Socket that takes datagrams from `inbox` and puts everything sent into `outbox`. Search
+/// requests arrive on the multicast address, everything is sent from the server's.
+struct MockUdp {
+    inbox: &'static Datagrams,
+    outbox: &'static Datagrams,
+}
+
+impl UnconnectedUdp for MockUdp {
+    type Error = ErrorKind;
+
+    async fn send(
+        &mut self,
+        local: SocketAddr,
+        remote: SocketAddr,
+        data: &[u8],
+    ) -> Result<(), Self::Error> {
+        assert_eq!(local, SocketAddr::V4(SERVER));
+        self.outbox.send((data.to_vec(), remote)).await;
+        Ok(())
+    }
+
+    async fn receive_into(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<(usize, SocketAddr, SocketAddr), Self::Error> {
+        let (data, remote) = self.inbox.receive().await;
+        buffer[..data.len()].copy_from_slice(&data);
+        let local = match data[2..4] {
+            [0x02, 0x01] => MULTICAST,
+            _ => SERVER,
+        };
+        Ok((data.len(), SocketAddr::V4(local), remote))
+    }
+}
+
+fn server(
+    inbox: &'static Datagrams,
+    outbox: &'static Datagrams,
+    channels: &'static PhyChannels,
+) -> TunnellingServer<MockUdp> {
+    TunnellingServer::new(
+        MockUdp { inbox, outbox },
+        DataLinkLayer::new(channels),
+        TunnellingConfig {
+            endpoint: SERVER,
+            tunnel_address: TUNNEL_ADDRESS,
+            device: DeviceInfo {
+                individual_address: IndividualAddress::from_parts(1, 1, 0),
+                serial_number: [0x00, 0xFA, 0x01, 0x02, 0x03, 0x04],
+                mac_address: [0x02, 0x00, 0x00, 0x00, 0x00, 0x01],
+                name: "embassy-knx",
+            },
+        },
+    )
+}
+
+fn packet(service: u16, body: &[&[u8]]) -> Vec<u8> {
+    let body = body.concat();
+    let mut packet = vec![0x06, 0x10];
+    packet.extend_from_slice(&service.to_be_bytes());
+    packet.extend_from_slice(&(6 + body.len() as u16).to_be_bytes());
+    packet.extend_from_slice(&body);
+    packet
+}
+
+/// Sends a request from the client and returns the body of the next answer, checking its type.
+async fn request(inbox: &Datagrams, outbox: &Datagrams, packet: Vec<u8>, service: u16) -> Vec<u8> {
+    inbox.send((packet, CLIENT)).await;
+    answer(outbox, service).await
+}
+
+async fn answer(outbox: &Datagrams, service: u16) -> Vec<u8> {
+    let (answer, remote) = outbox.receive().await;
+    assert_eq!(remote, CLIENT);
+    assert_eq!(&answer[..2], &[0x06, 0x10]);
+    assert_eq!(u16::from_be_bytes([answer[2], answer[3]]), service);
+    assert_eq!(
+        u16::from_be_bytes([answer[4], answer[5]]) as usize,
+        answer.len()
+    );
+    answer[6..].to_vec()
+}
+
+fn connect_request(connection_type: u8) -> Vec<u8> {
+    packet(
+        0x0205,
+        &[
+            &CLIENT_HPAI,
+            &CLIENT_HPAI,
+            &[0x04, connection_type, 0x02, 0x00],
+        ],
+    )
+}
+
+/// L_Data.req of A_GroupValue_Read to 1/1/98 with the source left for the server to fill in.
+fn group_read(channel_id: u8, seq: u8) -> Vec<u8> {
+    packet(
+        0x0420,
+        &[
+            &[0x04, channel_id, seq, 0x00],
+            &[
+                0x11, 0x00, 0xBC, 0xE0, 0x00, 0x00, 0x09, 0x62, 0x01, 0x00, 0x00,
+            ],
+        ],
+    )
+}
+
+#[test]
+fn group_read_through_tunnel() {
+    run_device!({
+        static TUNNEL_CHANNELS: PhyChannels = PhyChannels::new();
+        static INBOX: Datagrams = Channel::new();
+        static OUTBOX: Datagrams = Channel::new();
+        TABLE.load(&[0x02, 0x11, 0x78, 0x09, 0x62]).unwrap();
+        ASSOCIATION_TABLE.load(&[0x01, 0x01, 0x00]).unwrap();
+        OBJECT_TABLE.load(&[0x01, 0x50, 0x51, 0x5F, 0x00]).unwrap();
+        OBJECT_TABLE
+            .object(0)
+            .set(&DataPoint::B1(B1::new(true)))
+            .unwrap();
+
+        let tunnel = PhyRunner::new(BUS.port(1), &TUNNEL_CHANNELS).run();
+        let server = server(&INBOX, &OUTBOX, &TUNNEL_CHANNELS).run();
+
+        let test = async {
+            let response = request(&INBOX, &OUTBOX, connect_request(0x04), 0x0206).await;
+            assert_eq!(&response[..2], &[0x01, 0x00]);
+            assert_eq!(&response[2..10], &[0x08, 0x01, 127, 0, 0, 1, 0x0E, 0x57]);
+            assert_eq!(&response[10..], &[0x04, 0x04, 0x11, 0x64]);
+
+            let ack = request(&INBOX, &OUTBOX, group_read(1, 0), 0x0421).await;
+            assert_eq!(ack, [0x04, 0x01, 0x00, 0x00]);
+
+            let con = answer(&OUTBOX, 0x0420).await;
+            assert_eq!(&con[..4], &[0x04, 0x01, 0x00, 0x00]);
+            assert_eq!(
+                &con[4..],
+                &[0x2E, 0x00, 0xBC, 0xE0, 0x11, 0x64, 0x09, 0x62, 0x01, 0x00, 0x00]
+            );
+
+            assert!(matches!(
+                IND.receive().await,
+                ApplicationServiceInd::GroupValueRead(0)
+            ));
+            let ind = answer(&OUTBOX, 0x0420).await;
+            assert_eq!(&ind[..4], &[0x04, 0x01, 0x01, 0x00]);
+            assert_eq!(&ind[4..6], &[0x29, 0x00]);
+            assert_eq!(&ind[8..12], &[0x11, 0x78, 0x09, 0x62]);
+            assert_eq!(&ind[12..], &[0x01, 0x00, 0x41]);
+            INBOX
+                .send((packet(0x0421, &[&[0x04, 0x01, 0x01, 0x00]]), CLIENT))
+                .await;
+
+            let state = packet(0x0207, &[&[0x01, 0x00], &CLIENT_HPAI]);
+            let response = request(&INBOX, &OUTBOX, state.clone(), 0x0208).await;
+            assert_eq!(response, [0x01, 0x00]);
+
+            let disconnect = packet(0x0209, &[&[0x01, 0x00], &CLIENT_HPAI]);
+            let response = request(&INBOX, &OUTBOX, disconnect, 0x020A).await;
+            assert_eq!(response, [0x01, 0x00]);
+
+            let response = request(&INBOX, &OUTBOX, state, 0x0208).await;
+            assert_eq!(response, [0x01, 0x21]);
+        };
+        async move {
+            match select3(tunnel, server, test).await {
+                Either3::Third(()) => {}
+                _ => unreachable!(),
+            }
+        }
+    });
+}
+
+#[test]
+fn description_and_connection_handling() {
+    static BUS: SimBus<2> = SimBus::new();
+    static TUNNEL_CHANNELS: PhyChannels = PhyChannels::new();
+    static INBOX: Datagrams = Channel::new();
+    static OUTBOX: Datagrams = Channel::new();
+    common::init();
+
+    let tunnel = PhyRunner::new(BUS.port(0), &TUNNEL_CHANNELS).run();
+    let server = server(&INBOX, &OUTBOX, &TUNNEL_CHANNELS).run();
+    let mut line = BUS.port(1);
+
+    let test = async {
+        let description = packet(0x0203, &[&CLIENT_HPAI]);
+        let response = request(&INBOX, &OUTBOX, description, 0x0204).await;
+        assert_eq!(&response[..3], &[0x36, 0x01, 0x02]);
+        assert_eq!(&response[4..6], &[0x11, 0x00]);
+        assert_eq!(&response[8..14], &[0x00, 0xFA, 0x01, 0x02, 0x03, 0x04]);
+        assert_eq!(&response[14..18], &[224, 0, 23, 12]);
+        assert_eq!(&response[24..35], b"embassy-knx");
+        assert_eq!(&response[54..], &[0x06, 0x02, 0x02, 0x01, 0x04, 0x01]);
+
+        let search = packet(0x0201, &[&CLIENT_HPAI]);
+        let response = request(&INBOX, &OUTBOX, search, 0x0202).await;
+        assert_eq!(&response[..8], &[0x08, 0x01, 127, 0, 0, 1, 0x0E, 0x57]);
+        assert_eq!(&response[8..10], &[0x36, 0x01]);
+
+        let response = request(&INBOX, &OUTBOX, connect_request(0x03), 0x0206).await;
+        assert_eq!(response, [0x00, 0x22]);
+        let response = request(&INBOX, &OUTBOX, connect_request(0x04), 0x0206).await;
+        assert_eq!(&response[..2], &[0x01, 0x00]);
+        let response = request(&INBOX, &OUTBOX, connect_request(0x04), 0x0206).await;
+        assert_eq!(response, [0x00, 0x24]);
+
+        let ack = request(&INBOX, &OUTBOX, group_read(1, 0), 0x0421).await;
+        assert_eq!(ack, [0x04, 0x01, 0x00, 0x00]);
+        let frame = line.receive().await.unwrap();
+        assert_eq!(frame.src_addr(), TUNNEL_ADDRESS);
+        answer(&OUTBOX, 0x0420).await;
+
+        // A repeated request is acknowledged again but not sent twice
+        let ack = request(&INBOX, &OUTBOX, group_read(1, 0), 0x0421).await;
+        assert_eq!(ack, [0x04, 0x01, 0x00, 0x00]);
+        // Requests out of sequence and for other channels are dropped
+        INBOX.send((group_read(1, 5), CLIENT)).await;
+        INBOX.send((group_read(2, 1), CLIENT)).await;
+        let ack = request(&INBOX, &OUTBOX, group_read(1, 1), 0x0421).await;
+        assert_eq!(ack, [0x04, 0x01, 0x01, 0x00]);
+        line.receive().await.unwrap();
+        answer(&OUTBOX, 0x0420).await;
+        assert!(OUTBOX.is_empty());
+        assert!(matches!(
+            select(line.receive(), embassy_futures::yield_now()).await,
+            Either::Second(())
+        ));
+    };
+
+    match block_on(select3(tunnel, server, test)) {
+        Either3::Third(()) => {}
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,159 @@
+mod common;
+
+use async_io::Async;
+use embassy_futures::block_on;
+use embassy_futures::select::{select, Either};
+use embassy_knx::frame::*;
+use embassy_knx::knx_ip::routing::RoutingPhy;
+use embassy_knx::knx_ip::KNX_IP_MULTICAST_ADDR;
+use embassy_knx::phy::KnxPhy;
+use embassy_time::{Duration, Instant, Timer};
+use embedded_io_async::ErrorKind;
+use embedded_nal_async::UnconnectedUdp;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+
+const GROUP_WRITE: [u8; 9] = [0xBC, 0x11, 0x01, 0x09, 0x62, 0xE1, 0x00, 0x81, 0x58];
+
+/// Routing socket on the loopback interface, every test uses its own port as multicast group.
+struct LoopbackUdp {
+    socket: Async<UdpSocket>,
+    local: SocketAddr,
+}
+
+impl LoopbackUdp {
+    fn bind(port: u16) -> Self {
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP)).unwrap();
+        socket.set_reuse_address(true).unwrap();
+        socket
+            .bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port).into())
+            .unwrap();
+        socket
+            .join_multicast_v4(&KNX_IP_MULTICAST_ADDR, &Ipv4Addr::LOCALHOST)
+            .unwrap();
+        socket.set_multicast_if_v4(&Ipv4Addr::LOCALHOST).unwrap();
+        socket.set_multicast_loop_v4(true).unwrap();
+        Self {
+            socket: Async::new(socket.into()).unwrap(),
+            local: SocketAddrV4::new(Ipv4Addr::LOCALHOST, port).into(),
+        }
+    }
+}
+
+impl UnconnectedUdp for LoopbackUdp {
+    type Error = ErrorKind;
+
+    async fn send(
+        &mut self,
+        _local: SocketAddr,
+        remote: SocketAddr,
+        data: &[u8],
+    ) -> Result<(), Self::Error> {
+        self.socket
+            .send_to(data, remote)
+            .await
+            .map(|_| ())
+            .map_err(|_| ErrorKind::Other)
+    }
+
+    async fn receive_into(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<(usize, SocketAddr, SocketAddr), Self::Error> {
+        let (len, remote) = self
+            .socket
+            .recv_from(buffer)
+            .await
+            .map_err(|_| ErrorKind::Other)?;
+        Ok((len, self.local, remote))
+    }
+}
+
+async fn router(port: u16, address: IndividualAddress) -> RoutingPhy<LoopbackUdp> {
+    let socket = LoopbackUdp::bind(port);
+    let local = socket.local;
+    let mut phy = RoutingPhy::new(
+        socket,
+        local,
+        SocketAddrV4::new(KNX_IP_MULTICAST_ADDR, port),
+    );
+    phy.reset().await.unwrap();
+    phy.set_address(&address).await.unwrap();
+    phy
+}
+
+fn group_write() -> Frame {
+    Frame::parse(&GROUP_WRITE).unwrap()
+}
+
+/// Receives on `phy` for a while so it can process flow control packets.
+async fn listen(phy: &mut RoutingPhy<LoopbackUdp>, duration: Duration) {
+    match select(phy.ready_to_receive(), Timer::after(duration)).await {
+        Either::First(_) => panic!("unexpected frame"),
+        Either::Second(()) => {}
+    }
+}
+
+#[test]
+fn frames_are_exchanged_over_multicast() {
+    common::init();
+    block_on(async {
+        let mut a = router(13671, IndividualAddress::from_parts(1, 1, 1)).await;
+        let mut b = router(13671, IndividualAddress::from_parts(1, 1, 2)).await;
+
+        a.send(group_write()).await.unwrap();
+        let frame = b.receive().await.unwrap();
+        assert_eq!(frame.src_addr(), IndividualAddress::from_parts(1, 1, 1));
+        assert!(
+            matches!(frame.dst_addr(), Address::Group(addr) if addr == GroupAddress::from_parts(1, 1, 98))
+        );
+        assert_eq!(frame.apdu_data(), group_write().apdu_data());
+
+        // A doesn't see its own frame coming back, only the answer of B
+        let mut answer = GROUP_WRITE;
+        answer[2] = 0x02;
+        answer[8] ^= 0x01 ^ 0x02;
+        b.send(Frame::parse(&answer).unwrap()).await.unwrap();
+        let frame = a.receive().await.unwrap();
+        assert_eq!(frame.src_addr(), IndividualAddress::from_parts(1, 1, 2));
+    });
+}
+
+#[test]
+fn sending_is_rate_limited() {
+    common::init();
+    block_on(async {
+        let mut phy = router(13672, IndividualAddress::from_parts(1, 1, 1)).await;
+        let start = Instant::now();
+        for _ in 0..6 {
+            phy.send(group_write()).await.unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    });
+}
+
+#[test]
+fn routing_busy_pauses_sending() {
+    common::init();
+    block_on(async {
+        let mut phy = router(13673, IndividualAddress::from_parts(1, 1, 1)).await;
+        let busy = Socket::new(Domain::IPV4, Type::DGRAM, None).unwrap();
+        busy.set_multicast_if_v4(&Ipv4Addr::LOCALHOST).unwrap();
+        let busy: UdpSocket = busy.into();
+        // ROUTING_BUSY with a wait time of 200 ms, then some garbage that has to be ignored
+        busy.send_to(
+            &[
+                0x06, 0x10, 0x05, 0x32, 0x00, 0x0C, 0x06, 0x00, 0x00, 0xC8, 0x00, 0x00,
+            ],
+            (KNX_IP_MULTICAST_ADDR, 13673),
+        )
+        .unwrap();
+        busy.send_to(&[0x06, 0x10, 0x05, 0x30], (KNX_IP_MULTICAST_ADDR, 13673))
+            .unwrap();
+
+        let start = Instant::now();
+        listen(&mut phy, Duration::from_millis(20)).await;
+        phy.send(group_write()).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    });
+}
@@ -0,0 +1,287 @@
+mod common;
+
+use embassy_futures::block_on;
+use embassy_futures::join::join;
+use embassy_futures::select::{select, Either};
+use embassy_knx::data_link_layer::{DataLinkLayer, DataServiceInd};
+use embassy_knx::frame::*;
+use embassy_knx::ncn51_driver::*;
+use embassy_knx::phy::{PhyChannels, PhyRunner, TransferError};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embedded_io_async::{ErrorKind, ErrorType, Read, Write};
+
+type Bytes = Channel<CriticalSectionRawMutex, u8, 64>;
+
+const GROUP_WRITE: [u8; 9] = [0xBC, 0x11, 0x01, 0x09, 0x62, 0xE1, 0x00, 0x81, 0x58];
+
+/// UART whose other end is played by the test: `chip` is what the NCN5121 sends, `host`
+/// collects everything the driver writes.
+struct MockUart {
+    chip: &'static Bytes,
+    host: &'static Bytes,
+}
+
+impl ErrorType for MockUart {
+    type Error = ErrorKind;
+}
+
+impl Read for MockUart {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        buf[0] = self.chip.receive().await;
+        Ok(1)
+    }
+}
+
+impl Write for MockUart {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        for byte in buf {
+            self.host.send(*byte).await;
+        }
+        Ok(buf.len())
+    }
+}
+
+/// Waits until the driver wrote `expected`, then answers with `answer`.
+async fn chip(host: &Bytes, chip: &Bytes, expected: &[u8], answer: &[u8]) {
+    for byte in expected {
+        assert_eq!(host.receive().await, *byte);
+    }
+    for byte in answer {
+        chip.send(*byte).await;
+    }
+}
+
+#[test]
+fn control_requests_while_running() {
+    static CHIP: Bytes = Channel::new();
+    static HOST: Bytes = Channel::new();
+    static CHANNELS: PhyChannels = PhyChannels::new();
+    static CTRL: CtrlChannels = CtrlChannels::new();
+    common::init();
+
+    let driver = NCN51Driver::with_control(
+        MockUart {
+            chip: &CHIP,
+            host: &HOST,
+        },
+        &CTRL,
+    );
+    let runner = PhyRunner::new(driver, &CHANNELS);
+    let data_link = DataLinkLayer::new(&CHANNELS);
+    let control = NCN51Control::new(&CTRL);
+
+    let test = async {
+        // Reset and address setup of the runner
+        chip(&HOST, &CHIP, &[0x01], &[0x03]).await;
+        chip(&HOST, &CHIP, &[0xF1, 0x11, 0x78, 0x00], &[]).await;
+
+        let (state, ()) = join(control.state(), chip(&HOST, &CHIP, &[0x02], &[0x8F])).await;
+        let state = state.unwrap();
+        assert!(state.slave_collision());
+        assert!(state.thermal_warning());
+        assert!(!state.protocol_error());
+        assert!(!state.transmit_error());
+
+        let (mode, ()) = join(
+            control.system_state(),
+            chip(&HOST, &CHIP, &[0x0D], &[0x4B, 0x04]),
+        )
+        .await;
+        assert_eq!(mode.unwrap().mode(), Some(OperationMode::Normal));
+
+        // A register value that looks like the start of a frame
+        let (value, ()) = join(
+            control.read_register(Register::AnalogStatus0),
+            chip(&HOST, &CHIP, &[0x3B], &[0xBC]),
+        )
+        .await;
+        assert_eq!(value.unwrap(), 0xBC);
+
+        let (res, ()) = join(
+            control.set_repetition(3, 9),
+            chip(&HOST, &CHIP, &[0xF2, 0x37, 0x00, 0x00], &[]),
+        )
+        .await;
+        res.unwrap();
+        let (res, ()) = join(
+            control.write_register(Register::Watchdog, 0x55),
+            chip(&HOST, &CHIP, &[0x28, 0x55], &[]),
+        )
+        .await;
+        res.unwrap();
+        let (res, ()) = join(control.reset(), chip(&HOST, &CHIP, &[0x01], &[0x03])).await;
+        res.unwrap();
+
+        // Frames are still received and acknowledged
+        chip(&HOST, &CHIP, &[], &GROUP_WRITE).await;
+        match data_link.receive().await {
+            DataServiceInd::Data(frame) => {
+                assert_eq!(frame.src_addr(), IndividualAddress::from_parts(1, 1, 1))
+            }
+            ind => panic!("unexpected indication {:?}", ind),
+        }
+        assert_eq!(HOST.receive().await, 0x11);
+    };
+
+    match block_on(select(runner.run(), test)) {
+        Either::First(_) => unreachable!(),
+        Either::Second(()) => {}
+    }
+}
+
+#[test]
+fn unanswered_request_times_out() {
+    static CHIP: Bytes = Channel::new();
+    static HOST: Bytes = Channel::new();
+    static CHANNELS: PhyChannels = PhyChannels::new();
+    static CTRL: CtrlChannels = CtrlChannels::new();
+    common::init();
+
+    let driver = NCN51Driver::with_control(
+        MockUart {
+            chip: &CHIP,
+            host: &HOST,
+        },
+        &CTRL,
+    );
+    let runner = PhyRunner::new(driver, &CHANNELS);
+    let data_link = DataLinkLayer::new(&CHANNELS);
+    let control = NCN51Control::new(&CTRL);
+
+    let test = async {
+        chip(&HOST, &CHIP, &[0x01], &[0x03]).await;
+        chip(&HOST, &CHIP, &[0xF1, 0x11, 0x78, 0x00], &[]).await;
+        assert!(control.state().await.is_err());
+        // The driver keeps serving requests after one went unanswered
+        chip(&HOST, &CHIP, &[0x02], &[]).await;
+        let (state, ()) = join(control.state(), chip(&HOST, &CHIP, &[0x02], &[0x07])).await;
+        assert!(!state.unwrap().slave_collision());
+
+        // Once the register value is given up on, the next frame isn't taken for it
+        assert!(control.read_register(Register::Watchdog).await.is_err());
+        chip(&HOST, &CHIP, &[0x38], &GROUP_WRITE).await;
+        match data_link.receive().await {
+            DataServiceInd::Data(frame) => {
+                assert_eq!(frame.src_addr(), IndividualAddress::from_parts(1, 1, 1))
+            }
+            ind => panic!("unexpected indication {:?}", ind),
+        }
+    };
+
+    match block_on(select(runner.run(), test)) {
+        Either::First(_) => unreachable!(),
+        Either::Second(()) => {}
+    }
+}
+
+#[test]
+fn late_answers_are_skipped() {
+    static CHIP: Bytes = Channel::new();
+    static HOST: Bytes = Channel::new();
+    static CHANNELS: PhyChannels = PhyChannels::new();
+    static CTRL: CtrlChannels = CtrlChannels::new();
+    common::init();
+
+    let driver = NCN51Driver::with_control(
+        MockUart {
+            chip: &CHIP,
+            host: &HOST,
+        },
+        &CTRL,
+    );
+    let runner = PhyRunner::new(driver, &CHANNELS);
+    let control = NCN51Control::new(&CTRL);
+
+    let test = async {
+        chip(&HOST, &CHIP, &[0x01], &[0x03]).await;
+        chip(&HOST, &CHIP, &[0xF1, 0x11, 0x78, 0x00], &[]).await;
+
+        // The UART is stuck, so the request is only written after it was given up on
+        while HOST.try_send(0).is_ok() {}
+        assert!(matches!(
+            control.set_repetition(3, 3).await,
+            Err(TransferError::TimeoutError(_))
+        ));
+        let unstick = async {
+            while HOST.receive().await == 0 {}
+            chip(&HOST, &CHIP, &[0x33, 0x00, 0x00], &[]).await;
+            chip(&HOST, &CHIP, &[0x02], &[0x07]).await;
+        };
+        let (state, ()) = join(control.state(), unstick).await;
+        assert!(!state.unwrap().slave_collision());
+    };
+
+    match block_on(select(runner.run(), test)) {
+        Either::First(_) => unreachable!(),
+        Either::Second(()) => {}
+    }
+}
+
+#[test]
+fn bus_monitor_captures_everything() {
+    static CHIP: Bytes = Channel::new();
+    static HOST: Bytes = Channel::new();
+    static CHANNELS: PhyChannels = PhyChannels::new();
+    common::init();
+
+    let driver = NCN51Driver::new(MockUart {
+        chip: &CHIP,
+        host: &HOST,
+    });
+    let runner = PhyRunner::new(driver, &CHANNELS);
+    let data_link = DataLinkLayer::new(&CHANNELS);
+
+    let telegram = || async {
+        match data_link.receive().await {
+            DataServiceInd::Busmon(telegram) => telegram,
+            ind => panic!("unexpected indication {:?}", ind),
+        }
+    };
+
+    let test = async {
+        chip(&HOST, &CHIP, &[0x01], &[0x03]).await;
+        chip(&HOST, &CHIP, &[0xF1, 0x11, 0x78, 0x00], &[]).await;
+        data_link.set_busmon(true);
+        chip(&HOST, &CHIP, &[0x05], &GROUP_WRITE).await;
+        chip(&HOST, &CHIP, &[], &[0xCC]).await;
+        let mut corrupt = GROUP_WRITE;
+        corrupt[8] ^= 0x01;
+        chip(&HOST, &CHIP, &[], &corrupt).await;
+
+        let frame = telegram().await;
+        assert_eq!(frame.data(), &GROUP_WRITE);
+        assert!(matches!(frame.kind(), TelegramKind::Frame));
+        assert_eq!(
+            frame.frame().unwrap().src_addr(),
+            IndividualAddress::from_parts(1, 1, 1)
+        );
+        let ack = telegram().await;
+        assert!(matches!(
+            ack.kind(),
+            TelegramKind::Acknowledgement(Acknowledgement::Ack)
+        ));
+        assert!(ack.timestamp() >= frame.timestamp());
+        assert!(matches!(
+            telegram().await.kind(),
+            TelegramKind::Corrupt(FrameError::Checksum { .. })
+        ));
+        // A bus monitor neither acknowledges nor sends
+        assert!(matches!(
+            data_link.send(Frame::parse(&GROUP_WRITE).unwrap()).await,
+            Err(TransferError::Unsupported)
+        ));
+        assert!(HOST.is_empty());
+
+        data_link.set_busmon(false);
+        chip(&HOST, &CHIP, &[0x01], &[0x03]).await;
+        chip(&HOST, &CHIP, &[0xF1, 0x11, 0x78, 0x00], &GROUP_WRITE).await;
+        assert!(matches!(data_link.receive().await, DataServiceInd::Data(_)));
+        assert_eq!(HOST.receive().await, 0x11);
+    };
+
+    match block_on(select(runner.run(), test)) {
+        Either::First(_) => unreachable!(),
+        Either::Second(()) => {}
+    }
+}
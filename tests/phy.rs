@@ -0,0 +1,137 @@
+use embassy_futures::block_on;
+use embassy_futures::join::join;
+use embassy_futures::select::{select, Either};
+use embassy_knx::data_link_layer::{ConStatus, DataLinkLayer, DataServiceInd};
+use embassy_knx::data_point::*;
+use embassy_knx::frame::*;
+use embassy_knx::phy::{KnxPhy, PhyChannels, PhyRunner, TransferError};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_time::Timer;
+
+type FrameChannel = Channel<CriticalSectionRawMutex, Frame, 4>;
+type ConChannel = Channel<CriticalSectionRawMutex, ConStatus, 4>;
+
+/// Transceiver that hands every sent frame to `sent` and delivers whatever is put on `bus`.
+/// Sent frames are confirmed with what is put on `cons`, if there is one, positively
+/// otherwise.
+struct MockPhy {
+    bus: &'static FrameChannel,
+    sent: &'static FrameChannel,
+    cons: Option<&'static ConChannel>,
+    address: Option<IndividualAddress>,
+}
+
+impl KnxPhy for MockPhy {
+    async fn send(&mut self, frame: Frame) -> Result<ConStatus, TransferError> {
+        self.sent.send(frame).await;
+        match self.cons {
+            Some(cons) => Ok(cons.receive().await),
+            None => Ok(ConStatus::Ok),
+        }
+    }
+
+    async fn ready_to_receive(&mut self) -> Result<(), TransferError> {
+        self.bus.ready_to_receive().await;
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Result<Frame, TransferError> {
+        Ok(self.bus.receive().await)
+    }
+
+    async fn reset(&mut self) -> Result<(), TransferError> {
+        self.address = None;
+        Ok(())
+    }
+
+    async fn set_address(&mut self, addr: &IndividualAddress) -> Result<(), TransferError> {
+        self.address = Some(addr.clone());
+        Ok(())
+    }
+}
+
+fn test_frame(value: u8) -> Frame {
+    let mut frame = Frame::from_datapoint(&DataPoint::U8(U8::new(value))).unwrap();
+    frame.set_src_addr(&IndividualAddress::from_parts(1, 1, 1));
+    frame.set_dst_addr(&Address::Group(GroupAddress::from_parts(1, 1, 98)));
+    frame
+}
+
+#[test]
+fn data_link_layer_over_mock_phy() {
+    static CHANNELS: PhyChannels = PhyChannels::new();
+    static BUS: FrameChannel = Channel::new();
+    static SENT: FrameChannel = Channel::new();
+    init_frame_pool();
+
+    let phy = MockPhy {
+        bus: &BUS,
+        sent: &SENT,
+        cons: None,
+        address: None,
+    };
+    let runner = PhyRunner::new(phy, &CHANNELS);
+    let data_link = DataLinkLayer::new(&CHANNELS);
+
+    let test = async {
+        assert_eq!(
+            data_link.send(test_frame(0x12)).await.unwrap(),
+            ConStatus::Ok
+        );
+        let sent = SENT.receive().await;
+        assert_eq!(sent.apdu_data()[1], 0x12);
+
+        BUS.send(test_frame(0x34)).await;
+        match data_link.receive().await {
+            DataServiceInd::Data(frame) => assert_eq!(frame.apdu_data()[1], 0x34),
+            ind => panic!("unexpected indication {:?}", ind),
+        }
+    };
+
+    match block_on(select(runner.run(), test)) {
+        Either::First(_) => unreachable!(),
+        Either::Second(()) => {}
+    }
+}
+
+#[test]
+fn late_confirmations_are_skipped() {
+    static CHANNELS: PhyChannels = PhyChannels::new();
+    static BUS: FrameChannel = Channel::new();
+    static SENT: FrameChannel = Channel::new();
+    static CONS: ConChannel = Channel::new();
+    init_frame_pool();
+
+    let phy = MockPhy {
+        bus: &BUS,
+        sent: &SENT,
+        cons: Some(&CONS),
+        address: None,
+    };
+    let runner = PhyRunner::new(phy, &CHANNELS);
+    let data_link = DataLinkLayer::new(&CHANNELS);
+
+    let test = async {
+        assert!(matches!(
+            data_link.send(test_frame(0x12)).await,
+            Err(TransferError::TimeoutError(_))
+        ));
+        assert_eq!(SENT.receive().await.apdu_data()[1], 0x12);
+
+        // The transceiver only confirms the first frame once the second is waiting
+        let confirm = async {
+            Timer::after_millis(100).await;
+            CONS.send(ConStatus::NotOk).await;
+            assert_eq!(SENT.receive().await.apdu_data()[1], 0x34);
+            CONS.send(ConStatus::Ok).await;
+        };
+        let (con, ()) = join(data_link.send(test_frame(0x34)), confirm).await;
+        assert_eq!(con.unwrap(), ConStatus::Ok);
+    };
+
+    match block_on(select(runner.run(), test)) {
+        Either::First(_) => unreachable!(),
+        Either::Second(()) => {}
+    }
+}
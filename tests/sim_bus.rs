@@ -0,0 +1,912 @@
+mod common;
+
+use core::future::ready;
+use embassy_futures::block_on;
+use embassy_futures::join::{join, join3};
+use embassy_futures::select::{select, Either};
+use embassy_knx::application_layer::*;
+use embassy_knx::data_link_layer::ConStatus;
+use embassy_knx::data_point::*;
+use embassy_knx::frame::*;
+use embassy_knx::group_object::GroupObjectState;
+use embassy_knx::phy::{KnxPhy, TransferError};
+use embassy_knx::settings;
+use embassy_knx::sim_bus::SimBus;
+use embassy_knx::transport_layer::*;
+use embassy_time::{Duration, Instant, Timer};
+
+const TOOL_ADDRESS: IndividualAddress = IndividualAddress::from_parts(1, 1, 200);
+/// 1/1/96, 1/1/97 and 1/1/98 on TSAPs 1 to 3.
+const GROUP_ADDRESSES: [u8; 9] = [0x04, 0x11, 0x78, 0x09, 0x60, 0x09, 0x61, 0x09, 0x62];
+/// TSAP 1 to ASAP 0, TSAP 3 to ASAPs 1 and 2, ASAP 2 also listens on TSAP 2.
+const ASSOCIATIONS: [u8; 9] = [0x04, 0x01, 0x00, 0x03, 0x01, 0x03, 0x02, 0x02, 0x02];
+/// Three 1 bit objects, ASAP 0 can't be written from the bus, ASAP 2 can't be read.
+const OBJECTS: [u8; 11] = [
+    0x03, 0x50, 0x51, 0x4F, 0x00, 0x52, 0x5F, 0x00, 0x53, 0x57, 0x00,
+];
+
+fn frame(src: IndividualAddress, dst: Address, priority: Priority) -> Frame {
+    let mut frame = Frame::from_datapoint(&DataPoint::B1(B1::new(false))).unwrap();
+    frame.set_priority(priority);
+    frame.set_hop_count(6);
+    frame.set_src_addr(&src);
+    frame.set_dst_addr(&dst);
+    frame
+}
+
+fn individual_frame(dst: IndividualAddress, tpci: u8) -> Frame {
+    let mut frame = frame(TOOL_ADDRESS, Address::Individual(dst), Priority::System);
+    frame.set_tpci(TpciBits::Eight, tpci);
+    frame
+}
+
+#[test]
+fn unacknowledged_frame_is_repeated() {
+    static BUS: SimBus<2> = SimBus::new();
+    common::init();
+    let mut tool = BUS.port(0);
+    let mut device = BUS.port(1);
+
+    block_on(async {
+        device
+            .set_address(&IndividualAddress::from_parts(1, 1, 2))
+            .await
+            .unwrap();
+
+        let frame = individual_frame(IndividualAddress::from_parts(1, 1, 3), 0x80);
+        assert!(matches!(tool.send(frame).await, Ok(ConStatus::NotOk)));
+        for repetition in 0..4 {
+            let telegram = BUS.monitor().await;
+            let received = device.receive().await.unwrap();
+            assert_eq!(telegram.data(), received.data());
+            if repetition == 0 {
+                assert!(matches!(telegram.repeated(), Repeated::NotRepeated));
+            } else {
+                assert!(matches!(telegram.repeated(), Repeated::Repeated));
+            }
+        }
+
+        let frame = individual_frame(IndividualAddress::from_parts(1, 1, 2), 0x80);
+        assert!(matches!(tool.send(frame).await, Ok(ConStatus::Ok)));
+        assert!(matches!(
+            BUS.monitor().await.repeated(),
+            Repeated::NotRepeated
+        ));
+        assert_eq!(
+            device.receive().await.unwrap().src_addr(),
+            TOOL_ADDRESS.clone()
+        );
+    });
+}
+
+#[test]
+fn priority_decides_collisions() {
+    static BUS: SimBus<3> = SimBus::new();
+    common::init();
+    let mut low = BUS.port(0);
+    let mut system = BUS.port(1);
+    let mut listener = BUS.port(2);
+    let group = || Address::Group(GroupAddress::from_parts(1, 1, 1));
+
+    block_on(async {
+        let (low_con, system_con) = join(
+            low.send(frame(
+                IndividualAddress::from_parts(1, 1, 1),
+                group(),
+                Priority::Low,
+            )),
+            system.send(frame(
+                IndividualAddress::from_parts(1, 1, 2),
+                group(),
+                Priority::System,
+            )),
+        )
+        .await;
+        assert!(matches!(low_con, Ok(ConStatus::Ok)));
+        assert!(matches!(system_con, Ok(ConStatus::Ok)));
+
+        let first = listener.receive().await.unwrap();
+        let second = listener.receive().await.unwrap();
+        assert!(matches!(first.priority(), Priority::System));
+        assert!(matches!(second.priority(), Priority::Low));
+    });
+}
+
+#[test]
+fn own_frames_are_echoed() {
+    static BUS: SimBus<2> = SimBus::new();
+    common::init();
+    let mut tool = BUS.port(0);
+    let mut device = BUS.port(1);
+    let group = || Address::Group(GroupAddress::from_parts(1, 1, 1));
+
+    block_on(async {
+        tool.set_echo(true);
+        let sent = frame(TOOL_ADDRESS, group(), Priority::Low);
+        assert!(matches!(tool.send(sent).await, Ok(ConStatus::Ok)));
+        let echo = tool.receive().await.unwrap();
+        assert_eq!(echo.data(), device.receive().await.unwrap().data());
+        assert_eq!(echo.src_addr(), TOOL_ADDRESS);
+
+        // The device doesn't see its own frames
+        let sent = frame(settings::ADDRESS, group(), Priority::Low);
+        assert!(matches!(device.send(sent).await, Ok(ConStatus::Ok)));
+        assert_eq!(tool.receive().await.unwrap().src_addr(), settings::ADDRESS);
+        assert!(matches!(
+            select(device.receive(), embassy_futures::yield_now()).await,
+            Either::Second(())
+        ));
+    });
+}
+
+#[test]
+fn group_read_round_trip() {
+    run_device!({
+        TABLE.load(&GROUP_ADDRESSES).unwrap();
+        ASSOCIATION_TABLE.load(&ASSOCIATIONS).unwrap();
+        OBJECT_TABLE.load(&OBJECTS).unwrap();
+
+        let mut tool = BUS.port(1);
+
+        OBJECT_TABLE
+            .object(1)
+            .set(&DataPoint::B1(B1::new(true)))
+            .unwrap();
+
+        async move {
+            tool.set_address(&TOOL_ADDRESS).await.unwrap();
+            // Not in the group address table, the device doesn't answer
+            let read = frame(
+                TOOL_ADDRESS,
+                Address::Group(GroupAddress::from_parts(1, 1, 99)),
+                Priority::Low,
+            );
+            assert!(matches!(tool.send(read).await, Ok(ConStatus::Ok)));
+            let read = frame(
+                TOOL_ADDRESS,
+                Address::Group(GroupAddress::from_parts(1, 1, 98)),
+                Priority::Low,
+            );
+            assert!(matches!(tool.send(read).await, Ok(ConStatus::Ok)));
+
+            // ASAP 2 is on 1/1/98 as well, but doesn't have the read flag
+            match IND.receive().await {
+                ApplicationServiceInd::GroupValueRead(asap) => assert_eq!(asap, 1),
+                ind => panic!("unexpected indication {:?}", ind),
+            }
+
+            let response = tool.receive().await.unwrap();
+            assert_eq!(response.src_addr(), settings::ADDRESS);
+            assert!(matches!(
+                response.dst_addr(),
+                Address::Group(addr) if addr == GroupAddress::from_parts(1, 1, 98)
+            ));
+            assert_eq!(response.apci(ApciBits::Four), 0x1);
+            assert_eq!(response.data()[7] & 0x3F, 1);
+        }
+    });
+}
+
+#[test]
+fn group_write_sets_object_values() {
+    run_device!({
+        TABLE.load(&GROUP_ADDRESSES).unwrap();
+        // An additional 1 byte object on 1/1/97
+        ASSOCIATION_TABLE
+            .load(&[
+                0x05, 0x01, 0x00, 0x03, 0x01, 0x03, 0x02, 0x02, 0x02, 0x02, 0x03,
+            ])
+            .unwrap();
+        OBJECT_TABLE
+            .load(&[
+                0x04, 0x50, 0x51, 0x4F, 0x00, 0x52, 0x5F, 0x00, 0x53, 0x57, 0x00, 0x54, 0x57, 0x07,
+            ])
+            .unwrap();
+
+        let mut tool = BUS.port(1);
+
+        let write = |dst: GroupAddress, data: DataPoint| {
+            let mut frame = Frame::from_datapoint(&data).unwrap();
+            frame.set_apci(ApciBits::Four, 0x2);
+            frame.set_src_addr(&TOOL_ADDRESS);
+            frame.set_dst_addr(&Address::Group(dst));
+            frame
+        };
+        async move {
+            tool.set_address(&TOOL_ADDRESS).await.unwrap();
+            // ASAP 0 doesn't have the write flag
+            for dst in [
+                GroupAddress::from_parts(1, 1, 96),
+                GroupAddress::from_parts(1, 1, 98),
+            ] {
+                let frame = write(dst, DataPoint::B1(B1::new(true)));
+                assert!(matches!(tool.send(frame).await, Ok(ConStatus::Ok)));
+            }
+            for expected in [1, 2] {
+                match IND.receive().await {
+                    ApplicationServiceInd::GroupValueWrite(asap, value) => {
+                        assert_eq!(asap, expected);
+                        assert_eq!(value, GroupValue::Short(1));
+                    }
+                    ind => panic!("unexpected indication {:?}", ind),
+                }
+            }
+            // The 1 bit ASAP 2 is on 1/1/97 as well, it ignores the byte
+            let frame = write(
+                GroupAddress::from_parts(1, 1, 97),
+                DataPoint::U8(U8::new(0x42)),
+            );
+            assert!(matches!(tool.send(frame).await, Ok(ConStatus::Ok)));
+            match IND.receive().await {
+                ApplicationServiceInd::GroupValueWrite(3, GroupValue::Long(value)) => {
+                    assert_eq!(value, [0x42]);
+                }
+                ind => panic!("unexpected indication {:?}", ind),
+            }
+
+            let object = OBJECT_TABLE.get(0).unwrap();
+            assert_eq!(object.value(), [0]);
+            assert_eq!(object.state(), GroupObjectState::Ok);
+            for asap in [1, 2] {
+                let object = OBJECT_TABLE.get(asap).unwrap();
+                assert_eq!(object.value(), [1]);
+                assert_eq!(object.state(), GroupObjectState::Update);
+            }
+            assert_eq!(OBJECT_TABLE.get(3).unwrap().value(), [0x42]);
+        }
+    });
+}
+
+#[test]
+fn group_objects_are_written_and_read() {
+    run_device!({
+        TABLE.load(&GROUP_ADDRESSES).unwrap();
+        ASSOCIATION_TABLE.load(&ASSOCIATIONS).unwrap();
+        OBJECT_TABLE.load(&OBJECTS).unwrap();
+
+        let mut tool = BUS.port(1);
+        let switch = OBJECT_TABLE.object(1);
+        OBJECT_TABLE
+            .object(2)
+            .set(&DataPoint::B1(B1::new(true)))
+            .unwrap();
+
+        let group_value = |apci: u16, value: bool| {
+            let mut frame = Frame::from_datapoint(&DataPoint::B1(B1::new(value))).unwrap();
+            frame.set_apci(ApciBits::Four, apci);
+            frame.set_src_addr(&TOOL_ADDRESS);
+            frame.set_dst_addr(&Address::Group(GroupAddress::from_parts(1, 1, 98)));
+            frame
+        };
+        async move {
+            tool.set_address(&TOOL_ADDRESS).await.unwrap();
+
+            let (con, frame) =
+                join(switch.write(DataPoint::B1(B1::new(true))), tool.receive()).await;
+            assert_eq!(con.unwrap(), ConStatus::Ok);
+            assert_eq!(frame.unwrap().apci(ApciBits::Four), 0x2);
+            assert_eq!(switch.get().unwrap().value(), [1]);
+
+            // ASAP 1 has no update flag, but the response to its own read updates it. The
+            // write before it isn't taken as the response.
+            let responder = async {
+                let read = tool.receive().await.unwrap();
+                assert_eq!(Apdu::from_frame(&read), Ok(Apdu::GroupValueRead));
+                tool.send(group_value(0x2, true)).await.unwrap();
+                tool.send(group_value(0x1, false)).await.unwrap();
+            };
+            let (value, ()) = join(switch.read(Duration::from_millis(500)), responder).await;
+            assert!(matches!(value, Ok(Some(DataPoint::B1(_)))));
+            assert_eq!(switch.get().unwrap().value(), [0]);
+            // ASAP 2 shares the address, but neither asked nor has the update flag
+            assert_eq!(OBJECT_TABLE.get(2).unwrap().value(), [1]);
+            for _ in 0..3 {
+                IND.receive().await;
+            }
+
+            join(switch.wait_for_update(), async {
+                tool.send(group_value(0x2, true)).await.unwrap();
+            })
+            .await;
+            assert_eq!(switch.get().unwrap().value(), [1]);
+
+            let (value, _) = join(switch.read(Duration::from_millis(50)), tool.receive()).await;
+            assert!(matches!(value, Err(TransferError::TimeoutError(_))));
+            assert_eq!(switch.get().unwrap().state(), GroupObjectState::Ok);
+            // A response after the timeout is treated like any other
+            tool.send(group_value(0x1, false)).await.unwrap();
+            Timer::after_millis(50).await;
+            assert_eq!(switch.get().unwrap().value(), [1]);
+
+            // There is no ASAP 9 to send the read for
+            let missing = OBJECT_TABLE.object(9);
+            assert!(matches!(
+                missing.read(Duration::from_millis(50)).await,
+                Err(TransferError::NotAcknowledged)
+            ));
+        }
+    });
+}
+
+#[test]
+fn abandoned_requests_dont_confirm_the_next() {
+    run_device!({
+        TABLE.load(&GROUP_ADDRESSES).unwrap();
+        ASSOCIATION_TABLE.load(&ASSOCIATIONS).unwrap();
+        OBJECT_TABLE.load(&OBJECTS).unwrap();
+        let mut tool = BUS.port(1);
+        let switch = OBJECT_TABLE.object(1);
+
+        async move {
+            tool.set_address(&TOOL_ADDRESS).await.unwrap();
+            // There is no ASAP 9, the write fails once the caller already gave up on it
+            let missing = OBJECT_TABLE.object(9);
+            assert!(matches!(
+                select(missing.write(DataPoint::B1(B1::new(true))), ready(())).await,
+                Either::Second(())
+            ));
+
+            let (con, frame) =
+                join(switch.write(DataPoint::B1(B1::new(true))), tool.receive()).await;
+            assert_eq!(con.unwrap(), ConStatus::Ok);
+            assert_eq!(frame.unwrap().apci(ApciBits::Four), 0x2);
+        }
+    });
+}
+
+#[test]
+fn connected_data_is_acknowledged() {
+    run_device!({
+        TABLE.load(&GROUP_ADDRESSES).unwrap();
+        ASSOCIATION_TABLE.load(&ASSOCIATIONS).unwrap();
+        OBJECT_TABLE.load(&OBJECTS).unwrap();
+
+        let mut tool = BUS.port(1);
+
+        async move {
+            tool.set_address(&TOOL_ADDRESS).await.unwrap();
+            let connect = individual_frame(settings::ADDRESS, 0x80);
+            assert!(matches!(tool.send(connect).await, Ok(ConStatus::Ok)));
+
+            let data = individual_frame(settings::ADDRESS, 0x40);
+            assert!(matches!(tool.send(data).await, Ok(ConStatus::Ok)));
+
+            let ack = tool.receive().await.unwrap();
+            assert_eq!(ack.src_addr(), settings::ADDRESS);
+            assert_eq!(ack.tpci(TpciBits::Eight) & 0xC3, 0xC2);
+            assert_eq!(ack.tpci_seq(), 0);
+        }
+    });
+}
+
+#[test]
+fn connection_follows_the_sequence_numbers() {
+    run_device!({
+        let mut tool = BUS.port(1);
+
+        async move {
+            tool.set_address(&TOOL_ADDRESS).await.unwrap();
+            let connect = individual_frame(settings::ADDRESS, 0x80);
+            assert!(matches!(tool.send(connect).await, Ok(ConStatus::Ok)));
+            assert!(matches!(
+                IND.receive().await,
+                ApplicationServiceInd::Connect(a) if a == TOOL_ADDRESS
+            ));
+
+            // The sequence number has 4 bits
+            for seq in 0..10 {
+                let data = individual_frame(settings::ADDRESS, 0x40 | (seq & 0xF) << 2);
+                assert!(matches!(tool.send(data).await, Ok(ConStatus::Ok)));
+                let ack = tool.receive().await.unwrap();
+                assert_eq!(ack.tpci(TpciBits::Eight) & 0xC3, 0xC2);
+                assert_eq!(ack.tpci_seq(), seq & 0xF);
+                assert!(matches!(
+                    IND.receive().await,
+                    ApplicationServiceInd::DataConnected(_)
+                ));
+            }
+
+            // A repetition is acknowledged again, an unexpected number is not
+            let data = individual_frame(settings::ADDRESS, 0x40 | 9 << 2);
+            assert!(matches!(tool.send(data).await, Ok(ConStatus::Ok)));
+            let ack = tool.receive().await.unwrap();
+            assert_eq!(ack.tpci(TpciBits::Eight), 0xC2 | 9 << 2);
+            let data = individual_frame(settings::ADDRESS, 0x40 | 3 << 2);
+            assert!(matches!(tool.send(data).await, Ok(ConStatus::Ok)));
+            let nak = tool.receive().await.unwrap();
+            assert_eq!(nak.tpci(TpciBits::Eight), 0xC3 | 3 << 2);
+
+            // After the disconnect, data is rejected
+            let disconnect = individual_frame(settings::ADDRESS, 0x81);
+            assert!(matches!(tool.send(disconnect).await, Ok(ConStatus::Ok)));
+            // The repetition and the unexpected frame weren't indicated
+            assert!(matches!(
+                IND.receive().await,
+                ApplicationServiceInd::Disconnect(a) if a == TOOL_ADDRESS
+            ));
+            let data = individual_frame(settings::ADDRESS, 0x40 | 10 << 2);
+            assert!(matches!(tool.send(data).await, Ok(ConStatus::Ok)));
+            assert_eq!(tool.receive().await.unwrap().tpci(TpciBits::Eight), 0x81);
+        }
+    });
+}
+
+#[test]
+fn requests_dont_interrupt_the_connection() {
+    run_device!({
+        let mut tool = BUS.port(1);
+
+        async move {
+            tool.set_address(&TOOL_ADDRESS).await.unwrap();
+            let connect = individual_frame(settings::ADDRESS, 0x80);
+            assert!(matches!(tool.send(connect).await, Ok(ConStatus::Ok)));
+            assert!(matches!(
+                IND.receive().await,
+                ApplicationServiceInd::Connect(a) if a == TOOL_ADDRESS
+            ));
+
+            // A request comes in while the T_ACK is being sent
+            let data = individual_frame(settings::ADDRESS, 0x40);
+            assert!(matches!(tool.send(data).await, Ok(ConStatus::Ok)));
+            let write = GroupWriteRequest::new(0, DataPoint::B1(B1::new(true)), Priority::Low);
+            RES.send(ApplicationServiceRes::GroupValueWrite(write))
+                .await;
+            let ack = tool.receive().await.unwrap();
+            assert_eq!(ack.tpci(TpciBits::Eight), 0xC2);
+            let mut indicated = (false, false);
+            for _ in 0..2 {
+                match IND.receive().await {
+                    ApplicationServiceInd::DataConnected(_) => indicated.0 = true,
+                    ApplicationServiceInd::GroupValueWriteCon(0, _) => indicated.1 = true,
+                    ind => panic!("unexpected indication {:?}", ind),
+                }
+            }
+            assert_eq!(indicated, (true, true));
+        }
+    });
+}
+
+#[test]
+fn idle_connection_is_closed() {
+    run_device!({
+        let mut tool = BUS.port(1);
+
+        async move {
+            tool.set_address(&TOOL_ADDRESS).await.unwrap();
+            let connect = individual_frame(settings::ADDRESS, 0x80);
+            assert!(matches!(tool.send(connect).await, Ok(ConStatus::Ok)));
+            assert!(matches!(
+                IND.receive().await,
+                ApplicationServiceInd::Connect(_)
+            ));
+            let data = individual_frame(settings::ADDRESS, 0x40);
+            assert!(matches!(tool.send(data).await, Ok(ConStatus::Ok)));
+            assert_eq!(tool.receive().await.unwrap().tpci(TpciBits::Eight), 0xC2);
+            assert!(matches!(
+                IND.receive().await,
+                ApplicationServiceInd::DataConnected(_)
+            ));
+
+            // Six seconds after the last frame the device hangs up
+            let start = Instant::now();
+            let disconnect = tool.receive().await.unwrap();
+            assert!(start.elapsed() >= Duration::from_secs(5));
+            assert!(matches!(disconnect.dst_addr(), Address::Individual(a) if a == TOOL_ADDRESS));
+            assert_eq!(disconnect.tpci(TpciBits::Eight), 0x81);
+            assert!(matches!(
+                IND.receive().await,
+                ApplicationServiceInd::Disconnect(a) if a == TOOL_ADDRESS
+            ));
+        }
+    });
+}
+
+#[test]
+fn connected_data_is_repeated_until_acknowledged() {
+    run_transport!(transport, tool, {
+        let data = || {
+            frame(
+                settings::ADDRESS,
+                Address::Group(GroupAddress::new(0)),
+                Priority::Low,
+            )
+        };
+
+        async move {
+            tool.set_address(&TOOL_ADDRESS).await.unwrap();
+            // Nothing is sent without a connection
+            let con = transport
+                .send(TransportServiceReq::DataConnected(data()))
+                .await;
+            assert!(matches!(con, Ok(ConStatus::NotOk)));
+
+            let con = transport
+                .send(TransportServiceReq::Connect(TOOL_ADDRESS))
+                .await;
+            assert!(matches!(con, Ok(ConStatus::Ok)));
+            let connect = tool.receive().await.unwrap();
+            assert!(matches!(connect.dst_addr(), Address::Individual(a) if a == TOOL_ADDRESS));
+            assert_eq!(connect.tpci(TpciBits::Eight), 0x80);
+
+            let con = transport
+                .send(TransportServiceReq::DataConnected(data()))
+                .await;
+            assert!(matches!(con, Ok(ConStatus::Ok)));
+            let sent = tool.receive().await.unwrap();
+            assert_eq!(sent.tpci(TpciBits::Eight) & 0xC0, 0x40);
+            assert_eq!(sent.tpci_seq(), 0);
+            assert!(matches!(sent.dst_addr(), Address::Individual(a) if a == TOOL_ADDRESS));
+
+            // A NAK has the frame repeated right away, the ACK confirms it
+            let nak = individual_frame(settings::ADDRESS, 0xC3);
+            assert!(matches!(tool.send(nak).await, Ok(ConStatus::Ok)));
+            let (ind, ()) = join(transport.receive(), async {
+                let repeated = tool.receive().await.unwrap();
+                assert_eq!(repeated.data(), sent.data());
+                let ack = individual_frame(settings::ADDRESS, 0xC2);
+                assert!(matches!(tool.send(ack).await, Ok(ConStatus::Ok)));
+            })
+            .await;
+            assert!(matches!(
+                ind.unwrap().as_slice(),
+                [TransportServiceInd::DataConnectedCon(ConStatus::Ok)]
+            ));
+
+            let con = transport
+                .send(TransportServiceReq::DataConnected(data()))
+                .await;
+            assert!(matches!(con, Ok(ConStatus::Ok)));
+            assert_eq!(tool.receive().await.unwrap().tpci_seq(), 1);
+
+            let con = transport.send(TransportServiceReq::Disconnect).await;
+            assert!(matches!(con, Ok(ConStatus::Ok)));
+            assert_eq!(tool.receive().await.unwrap().tpci(TpciBits::Eight), 0x81);
+        }
+    });
+}
+
+#[test]
+fn unacknowledged_data_ends_the_connection() {
+    run_transport!(transport, tool, {
+        async move {
+            tool.set_address(&TOOL_ADDRESS).await.unwrap();
+            let con = transport
+                .send(TransportServiceReq::Connect(TOOL_ADDRESS))
+                .await;
+            assert!(matches!(con, Ok(ConStatus::Ok)));
+            assert_eq!(tool.receive().await.unwrap().tpci(TpciBits::Eight), 0x80);
+            let data = frame(
+                settings::ADDRESS,
+                Address::Group(GroupAddress::new(0)),
+                Priority::Low,
+            );
+            let con = transport
+                .send(TransportServiceReq::DataConnected(data))
+                .await;
+            assert!(matches!(con, Ok(ConStatus::Ok)));
+            let sent = tool.receive().await.unwrap();
+            let mut last = Instant::now();
+
+            // The peer stays silent, the frame is repeated every 3 s, 3 times
+            let (ind, ()) = join(transport.receive(), async {
+                for _ in 0..3 {
+                    let repeated = tool.receive().await.unwrap();
+                    assert_eq!(repeated.data(), sent.data());
+                    assert!(last.elapsed() >= Duration::from_secs(3));
+                    last = Instant::now();
+                }
+                let disconnect = tool.receive().await.unwrap();
+                assert_eq!(disconnect.tpci(TpciBits::Eight), 0x81);
+                assert!(last.elapsed() >= Duration::from_secs(3));
+            })
+            .await;
+            assert!(matches!(
+                ind.unwrap().as_slice(),
+                [TransportServiceInd::Disconnect(a)] if *a == TOOL_ADDRESS
+            ));
+        }
+    });
+}
+
+#[test]
+fn requests_wait_for_events_on_the_connection() {
+    run_transport!(transport, tool, {
+        async move {
+            tool.set_address(&TOOL_ADDRESS).await.unwrap();
+            // The events are waited for while the T_Connect is still being sent
+            let (con, ind, ()) = join3(
+                transport.send(TransportServiceReq::Connect(TOOL_ADDRESS)),
+                transport.receive(),
+                async {
+                    assert_eq!(tool.receive().await.unwrap().tpci(TpciBits::Eight), 0x80);
+                    let disconnect = individual_frame(settings::ADDRESS, 0x81);
+                    assert!(matches!(tool.send(disconnect).await, Ok(ConStatus::Ok)));
+                },
+            )
+            .await;
+            assert!(matches!(con, Ok(ConStatus::Ok)));
+            assert!(matches!(
+                ind.unwrap().as_slice(),
+                [TransportServiceInd::Disconnect(a)] if *a == TOOL_ADDRESS
+            ));
+        }
+    });
+}
+
+#[test]
+fn connectionless_data_is_addressed() {
+    run_transport!(transport, tool, {
+        // A_DeviceDescriptor_Response with descriptor type 0
+        let data = || {
+            let mut frame = Frame::from_datapoint(&DataPoint::U16(U16::new(0x07B0))).unwrap();
+            frame.set_apci(ApciBits::Ten, 0x340);
+            frame.set_priority(Priority::System);
+            frame.set_hop_count(6);
+            frame
+        };
+
+        async move {
+            tool.set_address(&TOOL_ADDRESS).await.unwrap();
+            let con = transport
+                .send(TransportServiceReq::DataIndividual(TOOL_ADDRESS, data()))
+                .await;
+            assert!(matches!(con, Ok(ConStatus::Ok)));
+            let sent = tool.receive().await.unwrap();
+            assert_eq!(sent.src_addr(), settings::ADDRESS);
+            assert!(matches!(sent.dst_addr(), Address::Individual(a) if a == TOOL_ADDRESS));
+            assert_eq!(sent.tpci(TpciBits::Eight) & 0xFC, 0x00);
+            assert_eq!(sent.apdu_data(), data().apdu_data());
+
+            for req in [
+                TransportServiceReq::DataBroadcast(data()),
+                TransportServiceReq::DataSystemBroadcast(data()),
+            ] {
+                assert!(matches!(transport.send(req).await, Ok(ConStatus::Ok)));
+                let sent = tool.receive().await.unwrap();
+                assert!(matches!(sent.dst_addr(), Address::Group(a) if a == GroupAddress::new(0)));
+                assert_eq!(sent.tpci(TpciBits::Eight) & 0xFC, 0x00);
+                assert_eq!(sent.apdu_data(), data().apdu_data());
+            }
+
+            // The peer's T_Data_Individual is indicated
+            let (ind, con) = join(
+                transport.receive(),
+                tool.send(individual_frame(settings::ADDRESS, 0x00)),
+            )
+            .await;
+            assert!(matches!(con, Ok(ConStatus::Ok)));
+            assert!(matches!(
+                ind.unwrap().as_slice(),
+                [TransportServiceInd::DataIndividual(frame)] if frame.src_addr() == TOOL_ADDRESS
+            ));
+        }
+    });
+}
+
+#[test]
+fn application_answers_connectionless_data() {
+    run_device!({
+        let mut tool = BUS.port(1);
+        let response = Apdu::DeviceDescriptorResponse {
+            descriptor_type: 0,
+            descriptor: &[0x07, 0xB0],
+        };
+
+        async move {
+            tool.set_address(&TOOL_ADDRESS).await.unwrap();
+            let mut read = Apdu::DeviceDescriptorRead { descriptor_type: 0 }
+                .to_frame()
+                .unwrap();
+            read.set_src_addr(&TOOL_ADDRESS);
+            read.set_dst_addr(&Address::Individual(settings::ADDRESS));
+            assert!(matches!(tool.send(read).await, Ok(ConStatus::Ok)));
+            let ApplicationServiceInd::DataIndividual(frame) = IND.receive().await else {
+                panic!("no T_Data_Individual");
+            };
+            assert_eq!(frame.src_addr(), TOOL_ADDRESS);
+            assert_eq!(
+                Apdu::from_frame(&frame),
+                Ok(Apdu::DeviceDescriptorRead { descriptor_type: 0 })
+            );
+
+            let answer = response.to_frame().unwrap();
+            RES.send(ApplicationServiceRes::DataIndividual(TOOL_ADDRESS, answer))
+                .await;
+            let sent = tool.receive().await.unwrap();
+            assert!(matches!(sent.dst_addr(), Address::Individual(a) if a == TOOL_ADDRESS));
+            assert_eq!(Apdu::from_frame(&sent), Ok(response.clone()));
+            assert!(matches!(
+                IND.receive().await,
+                ApplicationServiceInd::DataIndividualCon(a, Ok(ConStatus::Ok)) if a == TOOL_ADDRESS
+            ));
+
+            // Broadcasts other than the address programming reach the application
+            let serial_number = [0, 0x83, 1, 2, 3, 4];
+            let read = broadcast_frame(Apdu::IndividualAddressSerialNumberRead { serial_number });
+            assert!(matches!(tool.send(read).await, Ok(ConStatus::Ok)));
+            let ApplicationServiceInd::DataBroadcast(frame) = IND.receive().await else {
+                panic!("no T_Data_Broadcast");
+            };
+            assert_eq!(
+                Apdu::from_frame(&frame),
+                Ok(Apdu::IndividualAddressSerialNumberRead { serial_number })
+            );
+
+            RES.send(ApplicationServiceRes::DataBroadcast(
+                response.to_frame().unwrap(),
+            ))
+            .await;
+            let sent = tool.receive().await.unwrap();
+            assert!(matches!(sent.dst_addr(), Address::Group(a) if a == GroupAddress::new(0)));
+            assert!(matches!(
+                IND.receive().await,
+                ApplicationServiceInd::DataBroadcastCon(Ok(ConStatus::Ok))
+            ));
+        }
+    });
+}
+
+#[test]
+fn application_opens_connections() {
+    run_device!({
+        let mut tool = BUS.port(1);
+
+        async move {
+            tool.set_address(&TOOL_ADDRESS).await.unwrap();
+            RES.send(ApplicationServiceRes::Connect(TOOL_ADDRESS)).await;
+            assert_eq!(tool.receive().await.unwrap().tpci(TpciBits::Eight), 0x80);
+            assert!(matches!(
+                IND.receive().await,
+                ApplicationServiceInd::ConnectCon(a, Ok(ConStatus::Ok)) if a == TOOL_ADDRESS
+            ));
+
+            // The request is confirmed with the peer's acknowledgement
+            let mut request = frame(
+                settings::ADDRESS,
+                Address::Individual(TOOL_ADDRESS),
+                Priority::Low,
+            );
+            request.set_apci(ApciBits::Four, 0x2);
+            RES.send(ApplicationServiceRes::DataConnected(request))
+                .await;
+            let sent = tool.receive().await.unwrap();
+            assert_eq!(sent.tpci(TpciBits::Eight) & 0xFC, 0x40);
+            assert_eq!(sent.apdu_data(), [0x80]);
+            let ack = individual_frame(settings::ADDRESS, 0xC2);
+            assert!(matches!(tool.send(ack).await, Ok(ConStatus::Ok)));
+            assert!(matches!(
+                IND.receive().await,
+                ApplicationServiceInd::DataConnectedCon(Ok(ConStatus::Ok))
+            ));
+
+            // The peer's answer is acknowledged and indicated
+            let response = individual_frame(settings::ADDRESS, 0x40);
+            assert!(matches!(tool.send(response).await, Ok(ConStatus::Ok)));
+            assert_eq!(tool.receive().await.unwrap().tpci(TpciBits::Eight), 0xC2);
+            assert!(matches!(
+                IND.receive().await,
+                ApplicationServiceInd::DataConnected(frame) if frame.src_addr() == TOOL_ADDRESS
+            ));
+
+            RES.send(ApplicationServiceRes::Disconnect).await;
+            assert_eq!(tool.receive().await.unwrap().tpci(TpciBits::Eight), 0x81);
+            assert!(matches!(
+                IND.receive().await,
+                ApplicationServiceInd::DisconnectCon(Ok(ConStatus::Ok))
+            ));
+        }
+    });
+}
+
+/// A broadcast from the tool.
+fn broadcast_frame(apdu: Apdu) -> Frame {
+    let mut frame = apdu.to_frame().unwrap();
+    frame.set_priority(Priority::System);
+    frame.set_hop_count(6);
+    frame.set_src_addr(&TOOL_ADDRESS);
+    frame.set_dst_addr(&Address::Group(GroupAddress::new(0)));
+    frame
+}
+
+#[test]
+fn individual_address_is_programmed() {
+    run_device!({
+        const NEW_ADDRESS: IndividualAddress = IndividualAddress::from_parts(1, 1, 50);
+
+        let mut tool = BUS.port(1);
+
+        async move {
+            tool.set_address(&TOOL_ADDRESS).await.unwrap();
+            // Outside of programming mode the read isn't answered, the ACK comes first
+            let read = broadcast_frame(Apdu::IndividualAddressRead);
+            assert!(matches!(tool.send(read).await, Ok(ConStatus::Ok)));
+            let connect = individual_frame(settings::ADDRESS, 0x80);
+            assert!(matches!(tool.send(connect).await, Ok(ConStatus::Ok)));
+            let data = individual_frame(settings::ADDRESS, 0x40);
+            assert!(matches!(tool.send(data).await, Ok(ConStatus::Ok)));
+            assert_eq!(tool.receive().await.unwrap().tpci(TpciBits::Eight), 0xC2);
+            let disconnect = individual_frame(settings::ADDRESS, 0x81);
+            assert!(matches!(tool.send(disconnect).await, Ok(ConStatus::Ok)));
+            for _ in 0..3 {
+                IND.receive().await;
+            }
+
+            PROGRAMMING_MODE.set(true);
+            let read = broadcast_frame(Apdu::IndividualAddressRead);
+            assert!(matches!(tool.send(read).await, Ok(ConStatus::Ok)));
+            let response = tool.receive().await.unwrap();
+            assert_eq!(response.src_addr(), settings::ADDRESS);
+            assert!(matches!(response.dst_addr(), Address::Group(a) if a == GroupAddress::new(0)));
+            assert_eq!(
+                Apdu::from_frame(&response),
+                Ok(Apdu::IndividualAddressResponse)
+            );
+
+            let write = broadcast_frame(Apdu::IndividualAddressWrite(NEW_ADDRESS));
+            assert!(matches!(tool.send(write).await, Ok(ConStatus::Ok)));
+            assert!(matches!(
+                IND.receive().await,
+                ApplicationServiceInd::IndividualAddressWrite(a) if a == NEW_ADDRESS
+            ));
+
+            // Only the new address is acknowledged and served
+            let connect = individual_frame(settings::ADDRESS, 0x80);
+            assert!(matches!(tool.send(connect).await, Ok(ConStatus::NotOk)));
+            let connect = individual_frame(NEW_ADDRESS, 0x80);
+            assert!(matches!(tool.send(connect).await, Ok(ConStatus::Ok)));
+            assert!(matches!(
+                IND.receive().await,
+                ApplicationServiceInd::Connect(a) if a == TOOL_ADDRESS
+            ));
+            let data = individual_frame(NEW_ADDRESS, 0x40);
+            assert!(matches!(tool.send(data).await, Ok(ConStatus::Ok)));
+            let ack = tool.receive().await.unwrap();
+            assert_eq!(ack.src_addr(), NEW_ADDRESS);
+            assert_eq!(ack.tpci(TpciBits::Eight), 0xC2);
+        }
+    });
+}
+
+#[test]
+fn group_write_is_confirmed() {
+    run_device!({
+        TABLE.load(&GROUP_ADDRESSES).unwrap();
+        ASSOCIATION_TABLE.load(&ASSOCIATIONS).unwrap();
+        OBJECT_TABLE.load(&OBJECTS).unwrap();
+
+        let write = || {
+            ApplicationServiceRes::GroupValueWrite(GroupWriteRequest::new(
+                2,
+                DataPoint::B1(B1::new(true)),
+                Priority::Low,
+            ))
+        };
+        async move {
+            // Nobody on the line acknowledges the first write
+            RES.send(write()).await;
+            match IND.receive().await {
+                ApplicationServiceInd::GroupValueWriteCon(2, con) => {
+                    assert_eq!(con.unwrap(), ConStatus::NotOk)
+                }
+                ind => panic!("unexpected indication {:?}", ind),
+            }
+
+            let mut tool = BUS.port(1);
+            RES.send(write()).await;
+            let (con, frame) = join(IND.receive(), tool.receive()).await;
+            match con {
+                ApplicationServiceInd::GroupValueWriteCon(2, con) => {
+                    assert_eq!(con.unwrap(), ConStatus::Ok)
+                }
+                ind => panic!("unexpected indication {:?}", ind),
+            }
+            let frame = frame.unwrap();
+            assert!(matches!(
+                frame.dst_addr(),
+                Address::Group(addr) if addr == GroupAddress::from_parts(1, 1, 98)
+            ));
+            assert_eq!(frame.apci(ApciBits::Four), 0x2);
+            assert_eq!(frame.apdu_data()[0] & 0x3F, 0x01);
+        }
+    });
+}